@@ -73,3 +73,12 @@ def get_sim_log(db: Database, tournament_id, left_team, right_team) -> List[str]
     if data is not None:
         data = json.loads(data)
     return data
+
+
+def get_match_svg_key(tournament_id, left_team, right_team):
+    return f'brains.match-svg:{tournament_id}-{left_team}-{right_team}'
+
+
+def get_match_svg(db: Database, tournament_id, left_team, right_team) -> Optional[str]:
+    key = get_match_svg_key(tournament_id, left_team, right_team)
+    return db.get(key)
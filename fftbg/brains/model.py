@@ -73,11 +73,18 @@ class SimulatorModel(Model):
         self.baked = baked
 
     def predict_match_up(self, match_up: MatchUp, patch_date: datetime) -> float:
+        patch_obj, arena_obj, match_up_json = self._simulator_inputs(match_up, patch_date)
+        left_wins = fftbg.simulator.run_simulation(patch_obj, arena_obj, match_up_json, self.num_runs)
+        LOG.info(f'sim_left_wins = {left_wins:.4f}')
+        return self.baked.predict_match_up(match_up, patch_date, left_wins)
+
+    def render_match_svg(self, match_up: MatchUp, patch_date: datetime) -> str:
+        patch_obj, arena_obj, match_up_json = self._simulator_inputs(match_up, patch_date)
+        return fftbg.simulator.render_match_svg(patch_obj, arena_obj, match_up_json)
+
+    def _simulator_inputs(self, match_up: MatchUp, patch_date: datetime):
         patch_json = patch.get_patch(patch_date).to_json()
         patch_obj = fftbg.simulator.Patch(patch_json)
-        match_up_json = match_up.to_json()
         arena_json = Path(f'data/arena/MAP{match_up.game_map_num:03d}.json').read_text()
         arena_obj = fftbg.simulator.Arena(arena_json)
-        left_wins = fftbg.simulator.run_simulation(patch_obj, arena_obj, match_up_json, self.num_runs)
-        LOG.info(f'sim_left_wins = {left_wins:.4f}')
-        return self.baked.predict_match_up(match_up, patch_date, left_wins)
+        return patch_obj, arena_obj, match_up.to_json()
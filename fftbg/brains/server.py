@@ -12,7 +12,8 @@ import fftbg.server
 import fftbg.tournament
 import fftbg.twitch.msg_types as msg_types
 from fftbg.brains.api import CURRENT_TOURNAMENT_KEY, CURRENT_MATCH_KEY, get_current_tournament_id, get_predictions_key, \
-    get_predictions, get_importance_key, get_map_key, get_sim_log_key, get_prediction_key, get_prediction
+    get_predictions, get_importance_key, get_map_key, get_sim_log_key, get_prediction_key, get_prediction, \
+    get_match_svg_key
 from fftbg.brains.model import BakedModel, SimulatorModel, Model
 from fftbg.brains.msg_types import NEW_PREDICTIONS
 from fftbg.brains.predictions import Predictions
@@ -85,6 +86,12 @@ def set_map(db: Database, tournament_id, match_up: MatchUp):
     db.set(key, match_up.game_map)
 
 
+def post_match_svg(db: Database, model: SimulatorModel, tournament_id, match_up: MatchUp, patch_time):
+    svg = model.render_match_svg(match_up, patch_time)
+    key = get_match_svg_key(tournament_id, match_up.left.color, match_up.right.color)
+    db.set(key, svg)
+
+
 def post_importance(db: Database, model, tournament_id, match_up: MatchUp, patch_time):
     LOG.info(f'Computing importance for {tournament_id}, {match_up.left.color} vs {match_up.right.color}')
     importance = fftbg.brains.importance.compute(model, match_up, patch_time)
@@ -129,6 +136,7 @@ def run_server():
                 match_up = tournament.match_ups[idx]
 
                 set_map(db, tournament.id, match_up)
+                post_match_svg(db, sim_model, tournament.id, match_up, tournament.modified)
                 # post_sim_log(db, model, tournament.id, match_up, tournament.modified)
                 post_importance(db, baked_model, tournament.id, match_up, tournament.modified)
                 set_current_match(db, left_team, right_team)
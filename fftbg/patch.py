@@ -1,4 +1,4 @@
-from dataclasses import dataclass
+from dataclasses import dataclass, field
 from datetime import datetime
 from pathlib import Path
 from typing import List, Dict
@@ -25,6 +25,7 @@ class Patch:
     ability: AbilityData
     equipment: EquipmentData
     base_stats: BaseStatsData
+    status_durations: Dict[str, int] = field(default_factory=dict)
 
     def get_ability(self, name: str) -> Ability:
         return self.ability.get_ability(name)
@@ -60,6 +61,19 @@ def get_patch(when: datetime):
     raise Exception(f'Unable to find patch directory for {str(when)}')
 
 
+def parse_status_durations(status_durations_path: Path) -> Dict[str, int]:
+    """Reads lines like `Haste: 24` for the statuses this patch changed the duration of."""
+    if not status_durations_path.exists():
+        return {}
+    durations = {}
+    for line in status_durations_path.read_text().splitlines():
+        if not line.strip():
+            continue
+        name, duration = line.split(':')
+        durations[name.strip()] = int(duration)
+    return durations
+
+
 def get_patch_from_file(patch_dir: Path, time: datetime):
     if patch_dir.name in PATCH_MAP:
         return PATCH_MAP[patch_dir.name]
@@ -67,8 +81,10 @@ def get_patch_from_file(patch_dir: Path, time: datetime):
     base_stat_data = base_stats.parse_base_stats(patch_dir / 'classhelp.txt', patch_dir / 'MonsterSkills.txt')
     ability_data = ability.parse_abilities(patch_dir / 'infoability.txt')
     equipment_data = equipment.parse_equipment(patch_dir / 'infoitem.txt')
+    status_durations = parse_status_durations(patch_dir / 'status_durations.txt')
     PATCH_MAP[patch_dir.name] = Patch(time=time,
                                       ability=ability_data,
                                       equipment=equipment_data,
-                                      base_stats=base_stat_data)
+                                      base_stats=base_stat_data,
+                                      status_durations=status_durations)
     return PATCH_MAP[patch_dir.name]
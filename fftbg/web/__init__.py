@@ -5,9 +5,10 @@ from pathlib import Path
 
 import numpy as np
 import pandas
-from flask import Flask, render_template
+from flask import Flask, Response, render_template
 
 import fftbg.brains.api
+import fftbg.simulator
 from fftbg.bird.memory import Memory
 from fftbg.server import get_redis
 
@@ -109,6 +110,23 @@ def get_sim_log():
     return json.dumps(log)
 
 
+@app.route('/sim-log/svg')
+def get_match_svg():
+    db = get_redis()
+    tournament_id = fftbg.brains.api.get_current_tournament_id(db)
+    left_team, right_team = fftbg.brains.api.get_current_match(db)
+    svg = fftbg.brains.api.get_match_svg(db, tournament_id, left_team, right_team)
+    if svg is None:
+        return Response(status=404)
+    return Response(svg, mimetype='image/svg+xml')
+
+
 @app.route('/map/<int:map_id>')
 def get_map(map_id: int):
     return Path(f'data/arena/MAP{map_id:03d}.json').read_text()
+
+
+@app.route('/map/<int:map_id>/svg')
+def get_map_svg(map_id: int):
+    arena = fftbg.simulator.Arena(get_map(map_id))
+    return Response(fftbg.simulator.render_arena_svg(arena), mimetype='image/svg+xml')
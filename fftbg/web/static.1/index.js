@@ -473,6 +473,7 @@ const TeamSummary = {
                 m('h6.text-center', 'Use left and right arrow keys to rotate map.'),
                 m('h6.text-center', {id: 'surface-type-display'}, 'Mouse over a surface to display the surface\'s type here.'),
                 m(MapViewer, {map_num}),
+                m('img.mx-auto.d-block', {src: '/sim-log/svg', onerror: e => e.target.remove()}),
             ])),
             m('.row', m('.col-md', m('h4.text-center', 'Team Summary'))),
             m('.row', [
@@ -0,0 +1,674 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use bincode;
+use memmap::Mmap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::data;
+use crate::dto::rust::{MatchUp, Patch};
+use crate::dto::schema;
+use crate::dto::schema::Schema;
+
+// Layout:
+//   magic (8 bytes) | version (u32 LE) | index offset (u64 LE)
+//   patch & match up records, each one encoded by `dto::schema`, back to back
+//   the index, a bincode blob starting at the index offset
+// Appending writes the new records & then the new index after everything already in the file,
+// and only then points the header at the new index. Until that last write the header still
+// points at the old index, which is left untouched, so stopping part way through loses the
+// new records but never the archive. bincode ignores whatever follows the index it decodes.
+// Each append leaves the old index behind as dead space; `compact_archive` drops it.
+//
+// Each record carries its own schema version, see `dto::schema`. Older records still decode,
+// and `migrate_archive` rewrites them all in the current schema.
+
+pub const ARCHIVE_PATH: &str = "data/sim.archive";
+pub const ARCHIVE_VERSION: u32 = 2;
+
+const MAGIC: &[u8; 8] = b"BIRBSIM\0";
+const TEAM_COLORS: [&str; 9] = [
+    "red", "blue", "green", "yellow", "white", "black", "purple", "brown", "champion",
+];
+const HEADER_LEN: u64 = 20;
+const INDEX_OFFSET_POS: u64 = 12;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PatchEntry {
+    pub time: u64,
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct MatchEntry {
+    pub match_id: u64,
+    pub tournament_id: u64,
+    pub patch_time: u64,
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ArchiveIndex {
+    patches: Vec<PatchEntry>,
+    matches: Vec<MatchEntry>,
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    bincode::deserialize(bytes).map_err(invalid_data)
+}
+
+fn encode<T: Serialize>(val: &T) -> io::Result<Vec<u8>> {
+    bincode::serialize(val).map_err(invalid_data)
+}
+
+/// Checks the header, returning where the index starts.
+fn read_header(header: &[u8]) -> io::Result<u64> {
+    if header.len() < HEADER_LEN as usize || &header[0..8] != MAGIC {
+        return Err(invalid_data("not a match archive"));
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&header[8..12]);
+    let version = u32::from_le_bytes(version);
+    if version != ARCHIVE_VERSION {
+        return Err(invalid_data(format!(
+            "match archive is version {}, expected version {}, re-run `simulator archive`",
+            version, ARCHIVE_VERSION
+        )));
+    }
+    let mut index_offset = [0; 8];
+    index_offset.copy_from_slice(&header[12..20]);
+    Ok(u64::from_le_bytes(index_offset))
+}
+
+/// The ID a match up from the feed is stored under, worked out from its tournament & the two
+/// teams playing so that feeding the same match up twice always lands on the same ID. These
+/// sit well above the sequential IDs of match ups converted from the old directory.
+pub fn stable_match_id(tournament_id: u64, left_color: &str, right_color: &str) -> Option<u64> {
+    let left = TEAM_COLORS.iter().position(|c| *c == left_color)? as u64;
+    let right = TEAM_COLORS.iter().position(|c| *c == right_color)? as u64;
+    tournament_id
+        .checked_mul(100)?
+        .checked_add(left * 10 + right)
+}
+
+/// Read only access to an archive, memory mapped so that looking up a single match up only
+/// touches the bytes it needs.
+pub struct Archive {
+    mmap: Mmap,
+    index: ArchiveIndex,
+    by_match_id: HashMap<u64, usize>,
+}
+
+impl Archive {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Archive> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        let index_offset = read_header(&mmap)?;
+        let index_offset = index_offset as usize;
+        if index_offset < HEADER_LEN as usize || index_offset > mmap.len() {
+            return Err(invalid_data("match archive index is missing"));
+        }
+        let index: ArchiveIndex = decode(&mmap[index_offset..])?;
+        let by_match_id = index
+            .matches
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.match_id, i))
+            .collect();
+        Ok(Archive {
+            mmap,
+            index,
+            by_match_id,
+        })
+    }
+
+    pub fn patches(&self) -> &[PatchEntry] {
+        &self.index.patches
+    }
+
+    pub fn matches(&self) -> &[MatchEntry] {
+        &self.index.matches
+    }
+
+    pub fn match_entry(&self, match_id: u64) -> Option<&MatchEntry> {
+        self.by_match_id
+            .get(&match_id)
+            .map(|i| &self.index.matches[*i])
+    }
+
+    pub fn tournament(&self, tournament_id: u64) -> impl Iterator<Item = &MatchEntry> {
+        self.index
+            .matches
+            .iter()
+            .filter(move |entry| entry.tournament_id == tournament_id)
+    }
+
+    fn record<T: Schema>(&self, offset: u64, len: u64) -> io::Result<T> {
+        let start = offset as usize;
+        let end = start + len as usize;
+        if end > self.mmap.len() {
+            return Err(invalid_data("match archive record out of bounds"));
+        }
+        schema::decode(&self.mmap[start..end])
+    }
+
+    /// Whether every record is already in the current schema.
+    fn is_current(&self) -> io::Result<bool> {
+        let entries = self
+            .index
+            .patches
+            .iter()
+            .map(|entry| (entry.offset, entry.len));
+        let entries = entries.chain(self.index.matches.iter().map(|e| (e.offset, e.len)));
+        for (offset, len) in entries {
+            let start = offset as usize;
+            let end = start + len as usize;
+            if end > self.mmap.len() {
+                return Err(invalid_data("match archive record out of bounds"));
+            }
+            if schema::version_of(&self.mmap[start..end])? != schema::SCHEMA_VERSION {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn read_match(&self, entry: &MatchEntry) -> io::Result<MatchUp> {
+        self.record(entry.offset, entry.len)
+    }
+
+    pub fn read_patch(&self, time: u64) -> io::Result<Patch> {
+        let entry = self
+            .index
+            .patches
+            .iter()
+            .find(|entry| entry.time == time)
+            .ok_or_else(|| invalid_data(format!("patch {} missing from archive", time)))?;
+        self.record(entry.offset, entry.len)
+    }
+
+    pub fn read_all_patches(&self) -> io::Result<HashMap<u64, Patch>> {
+        let mut out = HashMap::new();
+        for entry in &self.index.patches {
+            let patch = self.record(entry.offset, entry.len)?;
+            out.insert(entry.time, patch);
+        }
+        Ok(out)
+    }
+}
+
+/// Appends patches & match ups to an archive, creating it if it doesn't exist yet. Nothing
+/// new is visible to readers until `finish` is called.
+pub struct ArchiveWriter {
+    file: File,
+    index: ArchiveIndex,
+    match_ids: HashSet<u64>,
+    end: u64,
+    dirty: bool,
+}
+
+impl ArchiveWriter {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<ArchiveWriter> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let len = file.metadata()?.len();
+        if len == 0 {
+            let mut header = Vec::with_capacity(HEADER_LEN as usize);
+            header.extend_from_slice(MAGIC);
+            header.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+            header.extend_from_slice(&0u64.to_le_bytes());
+            file.write_all(&header)?;
+            return Ok(ArchiveWriter {
+                file,
+                index: ArchiveIndex::default(),
+                match_ids: HashSet::new(),
+                end: HEADER_LEN,
+                dirty: true,
+            });
+        }
+
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+        let index_offset = read_header(&header)?;
+        let index = if index_offset == 0 {
+            ArchiveIndex::default()
+        } else {
+            let mut buffer = vec![];
+            file.seek(SeekFrom::Start(index_offset))?;
+            file.read_to_end(&mut buffer)?;
+            decode(&buffer)?
+        };
+        let match_ids = index.matches.iter().map(|entry| entry.match_id).collect();
+        Ok(ArchiveWriter {
+            file,
+            index,
+            match_ids,
+            end: len,
+            dirty: false,
+        })
+    }
+
+    pub fn has_patch(&self, time: u64) -> bool {
+        self.index.patches.iter().any(|entry| entry.time == time)
+    }
+
+    pub fn has_match(&self, match_id: u64) -> bool {
+        self.match_ids.contains(&match_id)
+    }
+
+    /// Whether the archive already has this match up, either under its stable ID or, for
+    /// match ups archived before there were stable IDs, under any other.
+    pub fn has_match_up(
+        &mut self,
+        tournament_id: u64,
+        left_color: &str,
+        right_color: &str,
+    ) -> io::Result<bool> {
+        if let Some(match_id) = stable_match_id(tournament_id, left_color, right_color) {
+            if self.has_match(match_id) {
+                return Ok(true);
+            }
+        }
+        let records: Vec<_> = self
+            .index
+            .matches
+            .iter()
+            .filter(|entry| entry.tournament_id == tournament_id)
+            .map(|entry| (entry.offset, entry.len))
+            .collect();
+        for (offset, len) in records {
+            let match_up: MatchUp = self.read_record(offset, len)?;
+            if match_up.left.color == left_color && match_up.right.color == right_color {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn read_record<T: Schema>(&mut self, offset: u64, len: u64) -> io::Result<T> {
+        let mut buffer = vec![0; len as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buffer)?;
+        schema::decode(&buffer)
+    }
+
+    pub fn next_match_id(&self) -> u64 {
+        self.index
+            .matches
+            .iter()
+            .map(|entry| entry.match_id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<(u64, u64)> {
+        let offset = self.end;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(bytes)?;
+        self.end += bytes.len() as u64;
+        self.dirty = true;
+        Ok((offset, bytes.len() as u64))
+    }
+
+    pub fn add_patch(&mut self, patch: &Patch) -> io::Result<()> {
+        let time = patch.time;
+        if self.has_patch(time) {
+            return Ok(());
+        }
+        let (offset, len) = self.append(&schema::encode(patch)?)?;
+        self.index.patches.push(PatchEntry { time, offset, len });
+        Ok(())
+    }
+
+    pub fn add_match(
+        &mut self,
+        match_id: u64,
+        patch_time: u64,
+        match_up: &MatchUp,
+    ) -> io::Result<()> {
+        if self.has_match(match_id) {
+            return Err(invalid_data(format!(
+                "match {} is already in the archive",
+                match_id
+            )));
+        }
+        let (offset, len) = self.append(&schema::encode(match_up)?)?;
+        self.match_ids.insert(match_id);
+        self.index.matches.push(MatchEntry {
+            match_id,
+            tournament_id: match_up.tournament_id,
+            patch_time,
+            offset,
+            len,
+        });
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let index_offset = self.end;
+        let bytes = encode(&self.index)?;
+        self.append(&bytes)?;
+        // Everything the new index points at has to be on disk before the header does.
+        self.file.sync_data()?;
+        self.file.seek(SeekFrom::Start(INDEX_OFFSET_POS))?;
+        self.file.write_all(&index_offset.to_le_bytes())?;
+        self.file.sync_data()
+    }
+}
+
+/// Converts the old directory of `.patch` & `.match` files into the archive, keeping the
+/// match IDs from their file names.
+pub fn archive_directory() -> io::Result<()> {
+    let mut writer = ArchiveWriter::open(ARCHIVE_PATH)?;
+
+    let patches: Vec<Patch> = data::read_all_patches()?;
+    for patch in &patches {
+        writer.add_patch(patch)?;
+    }
+
+    let mut buffer = vec![];
+    let mut added = 0;
+    let mut skipped = 0;
+    for path in data::find_all_match_ups()? {
+        let match_id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+            .ok_or_else(|| invalid_data(format!("bad match file name {:?}", path)))?;
+        if writer.has_match(match_id) {
+            skipped += 1;
+            continue;
+        }
+        let (patch_time, match_up) = data::read_match_at_path(&path, &mut buffer)?;
+        writer.add_match(match_id, patch_time as u64, &match_up)?;
+        added += 1;
+    }
+    writer.finish()?;
+
+    println!(
+        "{} patches, {} match ups added, {} already archived",
+        patches.len(),
+        added,
+        skipped
+    );
+    Ok(())
+}
+
+/// Writes a fresh copy of `old` next to `path`, holding only its records & its current index,
+/// and moves it over `path` once it's complete. Records are written in the current schema.
+fn rewrite_archive(old: &Archive, path: &Path) -> io::Result<()> {
+    let new_path = path.with_extension("rewriting");
+    let _ = std::fs::remove_file(&new_path);
+    let mut writer = ArchiveWriter::open(&new_path)?;
+    for entry in old.patches() {
+        writer.add_patch(&old.read_patch(entry.time)?)?;
+    }
+    for entry in old.matches() {
+        writer.add_match(entry.match_id, entry.patch_time, &old.read_match(entry)?)?;
+    }
+    writer.finish()?;
+    std::fs::rename(&new_path, path)
+}
+
+/// Rewrites every record in the archive at `path` in the current schema, keeping every match
+/// ID. The new archive is written next to the old one and only replaces it once it's complete.
+pub fn migrate_archive<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let old = Archive::open(path)?;
+    if old.is_current()? {
+        println!(
+            "{} is already on schema version {}",
+            path.display(),
+            schema::SCHEMA_VERSION
+        );
+        return Ok(());
+    }
+
+    rewrite_archive(&old, path)?;
+    println!(
+        "migrated {} patches & {} match ups to schema version {}",
+        old.patches().len(),
+        old.matches().len(),
+        schema::SCHEMA_VERSION
+    );
+    Ok(())
+}
+
+/// Rewrites the archive at `path` without the old indexes every append leaves behind, the same
+/// way `migrate_archive` does.
+pub fn compact_archive<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let old = Archive::open(path)?;
+    let before = old.mmap.len();
+    rewrite_archive(&old, path)?;
+    let after = std::fs::metadata(path)?.len();
+    println!(
+        "compacted {} from {} to {} bytes",
+        path.display(),
+        before,
+        after
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::rust::{AbilityData, Arena, BaseStatsData, EquipmentData, Team};
+    use crate::dto::schema::tests::without_status_durations;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn make_patch(time: u64) -> Patch {
+        Patch {
+            time,
+            ability: AbilityData {
+                by_name: HashMap::new(),
+            },
+            equipment: EquipmentData {
+                by_name: HashMap::new(),
+            },
+            base_stats: BaseStatsData {
+                by_job_gender: HashMap::new(),
+            },
+            status_durations: vec![],
+        }
+    }
+
+    fn make_match_up(tournament_id: u64) -> MatchUp {
+        MatchUp {
+            tournament_id,
+            modified: tournament_id,
+            left: Team {
+                color: "red".to_owned(),
+                combatants: vec![],
+            },
+            right: Team {
+                color: "blue".to_owned(),
+                combatants: vec![],
+            },
+            left_wins: Some(true),
+            arena_name: "Test Map".to_owned(),
+            arena: Arena {
+                lower: vec![],
+                upper: vec![],
+                width: 0,
+                height: 0,
+                starting_locations: vec![],
+            },
+        }
+    }
+
+    #[test]
+    pub fn append_and_read_back() {
+        let path = temp_path("append_and_read_back");
+        {
+            let mut writer = ArchiveWriter::open(&path).unwrap();
+            writer.add_patch(&make_patch(100)).unwrap();
+            writer.add_match(0, 100, &make_match_up(7)).unwrap();
+            writer.add_match(1, 100, &make_match_up(7)).unwrap();
+            writer.finish().unwrap();
+        }
+        {
+            let mut writer = ArchiveWriter::open(&path).unwrap();
+            assert_eq!(writer.next_match_id(), 2);
+            writer.add_patch(&make_patch(100)).unwrap();
+            writer.add_patch(&make_patch(200)).unwrap();
+            writer.add_match(2, 200, &make_match_up(8)).unwrap();
+            assert!(writer.add_match(1, 200, &make_match_up(8)).is_err());
+            writer.finish().unwrap();
+        }
+
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.patches().len(), 2);
+        assert_eq!(archive.matches().len(), 3);
+        assert_eq!(archive.tournament(7).count(), 2);
+
+        let entry = *archive.match_entry(2).unwrap();
+        assert_eq!(entry.patch_time, 200);
+        let match_up = archive.read_match(&entry).unwrap();
+        assert_eq!(match_up.tournament_id, 8);
+        assert_eq!(archive.read_patch(200).unwrap().time, 200);
+        assert!(archive.match_entry(3).is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn unfinished_appends_leave_the_old_index() {
+        let path = temp_path("unfinished_appends_leave_the_old_index");
+        {
+            let mut writer = ArchiveWriter::open(&path).unwrap();
+            writer.add_match(0, 100, &make_match_up(7)).unwrap();
+            writer.finish().unwrap();
+        }
+        {
+            // Stops before `finish`, like an error or Ctrl-C part way through a feed.
+            let mut writer = ArchiveWriter::open(&path).unwrap();
+            writer.add_match(1, 100, &make_match_up(8)).unwrap();
+            writer.add_match(2, 100, &make_match_up(8)).unwrap();
+        }
+
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.matches().len(), 1);
+        let entry = *archive.match_entry(0).unwrap();
+        assert_eq!(archive.read_match(&entry).unwrap(), make_match_up(7));
+
+        // And the next append still picks up from the old index.
+        let mut writer = ArchiveWriter::open(&path).unwrap();
+        assert_eq!(writer.next_match_id(), 1);
+        writer.add_match(1, 100, &make_match_up(9)).unwrap();
+        writer.finish().unwrap();
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.matches().len(), 2);
+        let entry = *archive.match_entry(1).unwrap();
+        assert_eq!(archive.read_match(&entry).unwrap(), make_match_up(9));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn compacting_drops_old_indexes() {
+        let path = temp_path("compacting_drops_old_indexes");
+        let mut records_len = 0;
+        for match_id in 0..20 {
+            let mut writer = ArchiveWriter::open(&path).unwrap();
+            writer.add_match(match_id, 100, &make_match_up(7)).unwrap();
+            records_len += schema::encode(&make_match_up(7)).unwrap().len() as u64;
+            writer.finish().unwrap();
+        }
+        // Opening & finishing without adding anything leaves it be.
+        let appended_len = std::fs::metadata(&path).unwrap().len();
+        ArchiveWriter::open(&path).unwrap().finish().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), appended_len);
+
+        compact_archive(&path).unwrap();
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.matches().len(), 20);
+        let index_len = encode(&archive.index).unwrap().len() as u64;
+        let file_len = std::fs::metadata(&path).unwrap().len();
+        assert!(file_len < appended_len);
+        assert_eq!(file_len, HEADER_LEN + records_len + index_len);
+        for entry in archive.matches() {
+            assert_eq!(archive.read_match(entry).unwrap(), make_match_up(7));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn rejects_other_versions() {
+        let path = temp_path("rejects_other_versions");
+        ArchiveWriter::open(&path).unwrap().finish().unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[8] = 99;
+        std::fs::write(&path, &bytes).unwrap();
+        let err = Archive::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn migrates_old_records() {
+        let path = temp_path("migrates_old_records");
+        {
+            // Records from before there was a schema version, written by hand.
+            let mut writer = ArchiveWriter::open(&path).unwrap();
+            let mut old_record = |val: &[u8]| {
+                let mut bytes = schema::LEGACY_SCHEMA_VERSION.to_le_bytes().to_vec();
+                bytes.extend_from_slice(val);
+                writer.append(&bytes).unwrap()
+            };
+            let (offset, len) = old_record(&without_status_durations(&make_patch(100)));
+            let patch = PatchEntry {
+                time: 100,
+                offset,
+                len,
+            };
+            let (offset, len) = old_record(&bincode::serialize(&make_match_up(7)).unwrap());
+            writer.index.patches.push(patch);
+            writer.index.matches.push(MatchEntry {
+                match_id: 5,
+                tournament_id: 7,
+                patch_time: 100,
+                offset,
+                len,
+            });
+            writer.finish().unwrap();
+        }
+        assert!(!Archive::open(&path).unwrap().is_current().unwrap());
+
+        migrate_archive(&path).unwrap();
+        let archive = Archive::open(&path).unwrap();
+        assert!(archive.is_current().unwrap());
+        let entry = *archive.match_entry(5).unwrap();
+        assert_eq!(archive.read_match(&entry).unwrap(), make_match_up(7));
+        assert_eq!(archive.read_patch(100).unwrap(), make_patch(100));
+
+        // Migrating again is a no-op.
+        migrate_archive(&path).unwrap();
+        assert_eq!(Archive::open(&path).unwrap().matches().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde_json;
+
+use crate::runner::log_loss;
+
+const BOOTSTRAP_RESAMPLES: usize = 2000;
+const TOP_MOVERS: usize = 10;
+
+/// Predictions as saved by `simulator test --save`, tournament ID to "left,right" colors to
+/// the chance the left team wins.
+pub type SavedPredictions = HashMap<String, HashMap<String, f64>>;
+
+/// One match up's prediction from the current run.
+pub struct Prediction {
+    pub match_id: u64,
+    pub tournament_id: u64,
+    pub key: String,
+    pub left_wins: bool,
+    pub left_wins_percent: f64,
+}
+
+pub struct MatchDelta {
+    pub match_id: u64,
+    pub tournament_id: u64,
+    pub key: String,
+    pub baseline: f64,
+    pub current: f64,
+    pub baseline_loss: f64,
+    pub current_loss: f64,
+}
+
+impl MatchDelta {
+    /// Negative when the current run did better.
+    pub fn loss_delta(&self) -> f64 {
+        self.current_loss - self.baseline_loss
+    }
+}
+
+pub fn load_baseline(path: &str) -> io::Result<SavedPredictions> {
+    let text = fs::read_to_string(path)?;
+    serde_json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Pairs every prediction with the baseline's prediction for the same match up, returning
+/// those along with how many the baseline didn't have.
+pub fn match_deltas(
+    baseline: &SavedPredictions,
+    predictions: &[Prediction],
+) -> (Vec<MatchDelta>, usize) {
+    let mut deltas = vec![];
+    let mut missing = 0;
+    for prediction in predictions {
+        let old = baseline
+            .get(&prediction.tournament_id.to_string())
+            .and_then(|tournament| tournament.get(&prediction.key));
+        let old = match old {
+            Some(old) => *old,
+            None => {
+                missing += 1;
+                continue;
+            }
+        };
+        deltas.push(MatchDelta {
+            match_id: prediction.match_id,
+            tournament_id: prediction.tournament_id,
+            key: prediction.key.clone(),
+            baseline: old,
+            current: prediction.left_wins_percent,
+            baseline_loss: log_loss(old, prediction.left_wins),
+            current_loss: log_loss(prediction.left_wins_percent, prediction.left_wins),
+        });
+    }
+    deltas.sort_by_key(|delta| delta.match_id);
+    (deltas, missing)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// A 95% confidence interval for the mean of `values`, resampling them with replacement.
+/// Since each value is the difference between two runs on the same match up, this is a
+/// paired bootstrap.
+pub fn bootstrap_interval<R: Rng>(values: &[f64], resamples: usize, rng: &mut R) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mut means = Vec::with_capacity(resamples);
+    for _ in 0..resamples {
+        let mut total = 0.0;
+        for _ in 0..values.len() {
+            total += values[rng.gen_range(0, values.len())];
+        }
+        means.push(total / values.len() as f64);
+    }
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let low = ((resamples as f64) * 0.025) as usize;
+    let high = (((resamples as f64) * 0.975) as usize).min(resamples - 1);
+    (means[low], means[high])
+}
+
+fn print_mover(delta: &MatchDelta) {
+    println!(
+        "  match {:<16} {:>14} {:<18} {:.3} -> {:.3}  log loss {:+.4}",
+        delta.match_id,
+        delta.tournament_id,
+        delta.key.replace(",", " vs "),
+        delta.baseline,
+        delta.current,
+        delta.loss_delta()
+    );
+}
+
+pub fn print_comparison(path: &str, baseline: &SavedPredictions, predictions: &[Prediction]) {
+    let (mut deltas, missing) = match_deltas(baseline, predictions);
+    println!(
+        "\ncompared with {}: {} match ups in common, {} not in the baseline",
+        path,
+        deltas.len(),
+        missing
+    );
+    if deltas.is_empty() {
+        return;
+    }
+
+    let losses: Vec<_> = deltas.iter().map(MatchDelta::loss_delta).collect();
+    let baseline_loss = mean(&deltas.iter().map(|d| d.baseline_loss).collect::<Vec<_>>());
+    let current_loss = mean(&deltas.iter().map(|d| d.current_loss).collect::<Vec<_>>());
+    // Seeded so that running the same comparison twice prints the same interval.
+    let mut rng = SmallRng::seed_from_u64(0);
+    let (low, high) = bootstrap_interval(&losses, BOOTSTRAP_RESAMPLES, &mut rng);
+    println!(
+        "log loss: {:.6} -> {:.6}, delta {:+.6} (95% CI {:+.6} to {:+.6})",
+        baseline_loss,
+        current_loss,
+        current_loss - baseline_loss,
+        low,
+        high
+    );
+    println!(
+        "improved: {}, regressed: {}, unchanged: {}",
+        losses.iter().filter(|d| **d < 0.0).count(),
+        losses.iter().filter(|d| **d > 0.0).count(),
+        losses.iter().filter(|d| **d == 0.0).count()
+    );
+
+    deltas.sort_by(|a, b| a.loss_delta().partial_cmp(&b.loss_delta()).unwrap());
+    println!("\nmost improved:");
+    for delta in deltas
+        .iter()
+        .take(TOP_MOVERS)
+        .filter(|d| d.loss_delta() < 0.0)
+    {
+        print_mover(delta);
+    }
+    println!("\nmost regressed:");
+    for delta in deltas
+        .iter()
+        .rev()
+        .take(TOP_MOVERS)
+        .filter(|d| d.loss_delta() > 0.0)
+    {
+        print_mover(delta);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn prediction(match_id: u64, key: &str, left_wins: bool, percent: f64) -> Prediction {
+        Prediction {
+            match_id,
+            tournament_id: 100,
+            key: key.to_owned(),
+            left_wins,
+            left_wins_percent: percent,
+        }
+    }
+
+    #[test]
+    pub fn pairs_predictions_with_the_baseline() {
+        let baseline: SavedPredictions =
+            serde_json::from_str(r#"{"100": {"red,blue": 0.5, "green,yellow": 0.9}}"#).unwrap();
+        let predictions = vec![
+            prediction(2, "green,yellow", false, 0.6),
+            prediction(1, "red,blue", true, 0.8),
+            prediction(3, "white,black", true, 0.5),
+        ];
+        let (deltas, missing) = match_deltas(&baseline, &predictions);
+        assert_eq!(missing, 1);
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].match_id, 1);
+        assert!(deltas[0].loss_delta() < 0.0);
+        assert!(deltas[1].loss_delta() < 0.0);
+        assert!((deltas[0].baseline_loss - 2f64.ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    pub fn bootstrap_interval_covers_the_mean() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let constant = vec![-0.25; 50];
+        assert_eq!(bootstrap_interval(&constant, 200, &mut rng), (-0.25, -0.25));
+
+        let spread: Vec<f64> = (0..200).map(|i| (i % 10) as f64 / 10.0 - 0.5).collect();
+        let (low, high) = bootstrap_interval(&spread, 1000, &mut rng);
+        let mean = mean(&spread);
+        assert!(low < mean && mean < high);
+        assert!(high - low < 0.2);
+    }
+}
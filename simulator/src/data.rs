@@ -1,54 +1,191 @@
-use std::collections::HashSet;
 use std::ffi::OsString;
 use std::fs;
 use std::io;
-use std::io::{Read, Write};
+use std::io::{BufRead, Read};
 use std::path::PathBuf;
 
-use bincode;
-use serde::de::DeserializeOwned;
 use serde_json;
 
+use crate::archive::{stable_match_id, ArchiveWriter, ARCHIVE_PATH};
 use crate::dto::python;
 use crate::dto::rust;
+use crate::dto::schema;
+use crate::dto::schema::Schema;
+
+/// Tallies of what happened to the match ups read from the feed.
+#[derive(Default, Debug, PartialEq)]
+pub struct FeedReport {
+    pub added: u64,
+    pub skipped: u64,
+    pub malformed: u64,
+    pub partial: bool,
+}
 
 pub fn convert_data_from_feed() -> io::Result<()> {
+    let mut writer = ArchiveWriter::open(ARCHIVE_PATH)?;
+    let stdin = io::stdin();
+    let report = ingest_feed(&mut writer, stdin.lock())?;
+    writer.finish()?;
+
+    println!(
+        "{} new match ups, {} already archived, {} malformed",
+        report.added, report.skipped, report.malformed
+    );
+    if report.partial {
+        println!("ignored an incomplete match up at the end of the feed");
+    }
+    Ok(())
+}
+
+/// One line of the feed, told apart by a field only that kind of record has.
+enum FeedLine {
+    Patch(python::Patch),
+    Arena(python::Arena),
+    MatchUp(python::MatchUp),
+    Unknown,
+}
+
+/// The patch & arena lines read so far for the match up that should come next.
+#[derive(Default)]
+struct PendingEntry {
+    patch: Option<python::Patch>,
+    arena: Option<python::Arena>,
+    broken: bool,
+}
+
+impl PendingEntry {
+    fn is_empty(&self) -> bool {
+        self.patch.is_none() && self.arena.is_none() && !self.broken
+    }
+}
+
+/// Reads the feed's patch, arena & match up lines, adding every match up the archive doesn't
+/// already have. Each match up ends an entry & each patch starts a new one, so a missing or
+/// garbled line only loses the entry it was part of. Safe to run over the same feed again.
+pub fn ingest_feed<R: BufRead>(writer: &mut ArchiveWriter, mut input: R) -> io::Result<FeedReport> {
+    let mut report = FeedReport::default();
+    let mut pending = PendingEntry::default();
     let mut buffer = String::new();
-    let mut match_counter = 0;
-    let mut seen_patches = HashSet::new();
 
     loop {
-        if std::io::stdin().read_line(&mut buffer)? == 0 {
-            return Ok(());
-        }
-        let patch: python::Patch = serde_json::from_str(&buffer).unwrap();
         buffer.clear();
-        if std::io::stdin().read_line(&mut buffer)? == 0 {
-            return Ok(());
+        if input.read_line(&mut buffer)? == 0 {
+            break;
         }
-        let arena: python::Arena = serde_json::from_str(&buffer).unwrap();
-        buffer.clear();
-        if std::io::stdin().read_line(&mut buffer)? == 0 {
-            return Ok(());
+        if buffer.trim().is_empty() {
+            continue;
         }
-        let match_up: python::MatchUp = serde_json::from_str(&buffer).unwrap();
-        buffer.clear();
+        // A last line without a newline may have been cut off part way through.
+        let cut_off = !buffer.ends_with('\n');
 
-        let int_time = patch.time as u64;
-        if !seen_patches.insert(int_time) {
-            let rust_patch = rust::Patch::from_python(patch);
+        let match_up = match parse_feed_line(&buffer) {
+            FeedLine::Patch(patch) => {
+                if !pending.is_empty() {
+                    report.malformed += 1;
+                }
+                pending = PendingEntry {
+                    patch: Some(patch),
+                    ..PendingEntry::default()
+                };
+                continue;
+            }
+            FeedLine::Arena(arena) => {
+                if pending.patch.is_none() || pending.arena.is_some() {
+                    pending.broken = true;
+                } else {
+                    pending.arena = Some(arena);
+                }
+                continue;
+            }
+            FeedLine::Unknown if cut_off => {
+                report.partial = true;
+                return Ok(report);
+            }
+            FeedLine::Unknown => {
+                pending.broken = true;
+                continue;
+            }
+            FeedLine::MatchUp(match_up) => match_up,
+        };
 
-            let rust_bin = bincode::serialize(&rust_patch).unwrap();
-            let mut file = std::fs::File::create(format!("data/sim/{}.patch", int_time))?;
-            file.write_all(&rust_bin)?;
+        let entry = std::mem::take(&mut pending);
+        let (patch, arena) = match (entry.patch, entry.arena, entry.broken) {
+            (Some(patch), Some(arena), false) if feed_entry_fits(&arena) => (patch, arena),
+            _ => {
+                report.malformed += 1;
+                continue;
+            }
+        };
+        let match_id = match stable_match_id(
+            match_up.tournament_id,
+            &match_up.left.color,
+            &match_up.right.color,
+        ) {
+            Some(match_id) => match_id,
+            None => {
+                report.malformed += 1;
+                continue;
+            }
+        };
+        if writer.has_match_up(
+            match_up.tournament_id,
+            &match_up.left.color,
+            &match_up.right.color,
+        )? {
+            report.skipped += 1;
+            continue;
         }
 
-        let rust_match_up = rust::MatchUp::from_python(match_up, arena);
-        let rust_bin = bincode::serialize(&(int_time, rust_match_up)).unwrap();
-        let mut file = std::fs::File::create(format!("data/sim/{:06}.match", match_counter))?;
-        file.write_all(&rust_bin)?;
-        match_counter += 1;
+        let rust_match_up = match rust::MatchUp::from_python(match_up, arena) {
+            Ok(match_up) => match_up,
+            Err(_) => {
+                report.malformed += 1;
+                continue;
+            }
+        };
+        let int_time = patch.time as u64;
+        if !writer.has_patch(int_time) {
+            match rust::Patch::from_python(patch) {
+                Ok(patch) => writer.add_patch(&patch)?,
+                Err(_) => {
+                    report.malformed += 1;
+                    continue;
+                }
+            }
+        }
+        writer.add_match(match_id, int_time, &rust_match_up)?;
+        report.added += 1;
     }
+
+    report.partial = !pending.is_empty();
+    Ok(report)
+}
+
+fn parse_feed_line(line: &str) -> FeedLine {
+    let value: serde_json::Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(_) => return FeedLine::Unknown,
+    };
+    let has = |key| value.get(key).is_some();
+    let parsed = if has("base_stats") {
+        serde_json::from_value(value).map(FeedLine::Patch)
+    } else if has("gns") {
+        serde_json::from_value(value).map(FeedLine::Arena)
+    } else if has("tournament_id") {
+        serde_json::from_value(value).map(FeedLine::MatchUp)
+    } else {
+        return FeedLine::Unknown;
+    };
+    parsed.unwrap_or(FeedLine::Unknown)
+}
+
+/// Catches the arena rows that are too short to convert, which would otherwise panic.
+fn feed_entry_fits(arena: &python::Arena) -> bool {
+    let rows_fit = |rows: &Vec<Vec<python::Tile>>| {
+        rows.len() >= arena.height as usize
+            && rows.iter().all(|row| row.len() >= arena.width as usize)
+    };
+    rows_fit(&arena.lower) && rows_fit(&arena.upper)
 }
 
 fn find_files_matching(extension: &str) -> io::Result<Vec<PathBuf>> {
@@ -68,13 +205,13 @@ fn find_files_matching(extension: &str) -> io::Result<Vec<PathBuf>> {
     return Ok(out);
 }
 
-fn read_files_matching<T: DeserializeOwned>(extension: &str) -> io::Result<Vec<T>> {
+fn read_files_matching<T: Schema>(extension: &str) -> io::Result<Vec<T>> {
     let mut out = vec![];
     let mut buffer = vec![];
     for path in find_files_matching(extension)? {
         buffer.clear();
         let _bin = std::fs::File::open(path)?.read_to_end(&mut buffer)?;
-        let val = bincode::deserialize(&buffer).unwrap();
+        let val = schema::decode_legacy(&buffer)?;
         out.push(val);
     }
     return Ok(out);
@@ -88,17 +225,182 @@ pub fn find_all_match_ups() -> io::Result<Vec<PathBuf>> {
     find_files_matching("match")
 }
 
-pub fn read_match(id: u64, buffer: &mut Vec<u8>) -> io::Result<(usize, rust::MatchUp)> {
-    buffer.clear();
-    fs::File::open(format!("data/sim/{:06}.match", id))?.read_to_end(buffer)?;
-    Ok(bincode::deserialize(&buffer).unwrap())
-}
-
 pub fn read_match_at_path(
     path: &PathBuf,
     buffer: &mut Vec<u8>,
 ) -> io::Result<(usize, rust::MatchUp)> {
     buffer.clear();
     fs::File::open(path)?.read_to_end(buffer)?;
-    Ok(bincode::deserialize(&buffer).unwrap())
+    // A match file is the patch time as a bincode usize, then the match up.
+    if buffer.len() < 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is too short", path.display()),
+        ));
+    }
+    let mut patch_time = [0; 8];
+    patch_time.copy_from_slice(&buffer[0..8]);
+    let match_up = schema::decode_legacy(&buffer[8..])?;
+    Ok((u64::from_le_bytes(patch_time) as usize, match_up))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::archive::Archive;
+    use crate::sim::Condition;
+
+    const PATCH: &str = r#"{"time": 1000.0, "ability": {"by_name": {}}, "equipment": {"by_name": {}}, "base_stats": {"by_job_gender": {}}}"#;
+    const ARENA: &str = r#"{"gns": "MAP001.GNS", "lower": [], "upper": [], "width": 0, "height": 0, "surface_types": [], "starting_locations": []}"#;
+
+    fn match_up(tournament_id: u64, left: &str, right: &str) -> String {
+        format!(
+            r#"{{"tournament_id": {}, "modified": 0.0, "left": {{"color": "{}", "combatants": []}}, "right": {{"color": "{}", "combatants": []}}, "left_wins": true, "game_map": "MAP 001", "game_map_num": 1}}"#,
+            tournament_id, left, right
+        )
+    }
+
+    fn feed(match_ups: &[String]) -> String {
+        let mut out = String::new();
+        for match_up in match_ups {
+            out.push_str(&format!("{}\n{}\n{}\n", PATCH, ARENA, match_up));
+        }
+        out
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    pub fn feed_resumes_without_duplicates() {
+        let path = temp_path("feed_resumes_without_duplicates");
+        let first = feed(&[match_up(5, "red", "blue"), match_up(5, "green", "yellow")]);
+        let mut writer = ArchiveWriter::open(&path).unwrap();
+        let report = ingest_feed(&mut writer, first.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(report.added, 2);
+        assert_eq!(report.skipped, 0);
+
+        let second = feed(&[
+            match_up(5, "green", "yellow"),
+            match_up(5, "red", "blue"),
+            match_up(6, "red", "blue"),
+            match_up(6, "red", "blue"),
+        ]);
+        let mut writer = ArchiveWriter::open(&path).unwrap();
+        let report = ingest_feed(&mut writer, second.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(report.added, 1);
+        assert_eq!(report.skipped, 3);
+
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.matches().len(), 3);
+        assert_eq!(archive.patches().len(), 1);
+        let match_id = stable_match_id(6, "red", "blue").unwrap();
+        assert_eq!(archive.match_entry(match_id).unwrap().tournament_id, 6);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn feed_counts_malformed_and_partial_entries() {
+        let path = temp_path("feed_counts_malformed_and_partial_entries");
+        let mut input = feed(&[match_up(5, "red", "blue"), match_up(5, "mauve", "blue")]);
+        input.push_str(&format!(
+            "{}\nnot json\n{}\n",
+            PATCH,
+            match_up(5, "green", "yellow")
+        ));
+        input.push_str(&format!("{}\n{}", PATCH, ARENA));
+
+        let mut writer = ArchiveWriter::open(&path).unwrap();
+        let report = ingest_feed(&mut writer, input.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            report,
+            FeedReport {
+                added: 1,
+                skipped: 0,
+                malformed: 2,
+                partial: true,
+            }
+        );
+
+        // A final match up cut off part way through its last line is partial too.
+        let mut input = feed(&[match_up(7, "red", "blue")]);
+        input.push_str(&format!("{}\n{}\n{{\"tournament_id\": 7,", PATCH, ARENA));
+        let mut writer = ArchiveWriter::open(&path).unwrap();
+        let report = ingest_feed(&mut writer, input.as_bytes()).unwrap();
+        assert_eq!(report.added, 1);
+        assert_eq!(report.malformed, 0);
+        assert!(report.partial);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn feed_resyncs_after_missing_lines() {
+        let path = temp_path("feed_resyncs_after_missing_lines");
+        let mut input = feed(&[match_up(5, "red", "blue")]);
+        // No arena, then no match up.
+        input.push_str(&format!("{}\n{}\n", PATCH, match_up(5, "green", "yellow")));
+        input.push_str(&format!("{}\n{}\n", PATCH, ARENA));
+        input.push_str(&feed(&[match_up(5, "white", "black")]));
+
+        let mut writer = ArchiveWriter::open(&path).unwrap();
+        let report = ingest_feed(&mut writer, input.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            report,
+            FeedReport {
+                added: 2,
+                skipped: 0,
+                malformed: 2,
+                partial: false,
+            }
+        );
+        let archive = Archive::open(&path).unwrap();
+        assert!(archive
+            .match_entry(stable_match_id(5, "white", "black").unwrap())
+            .is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn feed_counts_unusable_status_durations_as_malformed() {
+        let path = temp_path("feed_counts_unusable_status_durations_as_malformed");
+        let with_durations = |durations: &str| {
+            PATCH.replacen(
+                r#""time": 1000.0"#,
+                &format!(r#""time": 1000.0, "status_durations": {{{}}}"#, durations),
+                1,
+            )
+        };
+        let mut input = String::new();
+        for patch in &[
+            with_durations(r#""Hastee": 24"#),
+            with_durations(r#""Haste": 85"#),
+            with_durations(r#""Haste": 84"#),
+        ] {
+            input.push_str(&format!(
+                "{}\n{}\n{}\n",
+                patch,
+                ARENA,
+                match_up(5, "red", "blue")
+            ));
+        }
+
+        let mut writer = ArchiveWriter::open(&path).unwrap();
+        let report = ingest_feed(&mut writer, input.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(report.added, 1);
+        assert_eq!(report.malformed, 2);
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(
+            archive.read_patch(1000).unwrap().status_durations,
+            vec![(Condition::Haste, 84)]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,393 @@
+use std::cmp::Ordering;
+use std::io;
+use std::io::{BufRead, Write};
+
+use colored::Colorize;
+
+use crate::render::ascii_arena;
+use crate::sim::{
+    describe_combatant, describe_entry, describe_phase, unit_card, Arena, Entry, Event, Phase,
+};
+
+const HELP: &str = "\
+  n / p      next / previous active turn
+  t / T      next / previous clock tick
+  s / S      next / previous event
+  g TICK     go to the first event at or after clock tick TICK
+  c [ID]     unit cards for everyone, or just unit ID
+  m          the map & unit positions
+  a          the actions considered this turn
+  h          this help
+  q          quit
+  (empty)    repeat the last command";
+
+/// Steps through the log of a single finished simulation. Each entry carries a copy of every
+/// combatant as of that moment, so moving backwards is as cheap as moving forwards.
+pub struct Debugger<'a, 'b> {
+    entries: &'b [Entry<'a>],
+    arena: &'b Arena,
+    position: usize,
+}
+
+impl<'a, 'b> Debugger<'a, 'b> {
+    pub fn new(entries: &'b [Entry<'a>], arena: &'b Arena) -> Debugger<'a, 'b> {
+        Debugger {
+            entries,
+            arena,
+            position: 0,
+        }
+    }
+
+    fn same_group(&self, a: usize, b: usize) -> bool {
+        let a = &self.entries[a];
+        let b = &self.entries[b];
+        a.clock_tick == b.clock_tick && a.phase == b.phase
+    }
+
+    /// The first entry of the run of entries sharing `position`'s tick & phase.
+    fn group_start(&self, position: usize) -> usize {
+        let mut start = position;
+        while start > 0 && self.same_group(start - 1, position) {
+            start -= 1;
+        }
+        start
+    }
+
+    fn group_end(&self, position: usize) -> usize {
+        let mut end = position + 1;
+        while end < self.entries.len() && self.same_group(end, position) {
+            end += 1;
+        }
+        end
+    }
+
+    fn is_active_turn(&self, position: usize) -> bool {
+        match self.entries[position].phase {
+            Phase::ActiveTurn(_) => true,
+            _ => false,
+        }
+    }
+
+    fn next_turn(&self) -> usize {
+        let mut position = self.group_end(self.position);
+        while position < self.entries.len() {
+            if self.is_active_turn(position) {
+                return position;
+            }
+            position = self.group_end(position);
+        }
+        self.position
+    }
+
+    fn previous_turn(&self) -> usize {
+        let mut position = self.group_start(self.position);
+        while position > 0 {
+            position = self.group_start(position - 1);
+            if self.is_active_turn(position) {
+                return position;
+            }
+        }
+        self.position
+    }
+
+    fn next_tick(&self) -> usize {
+        let tick = self.entries[self.position].clock_tick;
+        self.entries[self.position..]
+            .iter()
+            .position(|entry| entry.clock_tick > tick)
+            .map_or(self.position, |offset| self.position + offset)
+    }
+
+    fn previous_tick(&self) -> usize {
+        let tick = self.entries[self.position].clock_tick;
+        match self.entries[..self.position]
+            .iter()
+            .rposition(|entry| entry.clock_tick < tick)
+        {
+            Some(last) => self.seek_tick(self.entries[last].clock_tick),
+            None => 0,
+        }
+    }
+
+    fn seek_tick(&self, tick: usize) -> usize {
+        self.entries
+            .iter()
+            .position(|entry| entry.clock_tick >= tick)
+            .unwrap_or(self.entries.len() - 1)
+    }
+
+    fn show_position(&self) {
+        let entry = &self.entries[self.position];
+        println!(
+            "\n{} (event {} of {})",
+            format!(
+                "== CT {}: {} ==",
+                entry.clock_tick,
+                describe_phase(&entry.phase, &entry.combatants)
+            )
+            .bold(),
+            self.position + 1,
+            self.entries.len()
+        );
+        println!("{}", ascii_arena(self.arena, Some(&entry.combatants)));
+
+        let start = self.group_start(self.position);
+        let end = self.group_end(self.position);
+        for idx in start..end {
+            let other = &self.entries[idx];
+            if is_ai_trace(&other.event) {
+                continue;
+            }
+            let marker = if idx == self.position { ">" } else { " " };
+            println!("{} {}", marker, describe_entry(other, self.arena));
+        }
+        if self.is_active_turn(self.position) {
+            self.show_considered();
+        }
+    }
+
+    fn show_considered(&self) {
+        let start = self.group_start(self.position);
+        let end = self.group_end(self.position);
+        let trace: Vec<_> = self.entries[start..end]
+            .iter()
+            .filter(|entry| is_ai_trace(&entry.event))
+            .collect();
+        if trace.is_empty() {
+            println!("  (no actions were scored this turn)");
+            return;
+        }
+        // The trace for each decision is its basis, the candidates, then what was chosen.
+        // Show the candidates best first, with the rejected ones after the rest.
+        let score = |entry: &Entry| match entry.event {
+            Event::ConsideredAction(_, _, consideration) => (
+                consideration.rejected.is_none(),
+                consideration
+                    .value
+                    .filter(|value| !value.is_nan())
+                    .unwrap_or(f32::MIN),
+            ),
+            _ => (false, f32::MIN),
+        };
+        println!("  considered:");
+        let mut candidates = vec![];
+        for entry in trace {
+            if let Event::ConsideredAction(_, _, _) = entry.event {
+                candidates.push(entry);
+                continue;
+            }
+            candidates.sort_by(|a, b| score(b).partial_cmp(&score(a)).unwrap_or(Ordering::Equal));
+            for candidate in candidates.drain(..) {
+                println!("      {}", describe_entry(candidate, self.arena));
+            }
+            println!("    {}", describe_entry(entry, self.arena));
+        }
+    }
+
+    fn show_unit_cards(&self, id: Option<usize>) {
+        let combatants = &self.entries[self.position].combatants;
+        for combatant in combatants.iter() {
+            if id.map_or(true, |id| combatant.id().index() == id) {
+                println!("{}", unit_card(combatant));
+                println!(
+                    "{}",
+                    describe_combatant(combatant.id(), combatants, self.arena)
+                );
+            }
+        }
+    }
+
+    /// Runs one command, returning false when it's time to quit.
+    fn command(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("n");
+        let argument = words.next();
+        match command {
+            "n" => self.position = self.next_turn(),
+            "p" => self.position = self.previous_turn(),
+            "t" => self.position = self.next_tick(),
+            "T" => self.position = self.previous_tick(),
+            "s" => self.position = (self.position + 1).min(self.entries.len() - 1),
+            "S" => self.position = self.position.saturating_sub(1),
+            "g" => match argument.and_then(|arg| arg.parse().ok()) {
+                Some(tick) => self.position = self.seek_tick(tick),
+                None => {
+                    println!("g needs a clock tick");
+                    return true;
+                }
+            },
+            "c" => {
+                self.show_unit_cards(argument.and_then(|arg| arg.parse().ok()));
+                return true;
+            }
+            "m" => {
+                let combatants = &self.entries[self.position].combatants;
+                println!("{}", ascii_arena(self.arena, Some(combatants)));
+                return true;
+            }
+            "a" => {
+                self.show_considered();
+                return true;
+            }
+            "q" => return false,
+            _ => {
+                println!("{}", HELP);
+                return true;
+            }
+        }
+        self.show_position();
+        true
+    }
+
+    pub fn run<R: BufRead>(&mut self, mut input: R) -> io::Result<()> {
+        if self.entries.is_empty() {
+            println!("nothing happened in this match");
+            return Ok(());
+        }
+        println!("{}", HELP);
+        self.show_position();
+
+        let mut last = String::from("n");
+        let mut line = String::new();
+        loop {
+            print!("(debug) ");
+            io::stdout().flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let trimmed = line.trim();
+            if !trimmed.is_empty() {
+                last = trimmed.to_owned();
+            }
+            if !self.command(&last) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn is_ai_trace(event: &Event) -> bool {
+    match event {
+        Event::ChoosingAction(_, _)
+        | Event::ConsideredAction(_, _, _)
+        | Event::ChoseAction(_, _) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::test_support::{facing_off_arena, sim_with};
+    use crate::sim::Log;
+
+    /// The log of the first 40 ticks of a mirror match, AI trace & all.
+    fn with_entries(check: impl FnOnce(&[Entry], &Arena)) {
+        let arena = facing_off_arena();
+        sim_with(
+            &arena,
+            |_| {},
+            |sim| {
+                sim.log = Log::new();
+                sim.trace_ai = true;
+                for _ in 0..40 {
+                    sim.tick();
+                }
+                check(&sim.log.entries(), &arena);
+            },
+        );
+    }
+
+    #[test]
+    pub fn steps_between_turns() {
+        with_entries(|entries, arena| {
+            let mut debugger = Debugger::new(entries, arena);
+            assert!(debugger.command("n"));
+            let first_turn = debugger.position;
+            assert!(debugger.is_active_turn(first_turn));
+            assert_eq!(debugger.group_start(first_turn), first_turn);
+
+            debugger.command("n");
+            let second_turn = debugger.position;
+            assert!(second_turn > first_turn);
+            assert!(debugger.is_active_turn(second_turn));
+            assert!(!debugger.same_group(first_turn, second_turn));
+
+            debugger.command("p");
+            assert_eq!(debugger.position, first_turn);
+            // Nothing to go back to, so stay put.
+            while debugger.previous_turn() != debugger.position {
+                debugger.command("p");
+            }
+            let earliest = debugger.position;
+            debugger.command("p");
+            assert_eq!(debugger.position, earliest);
+        });
+    }
+
+    #[test]
+    pub fn steps_between_ticks_and_events() {
+        with_entries(|entries, arena| {
+            let mut debugger = Debugger::new(entries, arena);
+            debugger.command("t");
+            let tick = entries[debugger.position].clock_tick;
+            assert!(tick > entries[0].clock_tick);
+            assert!(entries[debugger.position - 1].clock_tick < tick);
+
+            debugger.command("t");
+            debugger.command("T");
+            assert_eq!(entries[debugger.position].clock_tick, tick);
+            assert!(entries[debugger.position - 1].clock_tick < tick);
+
+            let at = debugger.position;
+            debugger.command("s");
+            assert_eq!(debugger.position, at + 1);
+            debugger.command("S");
+            debugger.command("S");
+            assert_eq!(debugger.position, at - 1);
+
+            debugger.position = 0;
+            debugger.command("S");
+            assert_eq!(debugger.position, 0);
+            debugger.position = entries.len() - 1;
+            debugger.command("s");
+            assert_eq!(debugger.position, entries.len() - 1);
+        });
+    }
+
+    #[test]
+    pub fn goes_to_ticks_and_shows_without_moving() {
+        with_entries(|entries, arena| {
+            let mut debugger = Debugger::new(entries, arena);
+            debugger.command("g 20");
+            let at = debugger.position;
+            assert!(entries[at].clock_tick >= 20);
+            assert!(entries[at - 1].clock_tick < 20);
+
+            for command in &["g", "g soon", "c", "c 4", "m", "a", "?"] {
+                assert!(debugger.command(command));
+                assert_eq!(debugger.position, at, "{}", command);
+            }
+            debugger.command("g 100000");
+            assert_eq!(debugger.position, entries.len() - 1);
+            assert!(!debugger.command("q"));
+        });
+    }
+
+    #[test]
+    pub fn shows_considered_actions_scored_nan() {
+        with_entries(|entries, arena| {
+            let mut entries = entries.to_vec();
+            for entry in &mut entries {
+                if let Event::ConsideredAction(_, _, ref mut consideration) = entry.event {
+                    consideration.value = Some(f32::NAN);
+                    consideration.rejected = None;
+                }
+            }
+            let mut debugger = Debugger::new(&entries, arena);
+            debugger.command("n");
+            assert!(debugger.command("a"));
+        });
+    }
+}
@@ -1,2 +1,5 @@
 pub mod python;
 pub mod rust;
+pub mod schema;
+#[cfg(test)]
+pub mod test_support;
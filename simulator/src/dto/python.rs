@@ -47,6 +47,8 @@ pub struct Patch {
     pub ability: AbilityData,
     pub equipment: EquipmentData,
     pub base_stats: BaseStatsData,
+    #[serde(default)]
+    pub status_durations: HashMap<String, i32>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -4,9 +4,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::dto::python;
 use crate::sim::{Condition, ConditionFlags, Element, ElementFlags, Facing, WeaponType};
-use crate::sim::{Gender, Sign};
+use crate::sim::{Gender, Sign, MAX_STATUS_DURATION};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct MatchUp {
     pub tournament_id: u64,
     pub modified: u64,
@@ -18,39 +18,40 @@ pub struct MatchUp {
 }
 
 impl MatchUp {
-    pub fn from_python(match_up: python::MatchUp, arena: python::Arena) -> MatchUp {
-        MatchUp {
+    /// Fails on a combatant with a gender or zodiac sign that doesn't parse.
+    pub fn from_python(match_up: python::MatchUp, arena: python::Arena) -> Result<MatchUp, String> {
+        Ok(MatchUp {
             tournament_id: match_up.tournament_id,
             modified: match_up.tournament_id as u64,
-            left: Team::from_python(match_up.left),
-            right: Team::from_python(match_up.right),
+            left: Team::from_python(match_up.left)?,
+            right: Team::from_python(match_up.right)?,
             left_wins: match_up.left_wins,
             arena_name: match_up.game_map,
             arena: Arena::from_python(arena),
-        }
+        })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Team {
     pub color: String,
     pub combatants: Vec<Combatant>,
 }
 
 impl Team {
-    pub fn from_python(team: python::Team) -> Team {
-        Team {
+    pub fn from_python(team: python::Team) -> Result<Team, String> {
+        Ok(Team {
             color: team.color,
             combatants: team
                 .combatants
                 .into_iter()
                 .map(Combatant::from_python)
-                .collect(),
-        }
+                .collect::<Result<_, _>>()?,
+        })
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Combatant {
     pub name: String,
     pub gender: Gender,
@@ -71,13 +72,25 @@ pub struct Combatant {
 }
 
 impl Combatant {
-    pub fn from_python(mut combatant: python::Combatant) -> Combatant {
+    pub fn from_python(mut combatant: python::Combatant) -> Result<Combatant, String> {
+        let gender = Gender::parse(&combatant.gender).ok_or_else(|| {
+            format!(
+                "{} has an unknown gender {:?}",
+                combatant.name, combatant.gender
+            )
+        })?;
+        let sign = Sign::parse(&combatant.sign).ok_or_else(|| {
+            format!(
+                "{} has an unknown sign {:?}",
+                combatant.name, combatant.sign
+            )
+        })?;
         let mut all_abilities = combatant.class_skills;
         all_abilities.append(&mut combatant.extra_skills);
-        Combatant {
+        Ok(Combatant {
             name: combatant.name,
-            gender: Gender::parse(&combatant.gender).unwrap(),
-            sign: Sign::parse(&combatant.sign).unwrap(),
+            gender,
+            sign,
             brave: combatant.brave,
             faith: combatant.faith,
             class: combatant.class,
@@ -91,30 +104,48 @@ impl Combatant {
             armor: combatant.armor,
             accessory: combatant.accessory,
             all_abilities,
-        }
+        })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct Patch {
     pub time: u64,
     pub ability: AbilityData,
     pub equipment: EquipmentData,
     pub base_stats: BaseStatsData,
+    /// How long the conditions FFTBattleground changed last in this patch, see `StatusRules`.
+    pub status_durations: Vec<(Condition, i32)>,
 }
 
 impl Patch {
-    pub fn from_python(patch: python::Patch) -> Patch {
-        Patch {
+    /// Fails on a status duration for a condition that doesn't exist, or one too long to count
+    /// down, see `MAX_STATUS_DURATION`.
+    pub fn from_python(patch: python::Patch) -> Result<Patch, String> {
+        let mut status_durations = Vec::with_capacity(patch.status_durations.len());
+        for (name, duration) in patch.status_durations {
+            let condition = Condition::parse(&name)
+                .ok_or_else(|| format!("unknown condition {:?} in status durations", name))?;
+            if !(0..=MAX_STATUS_DURATION).contains(&duration) {
+                return Err(format!(
+                    "{} lasting {} is out of range, it has to be 0 to {}",
+                    name, duration, MAX_STATUS_DURATION
+                ));
+            }
+            status_durations.push((condition, duration));
+        }
+        status_durations.sort_by_key(|(condition, _)| condition.index());
+        Ok(Patch {
             time: patch.time as u64,
             ability: AbilityData::from_python(patch.ability),
             equipment: EquipmentData::from_python(patch.equipment),
             base_stats: BaseStatsData::from_python(patch.base_stats),
-        }
+            status_durations,
+        })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct AbilityData {
     pub by_name: HashMap<String, Ability>,
 }
@@ -131,7 +162,7 @@ impl AbilityData {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct Ability {
     pub name: String,
     pub multiplier: Option<String>,
@@ -190,7 +221,7 @@ impl Ability {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct HitChance {
     pub ma_plus: Option<u8>,
     pub pa_plus: Option<u8>,
@@ -211,7 +242,7 @@ impl HitChance {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct EquipmentData {
     pub by_name: HashMap<String, Equipment>,
 }
@@ -228,7 +259,7 @@ impl EquipmentData {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct Equipment {
     pub name: String,
     pub hp_bonus: i16,
@@ -340,7 +371,7 @@ impl Equipment {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct BaseStatsData {
     pub by_job_gender: HashMap<(String, Gender), BaseStats>,
 }
@@ -362,7 +393,7 @@ impl BaseStatsData {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct BaseStats {
     pub job: String,
     pub gender: Gender,
@@ -434,7 +465,7 @@ impl BaseStats {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct StartingLocation {
     pub x: u8,
     pub y: u8,
@@ -463,7 +494,7 @@ impl StartingLocation {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Arena {
     pub lower: Vec<Tile>,
     pub upper: Vec<Tile>,
@@ -495,7 +526,7 @@ impl Arena {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
 pub struct Tile {
     pub height: u8,
     pub depth: u8,
@@ -0,0 +1,541 @@
+use std::io;
+
+use bincode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::dto::rust::{
+    Ability, AbilityData, Arena, BaseStats, BaseStatsData, Combatant, Equipment, EquipmentData,
+    HitChance, MatchUp, Patch, StartingLocation, Team, Tile,
+};
+use crate::sim::WeaponType;
+
+// Every stored `dto::rust` record is a u32 LE schema version followed by the bincode of the
+// value. bincode has no field names or lengths to check against, so adding a field to any of
+// these structs changes how every old record decodes: bump SCHEMA_VERSION whenever the layout
+// changes, and teach `Schema::migrate` for the affected types how to read the old one.
+
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Records written before there was a version tag, i.e. the loose `.match` & `.patch` files.
+/// These are laid out the same as schema version 1.
+pub const LEGACY_SCHEMA_VERSION: u32 = 0;
+
+/// Laid out the same as version 2, but rods were stored as `WeaponType::Pole` and axes as
+/// `WeaponType::Flail`.
+pub const POLES_AND_FLAILS_SCHEMA_VERSION: u32 = 1;
+
+/// Before `Patch::status_durations`, everything else is laid out the same as now.
+pub const NO_STATUS_DURATIONS_SCHEMA_VERSION: u32 = 2;
+
+/// Every axe there's been in a patch. Unlike rods, there's nothing else to tell them apart
+/// from flails by.
+const AXES: [&str; 3] = ["Battle Axe", "Giant Axe", "Slasher"];
+
+const VERSION_LEN: usize = 4;
+
+pub trait Schema: Serialize + DeserializeOwned {
+    /// Decodes `bytes` written by the older schema `version`.
+    fn migrate(version: u32, bytes: &[u8]) -> io::Result<Self> {
+        decode_same_layout(version, bytes)
+    }
+}
+
+impl Schema for MatchUp {}
+impl Schema for Team {}
+impl Schema for Combatant {}
+impl Schema for AbilityData {}
+impl Schema for Ability {}
+impl Schema for HitChance {}
+
+/// `Patch` up to `NO_STATUS_DURATIONS_SCHEMA_VERSION`.
+#[derive(Deserialize)]
+struct PatchWithoutStatusDurations {
+    time: u64,
+    ability: AbilityData,
+    equipment: EquipmentData,
+    base_stats: BaseStatsData,
+}
+
+impl Schema for Patch {
+    fn migrate(version: u32, bytes: &[u8]) -> io::Result<Self> {
+        let old: PatchWithoutStatusDurations = decode_same_layout(version, bytes)?;
+        let mut patch = Patch {
+            time: old.time,
+            ability: old.ability,
+            equipment: old.equipment,
+            base_stats: old.base_stats,
+            status_durations: vec![],
+        };
+        if version <= POLES_AND_FLAILS_SCHEMA_VERSION {
+            patch
+                .equipment
+                .by_name
+                .values_mut()
+                .for_each(split_rods_and_axes);
+        }
+        Ok(patch)
+    }
+}
+
+impl Schema for EquipmentData {
+    fn migrate(version: u32, bytes: &[u8]) -> io::Result<Self> {
+        let mut data: EquipmentData = decode_same_layout(version, bytes)?;
+        if version <= POLES_AND_FLAILS_SCHEMA_VERSION {
+            data.by_name.values_mut().for_each(split_rods_and_axes);
+        }
+        Ok(data)
+    }
+}
+
+impl Schema for Equipment {
+    fn migrate(version: u32, bytes: &[u8]) -> io::Result<Self> {
+        let mut equipment: Equipment = decode_same_layout(version, bytes)?;
+        if version <= POLES_AND_FLAILS_SCHEMA_VERSION {
+            split_rods_and_axes(&mut equipment);
+        }
+        Ok(equipment)
+    }
+}
+
+impl Schema for BaseStatsData {}
+impl Schema for BaseStats {}
+impl Schema for StartingLocation {}
+impl Schema for Arena {}
+impl Schema for Tile {}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Decodes a record from an older schema version as `T`, which has to be laid out as that
+/// version was.
+fn decode_same_layout<T: DeserializeOwned>(version: u32, bytes: &[u8]) -> io::Result<T> {
+    match version {
+        LEGACY_SCHEMA_VERSION
+        | POLES_AND_FLAILS_SCHEMA_VERSION
+        | NO_STATUS_DURATIONS_SCHEMA_VERSION => decode_exact(bytes),
+        _ => Err(invalid_data(format!(
+            "no migration for {} from schema version {}",
+            short_type_name::<T>(),
+            version
+        ))),
+    }
+}
+
+/// Gives the rods & axes of a record from `POLES_AND_FLAILS_SCHEMA_VERSION` or earlier their
+/// own weapon types. Rods are the one range poles.
+fn split_rods_and_axes(equipment: &mut Equipment) {
+    match equipment.weapon_type {
+        Some(WeaponType::Pole) if equipment.range == 1 => {
+            equipment.weapon_type = Some(WeaponType::Rod);
+        }
+        Some(WeaponType::Flail) if AXES.contains(&equipment.name.as_str()) => {
+            equipment.weapon_type = Some(WeaponType::Axe);
+        }
+        _ => {}
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Decodes a value, insisting that it used up all of `bytes`. A record that decodes with
+/// bytes left over was written with a different layout.
+fn decode_exact<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    let mut reader = bytes;
+    let val = bincode::deserialize_from(&mut reader).map_err(|err| {
+        invalid_data(format!(
+            "couldn't decode {}: {}",
+            short_type_name::<T>(),
+            err
+        ))
+    })?;
+    if !reader.is_empty() {
+        return Err(invalid_data(format!(
+            "{} has {} unexpected trailing bytes",
+            short_type_name::<T>(),
+            reader.len()
+        )));
+    }
+    Ok(val)
+}
+
+pub fn encode<T: Schema>(val: &T) -> io::Result<Vec<u8>> {
+    let mut out = SCHEMA_VERSION.to_le_bytes().to_vec();
+    bincode::serialize_into(&mut out, val).map_err(invalid_data)?;
+    Ok(out)
+}
+
+/// The schema version a record written by `encode` was written with.
+pub fn version_of(bytes: &[u8]) -> io::Result<u32> {
+    if bytes.len() < VERSION_LEN {
+        return Err(invalid_data("record is too short to have a schema version"));
+    }
+    let mut version = [0; VERSION_LEN];
+    version.copy_from_slice(&bytes[0..VERSION_LEN]);
+    Ok(u32::from_le_bytes(version))
+}
+
+/// Decodes a record written by `encode`, migrating it if it's from an older schema.
+pub fn decode<T: Schema>(bytes: &[u8]) -> io::Result<T> {
+    let version = version_of(bytes).map_err(|_| {
+        invalid_data(format!(
+            "{} record is too short to have a schema version",
+            short_type_name::<T>()
+        ))
+    })?;
+    let rest = &bytes[VERSION_LEN..];
+    if version == SCHEMA_VERSION {
+        decode_exact(rest)
+    } else if version > SCHEMA_VERSION {
+        Err(invalid_data(format!(
+            "{} has schema version {}, this simulator only knows up to {}",
+            short_type_name::<T>(),
+            version,
+            SCHEMA_VERSION
+        )))
+    } else {
+        T::migrate(version, rest)
+    }
+}
+
+/// Decodes a record from before there was a version tag.
+pub fn decode_legacy<T: Schema>(bytes: &[u8]) -> io::Result<T> {
+    T::migrate(LEGACY_SCHEMA_VERSION, bytes)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+
+    use super::*;
+    use crate::sim::{Condition, Element, Facing, Gender, Sign};
+
+    fn round_trip<T: Schema + PartialEq + Debug>(val: T) {
+        let bytes = encode(&val).unwrap();
+        assert_eq!(decode::<T>(&bytes).unwrap(), val);
+
+        let legacy = bincode::serialize(&val).unwrap();
+        assert_eq!(decode_legacy::<T>(&legacy).unwrap(), val);
+    }
+
+    /// `patch` laid out as it was up to `NO_STATUS_DURATIONS_SCHEMA_VERSION`.
+    pub fn without_status_durations(patch: &Patch) -> Vec<u8> {
+        bincode::serialize(&(
+            patch.time,
+            &patch.ability,
+            &patch.equipment,
+            &patch.base_stats,
+        ))
+        .unwrap()
+    }
+
+    fn sample_tile() -> Tile {
+        Tile {
+            height: 3,
+            depth: 1,
+            slope_type: 2,
+            surface_type: 14,
+            slope_height: 1,
+            no_cursor: false,
+            no_walk: true,
+        }
+    }
+
+    fn sample_starting_location() -> StartingLocation {
+        StartingLocation {
+            x: 1,
+            y: 2,
+            facing: Facing::West,
+            left_team: true,
+            unit: 3,
+            layer: false,
+        }
+    }
+
+    fn sample_arena() -> Arena {
+        Arena {
+            lower: vec![sample_tile(); 4],
+            upper: vec![sample_tile(); 4],
+            width: 2,
+            height: 2,
+            starting_locations: vec![sample_starting_location()],
+        }
+    }
+
+    pub fn sample_combatant() -> Combatant {
+        Combatant {
+            name: "Birb".to_owned(),
+            gender: Gender::Female,
+            sign: Sign::Libra,
+            brave: 70,
+            faith: 65,
+            class: "Wizard".to_owned(),
+            action_skill: "Black Magic".to_owned(),
+            reaction_skill: "Counter".to_owned(),
+            support_skill: "Short Charge".to_owned(),
+            move_skill: "Move+1".to_owned(),
+            main_hand: "Oak Staff".to_owned(),
+            off_hand: "".to_owned(),
+            head: "Feather Hat".to_owned(),
+            armor: "Black Robe".to_owned(),
+            accessory: "Magic Ring".to_owned(),
+            all_abilities: vec!["Fire".to_owned(), "Bolt 2".to_owned()],
+        }
+    }
+
+    fn sample_team() -> Team {
+        Team {
+            color: "red".to_owned(),
+            combatants: vec![sample_combatant()],
+        }
+    }
+
+    pub fn sample_match_up() -> MatchUp {
+        MatchUp {
+            tournament_id: 1_590_000_000,
+            modified: 1_590_000_000,
+            left: sample_team(),
+            right: sample_team(),
+            left_wins: Some(false),
+            arena_name: "MAP 001".to_owned(),
+            arena: sample_arena(),
+        }
+    }
+
+    fn sample_hit_chance() -> HitChance {
+        HitChance {
+            ma_plus: Some(180),
+            pa_plus: None,
+            speed_plus: None,
+            pa_wp_plus: None,
+            times_faith: true,
+        }
+    }
+
+    fn sample_ability() -> Ability {
+        Ability {
+            name: "Fire".to_owned(),
+            multiplier: Some("MA".to_owned()),
+            hit_chance: sample_hit_chance(),
+            damage: true,
+            heals: false,
+            element: Some(Element::Fire),
+            range: 4,
+            aoe: Some(1),
+            ct: 4,
+            mp: 6,
+            ma_constant: Some(14),
+            adds: 0,
+            cancels: 0,
+            chance_to_add: 0,
+            chance_to_cancel: 0,
+        }
+    }
+
+    pub fn sample_equipment() -> Equipment {
+        Equipment {
+            name: "Oak Staff".to_owned(),
+            hp_bonus: 0,
+            mp_bonus: 10,
+            speed_bonus: 0,
+            pa_bonus: 0,
+            ma_bonus: 1,
+            wp: 3,
+            absorbs_hp: false,
+            range: 1,
+            w_ev: 20,
+            phys_ev: 0,
+            magic_ev: 0,
+            move_bonus: 0,
+            jump_bonus: 0,
+            weapon_type: Some(WeaponType::Staff),
+            weapon_element: None,
+            strengthens: 0,
+            absorbs: 0,
+            halves: 0,
+            weaknesses: 0,
+            cancels: 0,
+            cancels_element: 0,
+            initial: 0,
+            permanent: 0,
+            chance_to_add: 0,
+            chance_to_cancel: 0,
+            immune_to: 0,
+        }
+    }
+
+    pub fn sample_base_stats() -> BaseStats {
+        BaseStats {
+            job: "Wizard".to_owned(),
+            gender: Gender::Female,
+            hp: 150,
+            mp: 80,
+            movement: 3,
+            jump: 3,
+            speed: 8,
+            pa: 4,
+            ma: 12,
+            c_ev: 5,
+            innates: vec![],
+            innate_conditions: 0,
+            skills: vec!["Fire".to_owned()],
+            absorbs: 0,
+            halves: 0,
+            weaknesses: 0,
+            cancels: 0,
+        }
+    }
+
+    pub fn sample_patch() -> Patch {
+        let mut abilities = HashMap::new();
+        abilities.insert("Fire".to_owned(), sample_ability());
+        let mut equipment = HashMap::new();
+        equipment.insert("Oak Staff".to_owned(), sample_equipment());
+        let mut base_stats = HashMap::new();
+        base_stats.insert(("Wizard".to_owned(), Gender::Female), sample_base_stats());
+        let mut male = sample_base_stats();
+        male.gender = Gender::Male;
+        base_stats.insert(("Wizard".to_owned(), Gender::Male), male);
+        Patch {
+            time: 1_590_000_000,
+            ability: AbilityData { by_name: abilities },
+            equipment: EquipmentData { by_name: equipment },
+            base_stats: BaseStatsData {
+                by_job_gender: base_stats,
+            },
+            status_durations: vec![],
+        }
+    }
+
+    #[test]
+    pub fn round_trips_match_ups() {
+        round_trip(sample_tile());
+        round_trip(sample_starting_location());
+        round_trip(sample_arena());
+        round_trip(sample_combatant());
+        round_trip(sample_team());
+        round_trip(sample_match_up());
+    }
+
+    #[test]
+    pub fn round_trips_patches() {
+        round_trip(sample_hit_chance());
+        round_trip(sample_ability());
+        round_trip(sample_equipment());
+        round_trip(sample_base_stats());
+
+        let mut patch = sample_patch();
+        patch.status_durations = vec![(Condition::Haste, 24)];
+        assert_eq!(decode::<Patch>(&encode(&patch).unwrap()).unwrap(), patch);
+    }
+
+    #[test]
+    pub fn migrates_patches_without_status_durations() {
+        let patch = sample_patch();
+        let old = without_status_durations(&patch);
+        for version in &[
+            LEGACY_SCHEMA_VERSION,
+            POLES_AND_FLAILS_SCHEMA_VERSION,
+            NO_STATUS_DURATIONS_SCHEMA_VERSION,
+        ] {
+            let mut bytes = version.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&old);
+            assert_eq!(decode::<Patch>(&bytes).unwrap(), patch);
+        }
+        assert_eq!(decode_legacy::<Patch>(&old).unwrap(), patch);
+    }
+
+    #[test]
+    pub fn rejects_newer_schemas() {
+        let mut bytes = encode(&sample_tile()).unwrap();
+        bytes[0..4].copy_from_slice(&(SCHEMA_VERSION + 1).to_le_bytes());
+        let err = decode::<Tile>(&bytes).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    pub fn rejects_mismatched_layouts() {
+        // A record that doesn't line up with the struct must error rather than panic or
+        // silently decode as something else.
+        let mut bytes = encode(&sample_tile()).unwrap();
+        bytes.push(0);
+        assert!(decode::<Tile>(&bytes).is_err());
+
+        let bytes = encode(&sample_tile()).unwrap();
+        assert!(decode::<Combatant>(&bytes).is_err());
+        assert!(decode::<Tile>(&bytes[0..6]).is_err());
+        assert!(decode::<Tile>(&[]).is_err());
+    }
+
+    #[test]
+    pub fn layout_is_pinned_to_schema_version() {
+        // If this fails then the on-disk layout of a `dto::rust` type changed, bump
+        // SCHEMA_VERSION & add a migration from the old one before updating these numbers.
+        assert_eq!(SCHEMA_VERSION, 3);
+        assert_eq!(bincode::serialize(&sample_match_up()).unwrap().len(), 583);
+        assert_eq!(bincode::serialize(&sample_ability()).unwrap().len(), 72);
+        assert_eq!(bincode::serialize(&sample_equipment()).unwrap().len(), 93);
+        assert_eq!(bincode::serialize(&sample_base_stats()).unwrap().len(), 69);
+        let mut patch = sample_patch();
+        patch.status_durations = vec![(Condition::Haste, 24)];
+        assert_eq!(
+            bincode::serialize(&patch).unwrap().len(),
+            without_status_durations(&patch).len() + 8 + 5
+        );
+    }
+
+    #[test]
+    pub fn migrates_rods_and_axes_to_their_own_types() {
+        let weapon = |name: &str, range: u8, weapon_type: WeaponType| Equipment {
+            name: name.to_owned(),
+            range,
+            weapon_type: Some(weapon_type),
+            ..sample_equipment()
+        };
+        let mut patch = sample_patch();
+        for equipment in [
+            weapon("Flame Rod", 1, WeaponType::Pole),
+            weapon("Musk Rod", 2, WeaponType::Pole),
+            weapon("Giant Axe", 1, WeaponType::Flail),
+            weapon("Morning Star", 1, WeaponType::Flail),
+        ] {
+            patch
+                .equipment
+                .by_name
+                .insert(equipment.name.clone(), equipment);
+        }
+        let weapon_type = |patch: &Patch, name: &str| patch.equipment.by_name[name].weapon_type;
+
+        let mut old = POLES_AND_FLAILS_SCHEMA_VERSION.to_le_bytes().to_vec();
+        old.extend(without_status_durations(&patch));
+        let mut unsplit = NO_STATUS_DURATIONS_SCHEMA_VERSION.to_le_bytes().to_vec();
+        unsplit.extend(without_status_durations(&patch));
+        assert_eq!(decode::<Patch>(&unsplit).unwrap(), patch);
+
+        for migrated in [
+            decode::<Patch>(&old).unwrap(),
+            decode_legacy::<Patch>(&old[4..]).unwrap(),
+        ] {
+            assert_eq!(weapon_type(&migrated, "Flame Rod"), Some(WeaponType::Rod));
+            assert_eq!(weapon_type(&migrated, "Musk Rod"), Some(WeaponType::Pole));
+            assert_eq!(weapon_type(&migrated, "Giant Axe"), Some(WeaponType::Axe));
+            assert_eq!(
+                weapon_type(&migrated, "Morning Star"),
+                Some(WeaponType::Flail)
+            );
+            assert_eq!(weapon_type(&migrated, "Oak Staff"), Some(WeaponType::Staff));
+        }
+
+        // Current records are taken as they are.
+        let current = decode::<Patch>(&encode(&patch).unwrap()).unwrap();
+        assert_eq!(current, patch);
+    }
+}
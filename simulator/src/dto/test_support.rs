@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+pub use crate::dto::schema::tests::{sample_combatant, sample_equipment, sample_patch};
+
+use crate::dto::rust::{MatchUp, StartingLocation, Tile};
+use crate::dto::schema::tests::sample_match_up;
+use crate::runner::{match_to_combatant_infos, match_to_combatants};
+use crate::sim::{Arena, Facing, Pathfinder, Simulation};
+
+/// Four sample Wizards on each side.
+pub fn sample_teams() -> MatchUp {
+    let mut match_up = sample_match_up();
+    match_up.left.combatants = (0..4).map(|_| sample_combatant()).collect();
+    match_up.right.combatants = (0..4).map(|_| sample_combatant()).collect();
+    match_up
+}
+
+pub fn flat_tile() -> Tile {
+    Tile {
+        height: 1,
+        depth: 0,
+        slope_type: 0,
+        surface_type: 0,
+        slope_height: 0,
+        no_cursor: false,
+        no_walk: false,
+    }
+}
+
+/// An 8x8 field of height 1 on both layers, with no starting locations.
+pub fn flat_arena() -> Arena {
+    arena_with(|_lower, _upper| {})
+}
+
+/// `flat_arena` with the left team spread along the north edge facing south, and the right
+/// team along the south edge facing north.
+pub fn facing_off_arena() -> Arena {
+    let starting_locations = (0..8)
+        .map(|i| StartingLocation {
+            x: (i % 4) * 2,
+            y: if i < 4 { 0 } else { 7 },
+            facing: if i < 4 { Facing::South } else { Facing::North },
+            left_team: i < 4,
+            unit: i % 4,
+            layer: false,
+        })
+        .collect();
+    let tiles = vec![flat_tile(); 64];
+    Arena::new(tiles.clone(), tiles, 8, 8, starting_locations)
+}
+
+/// `flat_arena` after `tweak` has changed the lower & upper tiles.
+pub fn arena_with(tweak: impl FnOnce(&mut [Tile], &mut [Tile])) -> Arena {
+    let mut lower = vec![flat_tile(); 64];
+    let mut upper = vec![flat_tile(); 64];
+    tweak(&mut lower, &mut upper);
+    Arena::new(lower, upper, 8, 8, vec![])
+}
+
+/// Runs `check` on a fresh simulation of `sample_teams` on `arena`, seeded with 0, after
+/// `tweak` has had its way with the match up.
+pub fn sim_with<R>(
+    arena: &Arena,
+    tweak: impl FnOnce(&mut MatchUp),
+    check: impl FnOnce(&mut Simulation) -> R,
+) -> R {
+    let patch = sample_patch();
+    let mut match_up = sample_teams();
+    tweak(&mut match_up);
+    let infos = match_to_combatant_infos(&patch, &match_up);
+    let combatants = match_to_combatants(&infos);
+    let pathfinder = RefCell::new(Pathfinder::new(arena));
+    let mut sim = Simulation::new(
+        combatants,
+        arena,
+        &pathfinder,
+        SmallRng::seed_from_u64(0),
+        false,
+    );
+    check(&mut sim)
+}
@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::de::{Deserializer, Error};
+use serde::Deserialize;
+use toml;
+
+use crate::dto::rust::{Combatant, MatchUp, Patch};
+use crate::sim::{CombatantInfo, Gender};
+
+pub const PRESETS_PATH: &str = "data/filters.toml";
+
+const DAY_IN_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+// Abilities CombatantInfo reads by name to set up Jump & Math Skill, rather than looking
+// them up as abilities of their own.
+const HANDLED_BY_NAME: &[&str] = &["CT", "Height", "Prime Number", "5", "4", "3"];
+
+/// An inclusive range written as `MIN..MAX`, where either end can be left off, or as a
+/// single value to match exactly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T: PartialOrd + Copy> Bounds<T> {
+    pub fn contains(&self, value: T) -> bool {
+        self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value <= max)
+    }
+}
+
+impl<T: FromStr + Clone> FromStr for Bounds<T>
+where
+    T::Err: Display,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |part: &str| -> Result<Option<T>, String> {
+            let part = part.trim();
+            if part.is_empty() {
+                return Ok(None);
+            }
+            part.parse()
+                .map(Some)
+                .map_err(|err| format!("bad bound '{}': {}", part, err))
+        };
+        match s.find("..") {
+            Some(idx) => Ok(Bounds {
+                min: parse(&s[..idx])?,
+                max: parse(&s[idx + 2..])?,
+            }),
+            None => {
+                let value = parse(s)?.ok_or_else(|| "empty range".to_owned())?;
+                Ok(Bounds {
+                    min: Some(value.clone()),
+                    max: Some(value),
+                })
+            }
+        }
+    }
+}
+
+impl<'de, T: FromStr + Clone> Deserialize<'de> for Bounds<T>
+where
+    T::Err: Display,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+/// A calendar day, `YYYY-MM-DD` in UTC, kept as milliseconds since the epoch so it can be
+/// compared against tournament IDs.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Date(pub u64);
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("expected a date like 2020-04-01, not '{}'", s);
+        let parts: Vec<_> = s.split('-').collect();
+        if parts.len() != 3 {
+            return Err(bad());
+        }
+        let year: i64 = parts[0].parse().map_err(|_| bad())?;
+        let month: i64 = parts[1].parse().map_err(|_| bad())?;
+        let day: i64 = parts[2].parse().map_err(|_| bad())?;
+        if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(bad());
+        }
+        Ok(Date(
+            days_from_civil(year, month, day) as u64 * DAY_IN_MILLIS,
+        ))
+    }
+}
+
+// Howard Hinnant's days_from_civil, days since 1970-01-01 in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Everything a filter can look at. The time out rate is only known once the match up has
+/// been simulated.
+pub struct MatchInfo<'a> {
+    pub match_up: &'a MatchUp,
+    pub patch: &'a Patch,
+    pub combatants: &'a [CombatantInfo<'a>],
+    pub time_out_rate: Option<f64>,
+}
+
+impl<'a> MatchInfo<'a> {
+    fn sources(&self) -> impl Iterator<Item = &'a Combatant> {
+        self.match_up
+            .left
+            .combatants
+            .iter()
+            .chain(self.match_up.right.combatants.iter())
+    }
+}
+
+/// Which match ups `simulator test` should run. Every condition given has to hold, `any`
+/// needs at least one of its filters to match and `not` needs none of them to. Conditions
+/// about units hold if any one unit in the match up satisfies them.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchFilter {
+    pub equip: Vec<String>,
+    pub ability: Vec<String>,
+    pub skill: Vec<String>,
+    pub class: Vec<String>,
+    pub color: Vec<String>,
+    pub map: Vec<String>,
+    pub monsters: Option<bool>,
+    pub unimplemented: Option<bool>,
+    pub tournament: Option<Bounds<u64>>,
+    pub date: Option<Bounds<Date>>,
+    pub patch: Option<Bounds<u64>>,
+    pub brave: Option<Bounds<i8>>,
+    pub faith: Option<Bounds<i8>>,
+    pub time_out_rate: Option<Bounds<f64>>,
+    pub all: Vec<MatchFilter>,
+    pub any: Vec<MatchFilter>,
+    pub not: Vec<MatchFilter>,
+}
+
+impl MatchFilter {
+    /// Whether the match up passes, or None if that depends on its time out rate and it
+    /// hasn't been simulated yet.
+    pub fn check(&self, info: &MatchInfo) -> Option<bool> {
+        let mut result = Some(true);
+        let mut and = |outcome: Option<bool>| {
+            result = match (result, outcome) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            }
+        };
+
+        let infos = info.combatants;
+        and(Some(self.equip.iter().all(|name| has_equip(infos, name))));
+        and(Some(
+            self.ability.iter().all(|name| has_ability(infos, name)),
+        ));
+        and(Some(self.skill.iter().all(|name| has_skill(infos, name))));
+        and(Some(self.class.iter().all(|name| has_class(infos, name))));
+        and(Some(self.color.iter().all(|color| {
+            info.match_up.left.color == *color || info.match_up.right.color == *color
+        })));
+        and(Some(
+            self.map
+                .iter()
+                .all(|map| info.match_up.arena_name.contains(map.as_str())),
+        ));
+        if let Some(monsters) = self.monsters {
+            and(Some(has_monster(infos) == monsters));
+        }
+        if let Some(unimplemented) = self.unimplemented {
+            let any = info.sources().zip(infos).any(|(src, combatant)| {
+                unimplemented_abilities(src, combatant, info.patch)
+                    .next()
+                    .is_some()
+            });
+            and(Some(any == unimplemented));
+        }
+        if let Some(bounds) = self.tournament {
+            and(Some(bounds.contains(info.match_up.tournament_id)));
+        }
+        if let Some(bounds) = self.date {
+            let time = info.match_up.tournament_id;
+            let after_min = bounds.min.map_or(true, |min| time >= min.0);
+            let before_max = bounds.max.map_or(true, |max| time < max.0 + DAY_IN_MILLIS);
+            and(Some(after_min && before_max));
+        }
+        if let Some(bounds) = self.patch {
+            and(Some(bounds.contains(info.patch.time)));
+        }
+        if let Some(bounds) = self.brave {
+            and(Some(
+                infos.iter().any(|c| bounds.contains(c.starting_brave)),
+            ));
+        }
+        if let Some(bounds) = self.faith {
+            and(Some(
+                infos.iter().any(|c| bounds.contains(c.starting_faith)),
+            ));
+        }
+        if let Some(bounds) = self.time_out_rate {
+            and(info.time_out_rate.map(|rate| bounds.contains(rate)));
+        }
+
+        for filter in &self.all {
+            and(filter.check(info));
+        }
+        if !self.any.is_empty() {
+            let mut any = Some(false);
+            for filter in &self.any {
+                any = match (any, filter.check(info)) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                };
+            }
+            and(any);
+        }
+        for filter in &self.not {
+            and(filter.check(info).map(|matched| !matched));
+        }
+        result
+    }
+}
+
+/// Reads the named preset out of a TOML file of them, each one a table of `MatchFilter`
+/// fields, e.g.
+///
+/// ```toml
+/// [mages]
+/// date = "2020-04-01.."
+/// any = [{ class = ["Wizard"] }, { class = ["Summoner"] }]
+/// not = [{ monsters = true }]
+/// ```
+pub fn load_preset<P: AsRef<Path>>(path: P, name: &str) -> io::Result<MatchFilter> {
+    let text = fs::read_to_string(path.as_ref())?;
+    let mut presets: HashMap<String, MatchFilter> =
+        toml::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    presets.remove(name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no preset '{}' in {}", name, path.as_ref().display()),
+        )
+    })
+}
+
+pub fn has_equip(combatants: &[CombatantInfo], name: &str) -> bool {
+    combatants.iter().any(|info| {
+        info.main_hand.map_or(false, |eq| eq.name == name)
+            || info.off_hand.map_or(false, |eq| eq.name == name)
+            || info.headgear.map_or(false, |eq| eq.name == name)
+            || info.armor.map_or(false, |eq| eq.name == name)
+            || info.accessory.map_or(false, |eq| eq.name == name)
+    })
+}
+
+pub fn has_ability(combatants: &[CombatantInfo], name: &str) -> bool {
+    combatants
+        .iter()
+        .any(|info| info.abilities.iter().any(|ability| ability.name == name))
+}
+
+pub fn has_skill(combatants: &[CombatantInfo], name: &str) -> bool {
+    combatants
+        .iter()
+        .any(|info| info.all_skills.iter().any(|skill| *skill == name))
+}
+
+pub fn has_monster(combatants: &[CombatantInfo]) -> bool {
+    combatants.iter().any(|info| info.gender == Gender::Monster)
+}
+
+/// Class names are compared ignoring case & spaces, so `timemage` finds Time Mages.
+pub fn has_class(combatants: &[CombatantInfo], name: &str) -> bool {
+    let normalize = |s: &str| s.replace(" ", "").to_lowercase();
+    let name = normalize(name);
+    combatants.iter().any(|info| normalize(info.job) == name)
+}
+
+/// The abilities the patch knows about that this combatant has, but that the simulator
+/// doesn't implement yet.
+pub fn unimplemented_abilities<'b>(
+    src: &'b Combatant,
+    info: &'b CombatantInfo,
+    patch: &'b Patch,
+) -> impl Iterator<Item = &'b str> {
+    src.all_abilities
+        .iter()
+        .map(|name| name.as_str())
+        .filter(move |name| patch.ability.by_name.contains_key(*name))
+        .filter(move |name| !info.abilities.iter().any(|ability| ability.name == *name))
+        .filter(|name| {
+            !HANDLED_BY_NAME.contains(name)
+                && !name.starts_with("Level Jump")
+                && !name.starts_with("Vertical Jump")
+        })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parses_bounds() {
+        let bounds: Bounds<i8> = "40..70".parse().unwrap();
+        assert!(bounds.contains(40) && bounds.contains(70));
+        assert!(!bounds.contains(39) && !bounds.contains(71));
+
+        let bounds: Bounds<i8> = "..50".parse().unwrap();
+        assert!(bounds.contains(-10) && !bounds.contains(51));
+
+        let bounds: Bounds<f64> = "0.25..".parse().unwrap();
+        assert!(bounds.contains(0.25) && !bounds.contains(0.2));
+
+        let bounds: Bounds<u64> = "12".parse().unwrap();
+        assert!(bounds.contains(12) && !bounds.contains(13));
+
+        assert!("".parse::<Bounds<i8>>().is_err());
+        assert!("a..b".parse::<Bounds<i8>>().is_err());
+    }
+
+    #[test]
+    pub fn parses_dates() {
+        assert_eq!("1970-01-01".parse::<Date>().unwrap(), Date(0));
+        // 1584818551017 is a tournament from the 21st of March 2020.
+        let day: Date = "2020-03-21".parse().unwrap();
+        assert!(day.0 <= 1_584_818_551_017 && 1_584_818_551_017 < day.0 + DAY_IN_MILLIS);
+        assert!("2020-13-01".parse::<Date>().is_err());
+        assert!("yesterday".parse::<Date>().is_err());
+    }
+
+    #[test]
+    pub fn parses_presets() {
+        let text = r#"
+            [mages]
+            date = "2020-04-01.."
+            brave = "..50"
+            any = [{ class = ["Wizard"] }, { class = ["Summoner"] }]
+            not = [{ monsters = true }]
+
+            [slow]
+            time_out_rate = "0.5.."
+        "#;
+        let mut presets: HashMap<String, MatchFilter> = toml::from_str(text).unwrap();
+        let mages = presets.remove("mages").unwrap();
+        assert_eq!(mages.any.len(), 2);
+        assert_eq!(mages.any[1].class, vec!["Summoner".to_owned()]);
+        assert_eq!(mages.not[0].monsters, Some(true));
+        assert_eq!(mages.brave.unwrap().max, Some(50));
+        assert!(presets["slow"].time_out_rate.is_some());
+
+        let unknown: Result<HashMap<String, MatchFilter>, _> = toml::from_str("[x]\nclas = []");
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    pub fn time_out_rate_waits_for_simulation() {
+        let match_up: MatchUp = crate::dto::schema::tests::sample_match_up();
+        let patch: Patch = crate::dto::schema::tests::sample_patch();
+        let mut info = MatchInfo {
+            match_up: &match_up,
+            patch: &patch,
+            combatants: &[],
+            time_out_rate: None,
+        };
+
+        let slow = MatchFilter {
+            time_out_rate: Some("0.5..".parse().unwrap()),
+            ..MatchFilter::default()
+        };
+        let red = MatchFilter {
+            color: vec!["red".to_owned()],
+            ..MatchFilter::default()
+        };
+        let green = MatchFilter {
+            color: vec!["green".to_owned()],
+            ..MatchFilter::default()
+        };
+        assert_eq!(slow.check(&info), None);
+        assert_eq!(red.check(&info), Some(true));
+        assert_eq!(green.check(&info), Some(false));
+
+        // Only an unknown that could still change the answer stays unknown.
+        let slow_and_green = MatchFilter {
+            all: vec![slow.clone(), green.clone()],
+            ..MatchFilter::default()
+        };
+        let slow_or_red = MatchFilter {
+            any: vec![slow.clone(), red.clone()],
+            ..MatchFilter::default()
+        };
+        let not_slow = MatchFilter {
+            not: vec![slow.clone()],
+            ..MatchFilter::default()
+        };
+        assert_eq!(slow_and_green.check(&info), Some(false));
+        assert_eq!(slow_or_red.check(&info), Some(true));
+        assert_eq!(not_slow.check(&info), None);
+
+        info.time_out_rate = Some(0.75);
+        assert_eq!(slow.check(&info), Some(true));
+        assert_eq!(not_slow.check(&info), Some(false));
+        info.time_out_rate = Some(0.0);
+        assert_eq!(not_slow.check(&info), Some(true));
+    }
+}
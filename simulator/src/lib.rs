@@ -4,8 +4,10 @@ extern crate lazy_static;
 use crate::dto::{python, rust};
 use crate::runner::*;
 
+use crate::optimize::{Optimizer, Search, SlotChoice};
 use crate::sim::log::describe_entry;
-use crate::sim::{Pathfinder, Simulation};
+use crate::sim::{BattleState, Observation, Pathfinder, Phase, Simulation, Team};
+use pyo3::exceptions::ValueError;
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 use rand::rngs::SmallRng;
@@ -13,8 +15,15 @@ use rand::SeedableRng;
 use serde_json;
 use std::cell::RefCell;
 
+pub mod archive;
+pub mod compare;
 pub mod data;
+pub mod debugger;
 pub mod dto;
+pub mod filter;
+pub mod optimize;
+pub mod render;
+pub mod replay;
 pub mod runner;
 pub mod sim;
 
@@ -25,19 +34,22 @@ struct Patch {
 
 #[pymethods]
 impl Patch {
+    /// Raises ValueError if the patch doesn't parse or has a status duration that can't be
+    /// used, see `rust::Patch::from_python`.
     #[new]
-    fn new(patch_json: &str) -> Self {
-        // TODO: Exception handling
-        let py_patch: python::Patch = serde_json::from_str(&patch_json).unwrap();
-        Patch {
-            patch: rust::Patch::from_python(py_patch),
-        }
+    fn new(patch_json: &str) -> PyResult<Self> {
+        let py_patch: python::Patch =
+            serde_json::from_str(patch_json).map_err(|err| ValueError::py_err(err.to_string()))?;
+        let patch = rust::Patch::from_python(py_patch).map_err(ValueError::py_err)?;
+        Ok(Patch { patch })
     }
 }
 
 #[pyclass(module = "simulator")]
 struct Arena {
     pub arena: python::Arena,
+    // Built once here so every simulation on this map shares its tables.
+    pub sim_arena: sim::Arena,
 }
 
 #[pymethods]
@@ -46,32 +58,130 @@ impl Arena {
     fn new(arena_json: &str) -> Self {
         // TODO: Exception handling
         let py_arena: python::Arena = serde_json::from_str(&arena_json).unwrap();
-        Arena { arena: py_arena }
+        let sim_arena = sim::Arena::from_dto(rust::Arena::from_python(py_arena.clone()));
+        Arena {
+            arena: py_arena,
+            sim_arena,
+        }
     }
 }
 
+/// Raises ValueError if the match up doesn't parse.
+#[pyfunction]
+fn run_simulation(patch: &Patch, arena: &Arena, match_up: &str, num_runs: i32) -> PyResult<f64> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let combatant_infos = match_to_combatant_infos(&patch.patch, &match_up);
+    let combatants = match_to_combatants(&combatant_infos);
+    let (left_wins_percent, _time_outs) = run_many_sims(num_runs, &combatants, &arena.sim_arena);
+    Ok(left_wins_percent)
+}
+
+/// The JSON `match_up` on `arena`, raising ValueError if it doesn't parse or a combatant's
+/// gender or sign isn't one we know.
+fn parse_match_up(arena: &Arena, match_up: &str) -> PyResult<rust::MatchUp> {
+    let py_match_up: python::MatchUp =
+        serde_json::from_str(match_up).map_err(|err| ValueError::py_err(err.to_string()))?;
+    rust::MatchUp::from_python(py_match_up, arena.arena.clone()).map_err(ValueError::py_err)
+}
+
+/// Same as `run_simulation`, but every run picks up from `state`, a JSON `BattleState`, e.g.
+/// for updating the odds part way through a match. Raises ValueError if the match up or state
+/// don't parse, or the state doesn't fit the match up or the arena.
 #[pyfunction]
-fn run_simulation(patch: &Patch, arena: &Arena, match_up: &str, num_runs: i32) -> f64 {
-    // TODO: Exception handling
-    let py_match_up: python::MatchUp = serde_json::from_str(&match_up).unwrap();
-    let match_up = rust::MatchUp::from_python(py_match_up, arena.arena.clone());
-    let arena = sim::Arena::from_dto(match_up.arena.clone());
+fn run_simulation_from_state(
+    patch: &Patch,
+    arena: &Arena,
+    match_up: &str,
+    state: &str,
+    num_runs: i32,
+) -> PyResult<f64> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let state: BattleState =
+        serde_json::from_str(state).map_err(|err| ValueError::py_err(err.to_string()))?;
     let combatant_infos = match_to_combatant_infos(&patch.patch, &match_up);
     let combatants = match_to_combatants(&combatant_infos);
-    let (left_wins_percent, _time_outs) = run_many_sims(num_runs, &combatants, &arena);
-    left_wins_percent
+    let (left_wins_percent, _time_outs) =
+        run_many_sims_from_state(num_runs, &combatants, &arena.sim_arena, &state)
+            .map_err(ValueError::py_err)?;
+    Ok(left_wins_percent)
 }
 
+/// Live odds for a match in progress: the left team's chance of winning from where
+/// `observations`, a JSON list of `Observation`s seen on stream so far, say the match has got
+/// to. Raises ValueError if the match up or observations don't parse, or the observations
+/// don't make sense for the match up.
 #[pyfunction]
-fn run_logged_simulation(patch: &Patch, arena: &Arena, match_up: &str) -> Vec<String> {
-    let py_match_up: python::MatchUp = serde_json::from_str(&match_up).unwrap();
-    let match_up = rust::MatchUp::from_python(py_match_up, arena.arena.clone());
-    let arena = sim::Arena::from_dto(match_up.arena.clone());
+fn run_simulation_observed(
+    patch: &Patch,
+    arena: &Arena,
+    match_up: &str,
+    observations: &str,
+    num_runs: i32,
+) -> PyResult<f64> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let observations: Vec<Observation> =
+        serde_json::from_str(observations).map_err(|err| ValueError::py_err(err.to_string()))?;
     let combatant_infos = match_to_combatant_infos(&patch.patch, &match_up);
     let combatants = match_to_combatants(&combatant_infos);
-    let pathfinder = RefCell::new(Pathfinder::new(&arena));
+    let (left_wins_percent, _time_outs) =
+        run_many_sims_observed(num_runs, &combatants, &arena.sim_arena, &observations)
+            .map_err(ValueError::py_err)?;
+    Ok(left_wins_percent)
+}
+
+/// Searches `slots`, each written like `0:accessory=Angel Ring,Magic Ring`, for the loadout
+/// that most improves the left (or with `right`, the right) team's odds. `search` is greedy,
+/// beam, beam:N or coordinate. Only main_hand, off_hand, brave & faith can leave out their
+/// candidates. Returns the `Report` as JSON, raising ValueError if the match up doesn't parse
+/// or the slots don't fit it.
+#[pyfunction(search = "\"greedy\"", right = "false")]
+fn optimize_team(
+    patch: &Patch,
+    arena: &Arena,
+    match_up: &str,
+    slots: Vec<String>,
+    num_runs: u32,
+    search: &str,
+    right: bool,
+) -> PyResult<String> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let slots = slots
+        .iter()
+        .map(|slot| slot.parse())
+        .collect::<Result<Vec<SlotChoice>, String>>()
+        .map_err(ValueError::py_err)?;
+    let search: Search = search.parse().map_err(ValueError::py_err)?;
+    let team = if right { Team::Right } else { Team::Left };
+    let optimizer = Optimizer::new(
+        &patch.patch,
+        &match_up,
+        &arena.sim_arena,
+        team,
+        &slots,
+        num_runs,
+    )
+    .map_err(ValueError::py_err)?;
+    Ok(serde_json::to_string(&optimizer.optimize(search)).unwrap())
+}
+
+/// With `trace_ai` the log also has the AI's reasoning for every decision: the value it had to
+/// beat, each action it considered & why it was thrown out, and the action it chose. Raises
+/// ValueError if the match up doesn't parse.
+#[pyfunction(trace_ai = "false")]
+fn run_logged_simulation(
+    patch: &Patch,
+    arena: &Arena,
+    match_up: &str,
+    trace_ai: bool,
+) -> PyResult<Vec<String>> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let arena = &arena.sim_arena;
+    let combatant_infos = match_to_combatant_infos(&patch.patch, &match_up);
+    let combatants = match_to_combatants(&combatant_infos);
+    let pathfinder = RefCell::new(Pathfinder::new(arena));
     let rng = SmallRng::from_entropy();
-    let mut sim = Simulation::new(combatants.clone(), &arena, &pathfinder, rng, true);
+    let mut sim = Simulation::new(combatants.clone(), arena, &pathfinder, rng, true);
+    sim.trace_ai = trace_ai;
     sim.run();
     // for combatant in &combatants {
     //     println!("{}", unit_card(combatant));
@@ -79,9 +189,77 @@ fn run_logged_simulation(patch: &Patch, arena: &Arena, match_up: &str) -> Vec<St
     // println!("Playing on {}", &match_up.arena_name);
     let mut results = vec![];
     for entry in sim.log.entries() {
-        results.push(describe_entry(&entry, &arena));
+        results.push(describe_entry(&entry, arena));
     }
-    results
+    Ok(results)
+}
+
+/// The arena with its starting locations, as an SVG document.
+#[pyfunction]
+fn render_arena_svg(arena: &Arena) -> String {
+    render::svg_arena(&arena.sim_arena, None)
+}
+
+/// The arena with the match up's units in their starting positions, as an SVG document.
+/// Raises ValueError if the match up doesn't parse.
+#[pyfunction]
+fn render_match_svg(patch: &Patch, arena: &Arena, match_up: &str) -> PyResult<String> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let combatant_infos = match_to_combatant_infos(&patch.patch, &match_up);
+    let combatants = match_to_combatants(&combatant_infos);
+    Ok(render::svg_arena(&arena.sim_arena, Some(&combatants)))
+}
+
+/// Runs one simulation & returns a self contained HTML page replaying it. Raises ValueError if
+/// the match up doesn't parse.
+#[pyfunction]
+fn run_replay_simulation(patch: &Patch, arena: &Arena, match_up: &str) -> PyResult<String> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let title = format!(
+        "{} vs {} on {}",
+        match_up.left.color, match_up.right.color, match_up.arena_name
+    );
+    let sim_arena = &arena.sim_arena;
+    let combatant_infos = match_to_combatant_infos(&patch.patch, &match_up);
+    let combatants = match_to_combatants(&combatant_infos);
+    let pathfinder = RefCell::new(Pathfinder::new(sim_arena));
+    let rng = SmallRng::from_entropy();
+    let mut sim = Simulation::new(combatants, sim_arena, &pathfinder, rng, true);
+    sim.run();
+    Ok(replay::replay_html(&title, sim_arena, &sim.log.entries()))
+}
+
+/// The first `num_turns` active turns & slow actions from the match up's starting positions,
+/// as `(clock_tick, "active_turn" or "slow_action", unit index, unit name)`. Raises ValueError
+/// if the match up doesn't parse.
+#[pyfunction]
+fn forecast_turns(
+    patch: &Patch,
+    arena: &Arena,
+    match_up: &str,
+    num_turns: usize,
+) -> PyResult<Vec<(usize, String, usize, String)>> {
+    let match_up = parse_match_up(arena, match_up)?;
+    let sim_arena = &arena.sim_arena;
+    let combatant_infos = match_to_combatant_infos(&patch.patch, &match_up);
+    let combatants = match_to_combatants(&combatant_infos);
+    let pathfinder = RefCell::new(Pathfinder::new(sim_arena));
+    let rng = SmallRng::from_entropy();
+    let sim = Simulation::new(combatants, sim_arena, &pathfinder, rng, false);
+    let turns = sim
+        .forecast_turns(num_turns)
+        .into_iter()
+        .flat_map(|turn| {
+            let (kind, id) = match turn.phase {
+                Phase::ActiveTurn(id) => ("active_turn", id),
+                Phase::SlowAction(id) => ("slow_action", id),
+                _ => return None,
+            };
+            let name = sim.combatant(id).name().to_owned();
+            Some((turn.clock_tick, kind.to_owned(), id.index(), name))
+        })
+        .collect();
+    Ok(turns)
 }
 
 #[pymodule]
@@ -89,6 +267,13 @@ fn simulator(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<Patch>()?;
     m.add_class::<Arena>()?;
     m.add_wrapped(wrap_pyfunction!(run_simulation))?;
+    m.add_wrapped(wrap_pyfunction!(run_simulation_from_state))?;
+    m.add_wrapped(wrap_pyfunction!(run_simulation_observed))?;
+    m.add_wrapped(wrap_pyfunction!(optimize_team))?;
     m.add_wrapped(wrap_pyfunction!(run_logged_simulation))?;
+    m.add_wrapped(wrap_pyfunction!(render_arena_svg))?;
+    m.add_wrapped(wrap_pyfunction!(render_match_svg))?;
+    m.add_wrapped(wrap_pyfunction!(run_replay_simulation))?;
+    m.add_wrapped(wrap_pyfunction!(forecast_turns))?;
     Ok(())
 }
@@ -5,8 +5,17 @@ use std::io;
 
 use clap::Clap;
 
+use filter::{Bounds, Date, MatchFilter};
+
+pub mod archive;
+pub mod compare;
 pub mod data;
+pub mod debugger;
 pub mod dto;
+pub mod filter;
+pub mod optimize;
+pub mod render;
+pub mod replay;
 pub mod runner;
 pub mod sim;
 
@@ -29,10 +38,30 @@ enum SubCommand {
     #[clap(name = "run")]
     Run(Run),
 
+    /// Forecast the order of the first turns of a match, from its starting positions
+    #[clap(name = "forecast")]
+    Forecast(Forecast),
+
+    /// Search for the equipment, skills, brave & faith that would most improve a team's odds
+    #[clap(name = "optimize")]
+    Optimize(Optimize),
+
     /// Read match up & patch data from my python code on stdin, writing out the match up &
     /// patch data into a binary format this program expects.
     #[clap(name = "feed")]
     Feed(Feed),
+
+    /// Convert the old directory of match up & patch files into a single archive file.
+    #[clap(name = "archive")]
+    Archive(Archive),
+
+    /// Rewrite every record of the archive in the current data format, in place.
+    #[clap(name = "migrate")]
+    Migrate(Migrate),
+
+    /// Rewrite the archive without the space old indexes take up after appending to it.
+    #[clap(name = "compact")]
+    Compact(Compact),
 }
 
 #[derive(Clap)]
@@ -53,6 +82,10 @@ struct Test {
     #[clap(short = "m")]
     most_recent: Option<u64>,
 
+    /// Compare against predictions saved by an earlier run with --save
+    #[clap(long = "compare")]
+    compare: Option<String>,
+
     /// Filter out all matches with any monsters
     #[clap(long = "filter-no-monsters")]
     filter_no_monsters: bool,
@@ -72,6 +105,86 @@ struct Test {
     /// Filter any map
     #[clap(long = "filter-map")]
     filter_map: Vec<String>,
+
+    /// Filter for a job class
+    #[clap(long = "filter-class")]
+    filter_class: Vec<String>,
+
+    /// Filter for a team color
+    #[clap(long = "filter-color")]
+    filter_color: Vec<String>,
+
+    /// Filter for a range of tournament IDs, e.g. 1584818551017..
+    #[clap(long = "filter-tournament")]
+    filter_tournament: Option<Bounds<u64>>,
+
+    /// Filter for a range of dates, e.g. 2020-04-01..2020-04-30
+    #[clap(long = "filter-date")]
+    filter_date: Option<Bounds<Date>>,
+
+    /// Filter for a range of patch times
+    #[clap(long = "filter-patch")]
+    filter_patch: Option<Bounds<u64>>,
+
+    /// Filter for a unit with brave in a range, e.g. 40..70
+    #[clap(long = "filter-brave")]
+    filter_brave: Option<Bounds<i8>>,
+
+    /// Filter for a unit with faith in a range, e.g. ..30
+    #[clap(long = "filter-faith")]
+    filter_faith: Option<Bounds<i8>>,
+
+    /// Filter for a range of simulated time out rates, e.g. 0.5..
+    #[clap(long = "filter-time-out-rate")]
+    filter_time_out_rate: Option<Bounds<f64>>,
+
+    /// Filter for matches involving abilities that aren't implemented yet
+    #[clap(long = "filter-unimplemented")]
+    filter_unimplemented: bool,
+
+    /// Filter out matches involving abilities that aren't implemented yet
+    #[clap(long = "filter-implemented")]
+    filter_implemented: bool,
+
+    /// Also apply a named filter preset
+    #[clap(long = "preset")]
+    preset: Option<String>,
+
+    /// The TOML file to read presets from
+    #[clap(long = "presets", default_value = filter::PRESETS_PATH)]
+    presets: String,
+}
+
+impl Test {
+    fn filter(&self) -> io::Result<MatchFilter> {
+        let mut filter = MatchFilter {
+            equip: self.filter_equip.clone(),
+            ability: self.filter_ability.clone(),
+            skill: self.filter_skill.clone(),
+            class: self.filter_class.clone(),
+            color: self.filter_color.clone(),
+            map: self.filter_map.clone(),
+            tournament: self.filter_tournament,
+            date: self.filter_date,
+            patch: self.filter_patch,
+            brave: self.filter_brave,
+            faith: self.filter_faith,
+            time_out_rate: self.filter_time_out_rate,
+            ..MatchFilter::default()
+        };
+        if self.filter_no_monsters {
+            filter.monsters = Some(false);
+        }
+        if self.filter_unimplemented {
+            filter.unimplemented = Some(true);
+        } else if self.filter_implemented {
+            filter.unimplemented = Some(false);
+        }
+        if let Some(preset) = &self.preset {
+            filter.all.push(filter::load_preset(&self.presets, preset)?);
+        }
+        Ok(filter)
+    }
 }
 
 #[derive(Clap)]
@@ -82,11 +195,73 @@ struct Run {
 
     /// The match ID
     match_id: u64,
+
+    /// Step through the match turn by turn instead of printing the whole log
+    #[clap(short = "i", long = "interactive")]
+    interactive: bool,
+
+    /// Include every action the AI considered, and why it was or wasn't chosen, in the log
+    #[clap(long = "trace-ai")]
+    trace_ai: bool,
+
+    /// Also draw the arena & the units on it to this SVG file
+    #[clap(long = "svg")]
+    svg: Option<String>,
+
+    /// Draw the SVG as of this clock tick instead of the starting positions
+    #[clap(long = "svg-at")]
+    svg_at: Option<usize>,
+
+    /// Also save an animated replay of the match to this HTML file
+    #[clap(long = "replay")]
+    replay: Option<String>,
+}
+
+#[derive(Clap)]
+struct Forecast {
+    /// The number of active turns & slow actions to forecast
+    #[clap(short = "n", default_value = "16")]
+    num_turns: usize,
+
+    /// The match ID
+    match_id: u64,
+}
+
+#[derive(Clap)]
+struct Optimize {
+    /// The number of simulated matches per loadout tried
+    #[clap(short = "n")]
+    num_runs: u32,
+
+    /// The match ID
+    match_id: u64,
+
+    /// A slot to search over, e.g. 0:reaction=Counter,Hamedo for the first unit's reaction.
+    /// Only main_hand, off_hand, brave & faith can leave out their candidates
+    #[clap(long = "slot", required = true)]
+    slots: Vec<optimize::SlotChoice>,
+
+    /// greedy, beam, beam:N (the width) or coordinate
+    #[clap(long = "search", default_value = "greedy")]
+    search: optimize::Search,
+
+    /// Optimize the right team instead of the left
+    #[clap(long = "right")]
+    right: bool,
 }
 
 #[derive(Clap)]
 struct Feed {}
 
+#[derive(Clap)]
+struct Archive {}
+
+#[derive(Clap)]
+struct Migrate {}
+
+#[derive(Clap)]
+struct Compact {}
+
 fn main() -> io::Result<()> {
     let opts: Opts = Opts::parse();
 
@@ -95,14 +270,32 @@ fn main() -> io::Result<()> {
             test.num_runs,
             test.print_worst,
             test.save,
-            test.filter_equip,
-            test.filter_ability,
-            test.filter_skill,
-            test.filter_no_monsters,
-            test.filter_map,
+            test.filter()?,
             test.most_recent,
+            test.compare,
+        ),
+        SubCommand::Run(run) => runner::run_specific_match(
+            run.match_id,
+            run.num_runs,
+            run.interactive,
+            run.trace_ai,
+            run.svg,
+            run.svg_at,
+            run.replay,
+        ),
+        SubCommand::Forecast(forecast) => {
+            runner::forecast_match(forecast.match_id, forecast.num_turns)
+        }
+        SubCommand::Optimize(optimize) => runner::optimize_match(
+            optimize.match_id,
+            optimize.num_runs,
+            optimize.right,
+            &optimize.slots,
+            optimize.search,
         ),
-        SubCommand::Run(run) => runner::run_specific_match(run.match_id, run.num_runs),
         SubCommand::Feed(_feed) => data::convert_data_from_feed(),
+        SubCommand::Archive(_archive) => archive::archive_directory(),
+        SubCommand::Migrate(_migrate) => archive::migrate_archive(archive::ARCHIVE_PATH),
+        SubCommand::Compact(_compact) => archive::compact_archive(archive::ARCHIVE_PATH),
     }
 }
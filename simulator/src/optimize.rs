@@ -0,0 +1,800 @@
+use std::cell::RefCell;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::rngs::SmallRng;
+use rand::{thread_rng, RngCore, SeedableRng};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::dto::rust::{Combatant, MatchUp, Patch};
+use crate::runner::{count_wins, match_to_combatant_infos, match_to_combatants};
+use crate::sim::{Arena, Pathfinder, Simulation, Team};
+
+/// For 95% confidence intervals.
+const Z: f64 = 1.96;
+
+/// How many times coordinate descent goes back over every slot before giving up.
+const MAX_PASSES: usize = 3;
+
+const DEFAULT_BEAM_WIDTH: usize = 4;
+
+/// Brave & faith to try when none are given.
+const BRAVE_FAITH_STEPS: &[i8] = &[10, 20, 30, 40, 50, 60, 70, 80, 90];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Slot {
+    MainHand,
+    OffHand,
+    Head,
+    Armor,
+    Accessory,
+    Reaction,
+    Support,
+    Move,
+    Brave,
+    Faith,
+}
+
+pub const ALL_SLOTS: &[Slot] = &[
+    Slot::MainHand,
+    Slot::OffHand,
+    Slot::Head,
+    Slot::Armor,
+    Slot::Accessory,
+    Slot::Reaction,
+    Slot::Support,
+    Slot::Move,
+    Slot::Brave,
+    Slot::Faith,
+];
+
+const EQUIPMENT_SLOTS: &[Slot] = &[
+    Slot::MainHand,
+    Slot::OffHand,
+    Slot::Head,
+    Slot::Armor,
+    Slot::Accessory,
+];
+
+impl Slot {
+    pub fn name(self) -> &'static str {
+        match self {
+            Slot::MainHand => "main_hand",
+            Slot::OffHand => "off_hand",
+            Slot::Head => "head",
+            Slot::Armor => "armor",
+            Slot::Accessory => "accessory",
+            Slot::Reaction => "reaction",
+            Slot::Support => "support",
+            Slot::Move => "move",
+            Slot::Brave => "brave",
+            Slot::Faith => "faith",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Slot> {
+        ALL_SLOTS.iter().copied().find(|slot| slot.name() == name)
+    }
+
+    pub fn is_equipment(self) -> bool {
+        EQUIPMENT_SLOTS.contains(&self)
+    }
+
+    pub fn get(self, unit: &Combatant) -> String {
+        match self {
+            Slot::MainHand => unit.main_hand.clone(),
+            Slot::OffHand => unit.off_hand.clone(),
+            Slot::Head => unit.head.clone(),
+            Slot::Armor => unit.armor.clone(),
+            Slot::Accessory => unit.accessory.clone(),
+            Slot::Reaction => unit.reaction_skill.clone(),
+            Slot::Support => unit.support_skill.clone(),
+            Slot::Move => unit.move_skill.clone(),
+            Slot::Brave => unit.brave.to_string(),
+            Slot::Faith => unit.faith.to_string(),
+        }
+    }
+
+    pub fn set(self, unit: &mut Combatant, value: &str) -> Result<(), String> {
+        let value = value.to_owned();
+        match self {
+            Slot::MainHand => unit.main_hand = value,
+            Slot::OffHand => unit.off_hand = value,
+            Slot::Head => unit.head = value,
+            Slot::Armor => unit.armor = value,
+            Slot::Accessory => unit.accessory = value,
+            Slot::Reaction => unit.reaction_skill = value,
+            Slot::Support => unit.support_skill = value,
+            Slot::Move => unit.move_skill = value,
+            Slot::Brave => unit.brave = parse_brave_faith(&value)?,
+            Slot::Faith => unit.faith = parse_brave_faith(&value)?,
+        }
+        Ok(())
+    }
+}
+
+fn parse_brave_faith(value: &str) -> Result<i8, String> {
+    match value.parse() {
+        Ok(n) if (0..=100).contains(&n) => Ok(n),
+        _ => Err(format!("expected 0 to 100, not '{}'", value)),
+    }
+}
+
+/// A slot the search may change, written `unit:slot` or `unit:slot=a,b,c`, e.g.
+/// `2:accessory=Angel Ring,Magic Ring`. `unit` is 0 to 3 within the team being optimized.
+/// Only main_hand, off_hand, brave & faith can leave the candidates out, see `Optimizer::new`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotChoice {
+    pub unit: usize,
+    pub slot: Slot,
+    pub candidates: Vec<String>,
+}
+
+impl FromStr for SlotChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("expected a slot like 0:accessory=Angel Ring, not '{}'", s);
+        let (spec, candidates) = match s.find('=') {
+            Some(idx) => (
+                &s[..idx],
+                s[idx + 1..]
+                    .split(',')
+                    .map(|c| c.trim().to_owned())
+                    .collect(),
+            ),
+            None => (s, vec![]),
+        };
+        let idx = spec.find(':').ok_or_else(bad)?;
+        let unit = spec[..idx].trim().parse().map_err(|_| bad())?;
+        let slot = spec[idx + 1..].trim();
+        let slot = Slot::parse(slot).ok_or_else(|| {
+            let names: Vec<_> = ALL_SLOTS.iter().map(|slot| slot.name()).collect();
+            format!(
+                "unknown slot '{}', expected one of {}",
+                slot,
+                names.join(", ")
+            )
+        })?;
+        Ok(SlotChoice {
+            unit,
+            slot,
+            candidates,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Search {
+    /// Make the single best change, then the best change to another slot, until none help.
+    Greedy,
+    /// Fill in the slots one at a time, keeping the best N partial loadouts.
+    Beam(usize),
+    /// Pick the best value for each slot in turn with the others held, going round until
+    /// nothing changes.
+    CoordinateDescent,
+}
+
+impl FromStr for Search {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("expected greedy, beam, beam:N or coordinate, not '{}'", s);
+        match s {
+            "greedy" => Ok(Search::Greedy),
+            "beam" => Ok(Search::Beam(DEFAULT_BEAM_WIDTH)),
+            "coordinate" => Ok(Search::CoordinateDescent),
+            _ if s.starts_with("beam:") => match s[5..].parse() {
+                Ok(width) if width > 0 => Ok(Search::Beam(width)),
+                _ => Err(bad()),
+            },
+            _ => Err(bad()),
+        }
+    }
+}
+
+/// `wins` out of `runs` simulated matches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WinRate {
+    pub wins: u32,
+    pub runs: u32,
+}
+
+/// An estimate with its 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Interval {
+    pub estimate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl WinRate {
+    pub fn rate(self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        f64::from(self.wins) / f64::from(self.runs)
+    }
+
+    fn variance(self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.rate() * (1.0 - self.rate()) / f64::from(self.runs)
+    }
+
+    /// The Wilson score interval, which stays within 0 to 1 and doesn't collapse to nothing
+    /// when every run was won or lost.
+    pub fn interval(self) -> Interval {
+        let n = f64::from(self.runs);
+        let p = self.rate();
+        let denominator = 1.0 + Z * Z / n;
+        let centre = (p + Z * Z / (2.0 * n)) / denominator;
+        let spread = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denominator;
+        Interval {
+            estimate: p,
+            low: (centre - spread).max(0.0),
+            high: (centre + spread).min(1.0),
+        }
+    }
+
+    /// How much better this did than `other`, treating the two as independent samples.
+    pub fn minus(self, other: WinRate) -> Interval {
+        let estimate = self.rate() - other.rate();
+        let spread = Z * (self.variance() + other.variance()).sqrt();
+        Interval {
+            estimate,
+            low: estimate - spread,
+            high: estimate + spread,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Change {
+    pub unit: usize,
+    pub name: String,
+    pub slot: Slot,
+    pub from: String,
+    pub to: String,
+    /// The best loadout's win rate minus its win rate without this change.
+    pub marginal: Interval,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub baseline: Interval,
+    pub best: Interval,
+    pub changes: Vec<Change>,
+    pub simulations: u64,
+}
+
+/// Searches for the loadout of some slots on one team that wins the most, using the simulator
+/// to score each loadout it tries.
+pub struct Optimizer<'a> {
+    patch: &'a Patch,
+    match_up: &'a MatchUp,
+    arena: &'a Arena,
+    team: Team,
+    /// Each with the unit's current value as the first candidate.
+    choices: Vec<SlotChoice>,
+    num_runs: u32,
+    /// Seeds the runs of every loadout the search scores, so they're compared on the same luck
+    /// rather than each getting its own.
+    search_seed: u64,
+    simulations: AtomicU64,
+}
+
+/// Indices into each choice's candidates, 0 being what the unit has now.
+type Loadout = Vec<usize>;
+
+impl<'a> Optimizer<'a> {
+    /// Slots without candidates get every weapon in the patch of the same type as the one
+    /// held, or brave & faith in steps of 10. The patch doesn't record which slot armor or
+    /// accessories go in, or which skills are reactions, supports or movement, so every
+    /// other slot needs its candidates listed.
+    pub fn new(
+        patch: &'a Patch,
+        match_up: &'a MatchUp,
+        arena: &'a Arena,
+        team: Team,
+        choices: &[SlotChoice],
+        num_runs: u32,
+    ) -> Result<Optimizer<'a>, String> {
+        if num_runs == 0 {
+            return Err("need at least one run per loadout".to_owned());
+        }
+        let units = match team {
+            Team::Left => &match_up.left.combatants,
+            Team::Right => &match_up.right.combatants,
+        };
+        let mut filled: Vec<SlotChoice> = vec![];
+        for choice in choices {
+            let unit = units
+                .get(choice.unit)
+                .ok_or_else(|| format!("there's no unit {} on the team", choice.unit))?;
+            let listed =
+                |other: &SlotChoice| other.unit == choice.unit && other.slot == choice.slot;
+            if filled.iter().any(listed) {
+                return Err(format!(
+                    "unit {}'s {} is listed more than once",
+                    choice.unit,
+                    choice.slot.name()
+                ));
+            }
+            let current = choice.slot.get(unit);
+            let mut candidates = if choice.candidates.is_empty() {
+                patch_candidates(patch, unit, choice.slot)?
+            } else {
+                choice.candidates.clone()
+            };
+            for (idx, candidate) in candidates.iter().enumerate() {
+                check_candidate(patch, choice.slot, candidate)?;
+                if candidates[..idx].contains(candidate) {
+                    return Err(format!(
+                        "'{}' is listed more than once for unit {}'s {}",
+                        candidate,
+                        choice.unit,
+                        choice.slot.name()
+                    ));
+                }
+            }
+            candidates.retain(|candidate| *candidate != current);
+            candidates.insert(0, current);
+            filled.push(SlotChoice {
+                unit: choice.unit,
+                slot: choice.slot,
+                candidates,
+            });
+        }
+        Ok(Optimizer {
+            patch,
+            match_up,
+            arena,
+            team,
+            choices: filled,
+            num_runs,
+            search_seed: thread_rng().next_u64(),
+            simulations: AtomicU64::new(0),
+        })
+    }
+
+    pub fn choices(&self) -> &[SlotChoice] {
+        &self.choices
+    }
+
+    pub fn optimize(&self, search: Search) -> Report {
+        let sizes: Vec<_> = self
+            .choices
+            .iter()
+            .map(|choice| choice.candidates.len())
+            .collect();
+        let evaluate = |loadouts: &[Loadout]| self.evaluate_all(loadouts, self.search_seed);
+        let (best, _) = match search {
+            Search::Greedy => greedy(&sizes, &evaluate),
+            Search::Beam(width) => beam(&sizes, width, &evaluate),
+            Search::CoordinateDescent => coordinate_descent(&sizes, &evaluate),
+        };
+        self.report(&best)
+    }
+
+    /// Scores `best` & the baseline again on fresh seeds, as the scores the search went by
+    /// are biased upwards by it having picked the luckiest, and works out what each change
+    /// is worth by undoing it.
+    fn report(&self, best: &[usize]) -> Report {
+        let changed: Vec<_> = (0..best.len()).filter(|&idx| best[idx] != 0).collect();
+        let mut loadouts = vec![vec![0; best.len()], best.to_vec()];
+        for &idx in &changed {
+            let mut without = best.to_vec();
+            without[idx] = 0;
+            loadouts.push(without);
+        }
+        let scores = self.evaluate_all(&loadouts, thread_rng().next_u64());
+        let best_score = scores[1];
+        let changes = changed
+            .iter()
+            .zip(&scores[2..])
+            .map(|(&idx, &without)| {
+                let choice = &self.choices[idx];
+                Change {
+                    unit: choice.unit,
+                    name: self.units()[choice.unit].name.clone(),
+                    slot: choice.slot,
+                    from: choice.candidates[0].clone(),
+                    to: choice.candidates[best[idx]].clone(),
+                    marginal: best_score.minus(without),
+                }
+            })
+            .collect();
+        Report {
+            baseline: scores[0].interval(),
+            best: best_score.interval(),
+            changes,
+            simulations: self.simulations.load(Ordering::Relaxed),
+        }
+    }
+
+    fn units(&self) -> &[Combatant] {
+        match self.team {
+            Team::Left => &self.match_up.left.combatants,
+            Team::Right => &self.match_up.right.combatants,
+        }
+    }
+
+    fn build(&self, loadout: &[usize]) -> MatchUp {
+        let mut match_up = self.match_up.clone();
+        for (choice, &idx) in self.choices.iter().zip(loadout) {
+            let team = match self.team {
+                Team::Left => &mut match_up.left,
+                Team::Right => &mut match_up.right,
+            };
+            // Checked when the candidates were filled in.
+            choice
+                .slot
+                .set(&mut team.combatants[choice.unit], &choice.candidates[idx])
+                .unwrap();
+        }
+        match_up
+    }
+
+    /// Scores every loadout on the same `seed`.
+    fn evaluate_all(&self, loadouts: &[Loadout], seed: u64) -> Vec<WinRate> {
+        loadouts
+            .par_iter()
+            .map(|loadout| self.evaluate(loadout, seed))
+            .collect()
+    }
+
+    fn evaluate(&self, loadout: &[usize], seed: u64) -> WinRate {
+        let match_up = self.build(loadout);
+        let combatant_infos = match_to_combatant_infos(self.patch, &match_up);
+        let combatants = match_to_combatants(&combatant_infos);
+        let pathfinder = RefCell::new(Pathfinder::new(self.arena));
+        let rng = SmallRng::seed_from_u64(seed);
+        let start = Simulation::new(combatants, self.arena, &pathfinder, rng, false);
+        let num_runs = self.num_runs as i32;
+        let (left_wins, _) = count_wins(num_runs, &start, &mut SmallRng::seed_from_u64(seed));
+        self.simulations
+            .fetch_add(u64::from(self.num_runs), Ordering::Relaxed);
+        let wins = match self.team {
+            Team::Left => left_wins,
+            Team::Right => num_runs - left_wins,
+        };
+        WinRate {
+            wins: wins as u32,
+            runs: self.num_runs,
+        }
+    }
+}
+
+fn patch_candidates(patch: &Patch, unit: &Combatant, slot: Slot) -> Result<Vec<String>, String> {
+    let held = match slot {
+        Slot::MainHand => Some(&unit.main_hand),
+        Slot::OffHand => Some(&unit.off_hand),
+        Slot::Brave | Slot::Faith => {
+            return Ok(BRAVE_FAITH_STEPS.iter().map(|n| n.to_string()).collect())
+        }
+        _ => None,
+    };
+    let weapon_type = held
+        .and_then(|name| patch.equipment.by_name.get(name))
+        .and_then(|equip| equip.weapon_type);
+    let weapon_type = match weapon_type {
+        Some(weapon_type) => weapon_type,
+        None => {
+            return Err(format!(
+                "the patch doesn't say what {} could have in {}, list the candidates",
+                unit.name,
+                slot.name()
+            ))
+        }
+    };
+    let mut candidates: Vec<_> = patch
+        .equipment
+        .by_name
+        .values()
+        .filter(|equip| equip.weapon_type == Some(weapon_type))
+        .map(|equip| equip.name.clone())
+        .collect();
+    candidates.sort();
+    Ok(candidates)
+}
+
+/// Equipment has to be in the patch, or empty for nothing. There's no list of skills to check
+/// against, so those are taken as given.
+fn check_candidate(patch: &Patch, slot: Slot, candidate: &str) -> Result<(), String> {
+    if slot == Slot::Brave || slot == Slot::Faith {
+        parse_brave_faith(candidate)?;
+    } else if slot.is_equipment()
+        && !candidate.is_empty()
+        && !patch.equipment.by_name.contains_key(candidate)
+    {
+        return Err(format!("there's no equipment '{}' in the patch", candidate));
+    }
+    Ok(())
+}
+
+fn best_of(loadouts: Vec<Loadout>, scores: Vec<WinRate>) -> Option<(Loadout, WinRate)> {
+    let mut best: Option<(Loadout, WinRate)> = None;
+    for (loadout, score) in loadouts.into_iter().zip(scores) {
+        let better = match &best {
+            Some((_, best)) => score.rate() > best.rate(),
+            None => true,
+        };
+        if better {
+            best = Some((loadout, score));
+        }
+    }
+    best
+}
+
+/// Whether `next` wins more than `current` by more than the noise between them, so the
+/// searches don't wander off after a lucky few runs.
+fn improves(next: WinRate, current: WinRate) -> bool {
+    next.minus(current).low > 0.0
+}
+
+/// Every loadout that differs from `loadout` in slot `idx` alone.
+fn neighbours(sizes: &[usize], loadout: &[usize], idx: usize) -> Vec<Loadout> {
+    (0..sizes[idx])
+        .filter(|&candidate| candidate != loadout[idx])
+        .map(|candidate| {
+            let mut next = loadout.to_vec();
+            next[idx] = candidate;
+            next
+        })
+        .collect()
+}
+
+fn greedy(sizes: &[usize], evaluate: &dyn Fn(&[Loadout]) -> Vec<WinRate>) -> (Loadout, WinRate) {
+    let mut current = vec![0; sizes.len()];
+    let mut score = evaluate(std::slice::from_ref(&current))[0];
+    let mut changed = vec![false; sizes.len()];
+    loop {
+        let mut candidates = vec![];
+        for idx in (0..sizes.len()).filter(|&idx| !changed[idx]) {
+            candidates.extend(neighbours(sizes, &current, idx));
+        }
+        let scores = evaluate(&candidates);
+        match best_of(candidates, scores) {
+            Some((next, next_score)) if improves(next_score, score) => {
+                for idx in 0..sizes.len() {
+                    changed[idx] |= next[idx] != current[idx];
+                }
+                current = next;
+                score = next_score;
+            }
+            _ => return (current, score),
+        }
+    }
+}
+
+fn beam(
+    sizes: &[usize],
+    width: usize,
+    evaluate: &dyn Fn(&[Loadout]) -> Vec<WinRate>,
+) -> (Loadout, WinRate) {
+    let start = vec![0; sizes.len()];
+    let score = evaluate(std::slice::from_ref(&start))[0];
+    let mut beam = vec![(start, score)];
+    for idx in 0..sizes.len() {
+        let mut expanded = vec![];
+        for (loadout, _) in &beam {
+            expanded.extend(neighbours(sizes, loadout, idx));
+        }
+        let scores = evaluate(&expanded);
+        beam.extend(expanded.into_iter().zip(scores));
+        // Stable, so ties go to the loadouts with fewer changes.
+        beam.sort_by(|a, b| b.1.rate().partial_cmp(&a.1.rate()).unwrap());
+        beam.truncate(width);
+    }
+    beam.swap_remove(0)
+}
+
+fn coordinate_descent(
+    sizes: &[usize],
+    evaluate: &dyn Fn(&[Loadout]) -> Vec<WinRate>,
+) -> (Loadout, WinRate) {
+    let mut current = vec![0; sizes.len()];
+    let mut score = evaluate(std::slice::from_ref(&current))[0];
+    for _ in 0..MAX_PASSES {
+        let mut changed = false;
+        for idx in 0..sizes.len() {
+            let candidates = neighbours(sizes, &current, idx);
+            let scores = evaluate(&candidates);
+            if let Some((next, next_score)) = best_of(candidates, scores) {
+                if improves(next_score, score) {
+                    current = next;
+                    score = next_score;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    (current, score)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::test_support::{facing_off_arena, sample_patch, sample_teams};
+
+    /// Each loadout scores the sum of its candidate indices weighted by `weights`, in percent,
+    /// out of enough runs that every step clears the noise.
+    fn scored_by(weights: &[i32]) -> impl Fn(&[Loadout]) -> Vec<WinRate> + '_ {
+        move |loadouts| {
+            loadouts
+                .iter()
+                .map(|loadout| {
+                    let total: i32 = loadout
+                        .iter()
+                        .zip(weights)
+                        .map(|(&idx, weight)| idx as i32 * weight)
+                        .sum();
+                    WinRate {
+                        wins: (5000 + total * 100) as u32,
+                        runs: 10000,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    pub fn slot_choices_parse() {
+        assert_eq!(
+            "2:accessory=Angel Ring, Magic Ring".parse(),
+            Ok(SlotChoice {
+                unit: 2,
+                slot: Slot::Accessory,
+                candidates: vec!["Angel Ring".to_owned(), "Magic Ring".to_owned()],
+            })
+        );
+        assert_eq!(
+            "0:brave".parse(),
+            Ok(SlotChoice {
+                unit: 0,
+                slot: Slot::Brave,
+                candidates: vec![],
+            })
+        );
+        assert!("accessory".parse::<SlotChoice>().is_err());
+        assert!("0:hat".parse::<SlotChoice>().is_err());
+        assert_eq!("beam:3".parse(), Ok(Search::Beam(3)));
+        assert!("beam:0".parse::<Search>().is_err());
+    }
+
+    #[test]
+    pub fn intervals() {
+        let interval = WinRate {
+            wins: 50,
+            runs: 100,
+        }
+        .interval();
+        assert_eq!(interval.estimate, 0.5);
+        assert!((interval.low - 0.4038).abs() < 1e-4);
+        assert!((interval.high - 0.5962).abs() < 1e-4);
+
+        let all_won = WinRate { wins: 10, runs: 10 }.interval();
+        assert!(all_won.high > 0.999);
+        assert!(all_won.low > 0.6 && all_won.low < 0.75);
+
+        let delta = WinRate {
+            wins: 60,
+            runs: 100,
+        }
+        .minus(WinRate {
+            wins: 40,
+            runs: 100,
+        });
+        assert!((delta.estimate - 0.2).abs() < 1e-9);
+        assert!((delta.high - delta.estimate - 0.1358).abs() < 1e-4);
+    }
+
+    #[test]
+    pub fn searches_find_the_best_loadout() {
+        let sizes = [3, 2, 4];
+        let evaluate = scored_by(&[5, -10, 3]);
+        assert_eq!(greedy(&sizes, &evaluate).0, vec![2, 0, 3]);
+        assert_eq!(beam(&sizes, 2, &evaluate).0, vec![2, 0, 3]);
+        assert_eq!(coordinate_descent(&sizes, &evaluate).0, vec![2, 0, 3]);
+
+        let (loadout, score) = greedy(&sizes, &scored_by(&[-1, -1, -1]));
+        assert_eq!(loadout, vec![0, 0, 0]);
+        assert_eq!(score.wins, 5000);
+    }
+
+    #[test]
+    pub fn searches_stay_put_when_a_gain_is_within_the_noise() {
+        let sizes = [3, 2];
+        let barely = |loadouts: &[Loadout]| -> Vec<WinRate> {
+            loadouts
+                .iter()
+                .map(|loadout| WinRate {
+                    wins: 50 + loadout.iter().sum::<usize>() as u32,
+                    runs: 100,
+                })
+                .collect()
+        };
+        assert_eq!(greedy(&sizes, &barely).0, vec![0, 0]);
+        assert_eq!(coordinate_descent(&sizes, &barely).0, vec![0, 0]);
+    }
+
+    #[test]
+    pub fn reports_each_change() {
+        let patch = sample_patch();
+        let match_up = sample_teams();
+        let arena = facing_off_arena();
+
+        let choices = [
+            "0:faith=1".parse().unwrap(),
+            "1:brave".parse().unwrap(),
+            "1:support=Short Charge,Magic Attack UP".parse().unwrap(),
+        ];
+        let optimizer =
+            Optimizer::new(&patch, &match_up, &arena, Team::Right, &choices, 2).unwrap();
+        let filled = optimizer.choices();
+        assert_eq!(filled[0].candidates, vec!["65", "1"]);
+        assert_eq!(filled[1].candidates.len(), 9);
+        assert_eq!(filled[1].candidates[0], "70");
+        assert_eq!(
+            filled[2].candidates,
+            vec!["Short Charge", "Magic Attack UP"]
+        );
+
+        let report = optimizer.optimize(Search::Greedy);
+        assert!(report.simulations >= 2 * 12);
+        for change in &report.changes {
+            assert_ne!(change.from, change.to);
+            assert!(change.marginal.low <= change.marginal.estimate);
+            assert!(change.marginal.estimate <= change.marginal.high);
+        }
+        assert!(report.baseline.low <= report.baseline.high);
+    }
+
+    #[test]
+    pub fn rejects_choices_that_do_not_fit() {
+        let patch = sample_patch();
+        let match_up = sample_teams();
+        let arena = facing_off_arena();
+        let new = |choice: &str| {
+            let choices = [choice.parse().unwrap()];
+            Optimizer::new(&patch, &match_up, &arena, Team::Left, &choices, 1).err()
+        };
+        assert!(new("4:brave").is_some());
+        assert!(new("0:faith=101").is_some());
+        assert!(new("0:head=Flash Hat").unwrap().contains("Flash Hat"));
+        for slot in &["head", "armor", "accessory", "reaction", "support", "move"] {
+            let err = new(&format!("0:{}", slot)).unwrap();
+            assert!(err.contains("list the candidates"), "{}", err);
+        }
+        assert!(new("0:main_hand").is_none());
+        assert!(new("0:support=Short Charge,Short Charge")
+            .unwrap()
+            .contains("Short Charge"));
+
+        let choices = ["0:brave".parse().unwrap(), "0:brave=50".parse().unwrap()];
+        let err = Optimizer::new(&patch, &match_up, &arena, Team::Left, &choices, 1).err();
+        assert!(err.unwrap().contains("brave"));
+    }
+
+    #[test]
+    pub fn loadouts_are_scored_on_the_same_luck() {
+        let patch = sample_patch();
+        let match_up = sample_teams();
+        let arena = facing_off_arena();
+        let choices = ["0:brave".parse().unwrap()];
+        let optimizer =
+            Optimizer::new(&patch, &match_up, &arena, Team::Left, &choices, 20).unwrap();
+        let loadout = vec![0];
+        assert_eq!(
+            optimizer.evaluate(&loadout, 7),
+            optimizer.evaluate(&loadout, 7)
+        );
+    }
+}
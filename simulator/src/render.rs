@@ -0,0 +1,552 @@
+use std::fs;
+use std::io;
+
+use colored::Colorize;
+
+use crate::dto::rust::Tile;
+use crate::sim::{describe_location, Arena, Combatant, Facing, Location, Panel, Team};
+
+pub const SVG_TILE: f32 = 32.0;
+pub const SVG_MARGIN: f32 = 20.0;
+const SVG_LEGEND_LINE: f32 = 16.0;
+
+fn facing_arrow(facing: Facing) -> char {
+    match facing {
+        Facing::North => '^',
+        Facing::East => '>',
+        Facing::South => 'v',
+        Facing::West => '<',
+    }
+}
+
+pub fn facing_name(facing: Facing) -> &'static str {
+    match facing {
+        Facing::North => "north",
+        Facing::East => "east",
+        Facing::South => "south",
+        Facing::West => "west",
+    }
+}
+
+/// The panel you'd see looking down on `location`, the upper layer if there is one.
+fn top_panel(arena: &Arena, location: Location) -> Panel {
+    Panel::new(location, arena.has_upper(location))
+}
+
+fn max_height(arena: &Arena) -> f32 {
+    let mut highest = 1.0f32;
+    for y in 0..arena.height {
+        for x in 0..arena.width {
+            let location = Location::new(x as i16, y as i16);
+            highest = highest.max(arena.tile_height(top_panel(arena, location)));
+        }
+    }
+    highest
+}
+
+/// 0.0 for the lowest ground through 1.0 for the highest on the map.
+fn shade(arena: &Arena, panel: Panel, highest: f32) -> f32 {
+    arena.tile_height(panel) / highest
+}
+
+fn grey(fraction: f32, darkest: f32, lightest: f32) -> u8 {
+    (darkest + fraction * (lightest - darkest)) as u8
+}
+
+/// The unit standing on `location`, preferring anyone still up over the fallen.
+fn unit_at<'a, 'b>(
+    combatants: &'b [Combatant<'a>],
+    location: Location,
+) -> Option<&'b Combatant<'a>> {
+    combatants
+        .iter()
+        .filter(|combatant| combatant.panel.location() == location)
+        .max_by_key(|combatant| combatant.healthy())
+}
+
+fn starting_location_at(arena: &Arena, location: Location) -> Option<(bool, u8, Facing)> {
+    arena
+        .starting_locations
+        .iter()
+        .find(|start| start.x as i16 == location.x && start.y as i16 == location.y)
+        .map(|start| (start.left_team, start.unit, start.facing))
+}
+
+fn ascii_tile(tile: &Tile, height: f32) -> String {
+    if tile.no_walk {
+        format!("{:>4}", "##")
+    } else if tile.depth > 0 {
+        format!("{:>4}", format!("~{}", tile.depth))
+    } else {
+        format!("{:>4}", height)
+    }
+}
+
+/// A terminal only has a handful of background colors to shade heights with, so the map is
+/// split into low, middle and high ground.
+fn ascii_band(fraction: f32) -> usize {
+    ((fraction * 3.0) as usize).min(2)
+}
+
+/// A top down view with north at the top, shaded lighter the higher the ground is. Empty
+/// panels show their height, `~N` for water N deep, or `##` if they can't be walked on.
+/// Without `combatants` the starting locations are marked `L0` through `R3`, with them each
+/// unit shows its ID & facing (or `x` once it's down) and is listed with its HP underneath.
+pub fn ascii_arena(arena: &Arena, combatants: Option<&[Combatant]>) -> String {
+    let highest = max_height(arena);
+    let mut out = String::new();
+    out.push_str("    ");
+    for x in 0..arena.width {
+        out.push_str(&format!("{:>4}", x));
+    }
+    out.push('\n');
+    for y in 0..arena.height {
+        out.push_str(&format!("{:>4}", y));
+        for x in 0..arena.width {
+            let location = Location::new(x as i16, y as i16);
+            let panel = top_panel(arena, location);
+            let tile = arena.tile(panel);
+            let unit = combatants.and_then(|combatants| unit_at(combatants, location));
+            let start = if combatants.is_none() {
+                starting_location_at(arena, location)
+            } else {
+                None
+            };
+
+            let cell = match (unit, start) {
+                (Some(combatant), _) => {
+                    let marker = if combatant.healthy() {
+                        facing_arrow(combatant.facing)
+                    } else {
+                        'x'
+                    };
+                    let layer = if combatant.panel.layer() { "+" } else { " " };
+                    let text = format!(" {}{}{}", layer, combatant.id().index(), marker);
+                    match combatant.team() {
+                        Team::Left => text.red().bold(),
+                        Team::Right => text.blue().bold(),
+                    }
+                }
+                (None, Some((left_team, unit, facing))) => {
+                    let team = if left_team { 'L' } else { 'R' };
+                    let text = format!(" {}{}{}", team, unit, facing_arrow(facing));
+                    if left_team {
+                        text.red()
+                    } else {
+                        text.blue()
+                    }
+                }
+                (None, None) => {
+                    let text = ascii_tile(&tile, arena.tile_height(panel));
+                    if tile.no_walk || tile.depth > 0 {
+                        text.bright_white()
+                    } else if ascii_band(shade(arena, panel, highest)) == 0 {
+                        text.white()
+                    } else {
+                        text.black()
+                    }
+                }
+            };
+            let cell = if tile.no_walk {
+                cell.on_black()
+            } else if tile.depth > 0 {
+                cell.on_blue()
+            } else {
+                match ascii_band(shade(arena, panel, highest)) {
+                    0 => cell.on_bright_black(),
+                    1 => cell.on_white(),
+                    _ => cell.on_bright_white(),
+                }
+            };
+            out.push_str(&cell.to_string());
+        }
+        out.push('\n');
+    }
+
+    if let Some(combatants) = combatants {
+        for combatant in combatants {
+            let status = if combatant.healthy() { "" } else { ", down" };
+            let line = format!(
+                "{:>4} {:<16} {} facing {}, {}/{} HP{}",
+                combatant.id().index(),
+                combatant.name(),
+                describe_location(combatant.panel, arena),
+                facing_name(combatant.facing),
+                combatant.hp(),
+                combatant.max_hp(),
+                status
+            );
+            let line = match combatant.team() {
+                Team::Left => line.red(),
+                Team::Right => line.blue(),
+            };
+            out.push_str(&format!("{}\n", line));
+        }
+    }
+    out
+}
+
+/// Drops the terminal color codes `colored` adds, for text that's going somewhere else.
+pub fn strip_colors(text: &str) -> String {
+    let mut out = String::new();
+    let mut in_escape = false;
+    for c in text.chars() {
+        if c == '\u{1b}' {
+            in_escape = true;
+        } else if in_escape {
+            in_escape = c != 'm';
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn team_color(left_team: bool) -> &'static str {
+    if left_team {
+        "#c0392b"
+    } else {
+        "#2e6fbf"
+    }
+}
+
+fn tile_origin(x: u8, y: u8) -> (f32, f32) {
+    (
+        SVG_MARGIN + x as f32 * SVG_TILE,
+        SVG_MARGIN + y as f32 * SVG_TILE,
+    )
+}
+
+fn svg_tile(out: &mut String, arena: &Arena, panel: Panel, highest: f32) {
+    let (left, top) = tile_origin(panel.x(), panel.y());
+    let tile = arena.tile(panel);
+    let height = arena.tile_height(panel);
+    let level = grey(shade(arena, panel, highest), 70.0, 235.0);
+    let fill = if tile.no_walk {
+        "#282828".to_owned()
+    } else if tile.depth > 0 {
+        format!("rgb({},{},{})", level / 3, level / 2, level)
+    } else {
+        format!("rgb({},{},{})", level, level, level)
+    };
+    out.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#555\" stroke-width=\"0.5\"><title>({},{}) height {}{}{}</title></rect>\n",
+        left,
+        top,
+        SVG_TILE,
+        SVG_TILE,
+        fill,
+        panel.x(),
+        panel.y(),
+        height,
+        if tile.depth > 0 {
+            format!(", water {} deep", tile.depth)
+        } else {
+            "".to_owned()
+        },
+        if tile.no_walk { ", can't walk" } else { "" }
+    ));
+    if tile.no_walk {
+        out.push_str(&format!(
+            "<path d=\"M{} {} L{} {} M{} {} L{} {}\" stroke=\"#777\" stroke-width=\"1\"/>\n",
+            left + 4.0,
+            top + 4.0,
+            left + SVG_TILE - 4.0,
+            top + SVG_TILE - 4.0,
+            left + SVG_TILE - 4.0,
+            top + 4.0,
+            left + 4.0,
+            top + SVG_TILE - 4.0
+        ));
+    }
+    if panel.layer() {
+        // A bridge or similar, there's walkable ground underneath as well.
+        out.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#8b5a2b\" stroke-width=\"1.5\" stroke-dasharray=\"3 2\"/>\n",
+            left + 2.0,
+            top + 2.0,
+            SVG_TILE - 4.0,
+            SVG_TILE - 4.0
+        ));
+    }
+    let label = if tile.depth > 0 && !tile.no_walk {
+        format!("~{}", tile.depth)
+    } else {
+        format!("{}", height)
+    };
+    out.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"8\" fill=\"#333\">{}</text>\n",
+        left + 2.0,
+        top + 9.0,
+        label
+    ));
+}
+
+fn facing_pointer(facing: Facing, cx: f32, cy: f32, length: f32) -> (f32, f32) {
+    let offset = facing.offset();
+    (cx + offset.x as f32 * length, cy + offset.y as f32 * length)
+}
+
+fn svg_starting_location(
+    out: &mut String,
+    left_team: bool,
+    unit: u8,
+    x: u8,
+    y: u8,
+    facing: Facing,
+) {
+    let (left, top) = tile_origin(x, y);
+    let (cx, cy) = (left + SVG_TILE / 2.0, top + SVG_TILE / 2.0);
+    let (px, py) = facing_pointer(facing, cx, cy, 12.0);
+    let color = team_color(left_team);
+    out.push_str(&format!(
+        "<circle cx=\"{}\" cy=\"{}\" r=\"9\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+        cx, cy, color
+    ));
+    out.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+        cx, cy, px, py, color
+    ));
+    out.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"9\" text-anchor=\"middle\" fill=\"{}\">{}{}</text>\n",
+        cx,
+        cy + 3.0,
+        color,
+        if left_team { 'L' } else { 'R' },
+        unit
+    ));
+}
+
+fn svg_combatant(out: &mut String, combatant: &Combatant) {
+    let panel = combatant.panel;
+    let (left, top) = tile_origin(panel.x(), panel.y());
+    let (cx, cy) = (left + SVG_TILE / 2.0, top + SVG_TILE / 2.0 - 2.0);
+    let color = team_color(combatant.team() == Team::Left);
+    let opacity = if combatant.healthy() { 1.0 } else { 0.35 };
+    let hp_fraction = if combatant.max_hp() > 0 {
+        combatant.hp().max(0) as f32 / combatant.max_hp() as f32
+    } else {
+        0.0
+    };
+    let (px, py) = facing_pointer(combatant.facing, cx, cy, 13.0);
+    out.push_str(&format!("<g opacity=\"{}\">\n", opacity));
+    out.push_str(&format!(
+        "<title>{}: {}/{} HP, facing {}</title>\n",
+        escape_xml(combatant.name()),
+        combatant.hp(),
+        combatant.max_hp(),
+        facing_name(combatant.facing)
+    ));
+    out.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#111\" stroke-width=\"3\"/>\n",
+        cx, cy, px, py
+    ));
+    out.push_str(&format!(
+        "<circle cx=\"{}\" cy=\"{}\" r=\"9\" fill=\"{}\" stroke=\"#111\" stroke-width=\"1\"/>\n",
+        cx, cy, color
+    ));
+    out.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\" fill=\"#fff\">{}</text>\n",
+        cx,
+        cy + 3.5,
+        combatant.id().index()
+    ));
+    let bar_width = SVG_TILE - 6.0;
+    out.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"3\" fill=\"#400\"/>\n",
+        left + 3.0,
+        top + SVG_TILE - 5.0,
+        bar_width
+    ));
+    out.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"3\" fill=\"#2ecc40\"/>\n",
+        left + 3.0,
+        top + SVG_TILE - 5.0,
+        bar_width * hp_fraction
+    ));
+    out.push_str("</g>\n");
+}
+
+/// Opens an SVG document with the coordinates along the edges and every panel drawn,
+/// leaving `extra_height` free underneath the map.
+fn svg_terrain_into(out: &mut String, arena: &Arena, extra_height: f32) {
+    let highest = max_height(arena);
+    let width = SVG_MARGIN * 2.0 + arena.width as f32 * SVG_TILE;
+    let height = SVG_MARGIN * 2.0 + arena.height as f32 * SVG_TILE + extra_height;
+    out.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"monospace\">\n",
+        width, height, width, height
+    ));
+    out.push_str(&format!(
+        "<rect width=\"{}\" height=\"{}\" fill=\"#fafafa\"/>\n",
+        width, height
+    ));
+    for x in 0..arena.width {
+        let (left, _) = tile_origin(x, 0);
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\">{}</text>\n",
+            left + SVG_TILE / 2.0,
+            SVG_MARGIN - 6.0,
+            x
+        ));
+    }
+    for y in 0..arena.height {
+        let (_, top) = tile_origin(0, y);
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"end\">{}</text>\n",
+            SVG_MARGIN - 4.0,
+            top + SVG_TILE / 2.0 + 3.0,
+            y
+        ));
+    }
+
+    for y in 0..arena.height {
+        for x in 0..arena.width {
+            let location = Location::new(x as i16, y as i16);
+            svg_tile(out, arena, top_panel(arena, location), highest);
+        }
+    }
+}
+
+/// Just the map, with nothing standing on it. Panel `(x, y)` is the `SVG_TILE` sized square
+/// with its top left corner at `SVG_MARGIN + SVG_TILE * x`, `SVG_MARGIN + SVG_TILE * y`.
+pub fn svg_terrain(arena: &Arena) -> String {
+    let mut out = String::new();
+    svg_terrain_into(&mut out, arena, 0.0);
+    out.push_str("</svg>\n");
+    out
+}
+
+/// The same view as `ascii_arena` as a standalone SVG document. Hovering over a panel or a
+/// unit shows its details.
+pub fn svg_arena(arena: &Arena, combatants: Option<&[Combatant]>) -> String {
+    let legend_lines = combatants.map_or(0, |combatants| combatants.len());
+    let mut out = String::new();
+    svg_terrain_into(&mut out, arena, legend_lines as f32 * SVG_LEGEND_LINE);
+
+    match combatants {
+        None => {
+            for start in &arena.starting_locations {
+                svg_starting_location(
+                    &mut out,
+                    start.left_team,
+                    start.unit,
+                    start.x,
+                    start.y,
+                    start.facing,
+                );
+            }
+        }
+        Some(combatants) => {
+            // Draw the fallen first so anyone standing on the same panel ends up on top.
+            let mut ordered: Vec<_> = combatants
+                .iter()
+                .filter(|combatant| arena.in_map(combatant.panel.location()))
+                .collect();
+            ordered.sort_by_key(|combatant| combatant.healthy());
+            for combatant in ordered {
+                svg_combatant(&mut out, combatant);
+            }
+
+            let legend_top = SVG_MARGIN * 1.5 + arena.height as f32 * SVG_TILE;
+            for (i, combatant) in combatants.iter().enumerate() {
+                out.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"11\" fill=\"{}\">{} {} ({},{}) {}/{} HP</text>\n",
+                    SVG_MARGIN,
+                    legend_top + i as f32 * SVG_LEGEND_LINE,
+                    team_color(combatant.team() == Team::Left),
+                    combatant.id().index(),
+                    escape_xml(combatant.name()),
+                    combatant.panel.x(),
+                    combatant.panel.y(),
+                    combatant.hp(),
+                    combatant.max_hp()
+                ));
+            }
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+pub fn write_svg(path: &str, arena: &Arena, combatants: Option<&[Combatant]>) -> io::Result<()> {
+    fs::write(path, svg_arena(arena, combatants))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::rust::StartingLocation;
+    use crate::dto::test_support::flat_tile;
+
+    fn tile(height: u8) -> Tile {
+        Tile {
+            height,
+            ..flat_tile()
+        }
+    }
+
+    fn sample_arena() -> Arena {
+        let mut lower = vec![tile(2); 6];
+        lower[1].depth = 2;
+        lower[4].no_walk = true;
+        lower[5].height = 6;
+        let starting_locations = vec![
+            StartingLocation {
+                x: 0,
+                y: 0,
+                facing: Facing::South,
+                left_team: true,
+                unit: 0,
+                layer: false,
+            },
+            StartingLocation {
+                x: 2,
+                y: 1,
+                facing: Facing::North,
+                left_team: false,
+                unit: 3,
+                layer: false,
+            },
+        ];
+        Arena::new(lower, vec![tile(0); 6], 3, 2, starting_locations)
+    }
+
+    #[test]
+    pub fn ascii_marks_water_walls_and_starting_locations() {
+        let text = strip_colors(&ascii_arena(&sample_arena(), None));
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "       0   1   2");
+        assert_eq!(lines[1], "   0 L0v  ~2   2");
+        assert_eq!(lines[2], "   1   2  ## R3^");
+    }
+
+    #[test]
+    pub fn svg_draws_every_panel_and_starting_location() {
+        let svg = svg_arena(&sample_arena(), None);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<title>(").count(), 6);
+        assert!(svg.contains("water 2 deep"));
+        assert!(svg.contains("can't walk"));
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert!(svg.contains(">L0</text>"));
+        assert!(svg.contains(">R3</text>"));
+    }
+
+    #[test]
+    pub fn escapes_names_for_svg() {
+        assert_eq!(
+            escape_xml("<Birb & \"Co\">"),
+            "&lt;Birb &amp; &quot;Co&quot;&gt;"
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use serde::Serialize;
+use serde_json;
+
+use crate::render::{escape_xml, facing_name, strip_colors, svg_terrain, SVG_MARGIN, SVG_TILE};
+use crate::sim::{
+    describe_entry, describe_phase, Arena, Combatant, Entry, Event, Pathfinder, Phase, Simulation,
+    Team, MAX_COMBATANTS,
+};
+
+const TEMPLATE: &str = include_str!("replay.html");
+
+#[derive(Serialize)]
+struct ReplayUnit {
+    x: u8,
+    y: u8,
+    layer: bool,
+    facing: &'static str,
+    hp: i16,
+    max_hp: i16,
+    mp: i16,
+    max_mp: i16,
+    ct: u8,
+    healthy: bool,
+    conditions: Vec<&'static str>,
+}
+
+/// What the page needs to animate an event, the rest is in the frame's text.
+#[derive(Serialize)]
+struct ReplayEvent {
+    kind: &'static str,
+    target: Option<usize>,
+    amount: Option<i16>,
+    label: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ReplayFrame {
+    tick: usize,
+    phase: String,
+    text: String,
+    event: ReplayEvent,
+    units: Vec<ReplayUnit>,
+    turn_order: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Replay {
+    title: String,
+    tile: f32,
+    margin: f32,
+    names: Vec<String>,
+    left_team: Vec<bool>,
+    frames: Vec<ReplayFrame>,
+}
+
+fn replay_unit(combatant: &Combatant) -> ReplayUnit {
+    ReplayUnit {
+        x: combatant.panel.x(),
+        y: combatant.panel.y(),
+        layer: combatant.panel.layer(),
+        facing: facing_name(combatant.facing),
+        hp: combatant.hp(),
+        max_hp: combatant.max_hp(),
+        mp: combatant.mp(),
+        max_mp: combatant.max_mp(),
+        ct: combatant.ct,
+        healthy: combatant.healthy(),
+        conditions: combatant
+            .all_conditions()
+            .iter()
+            .map(|condition| condition.name())
+            .collect(),
+    }
+}
+
+fn replay_event(event: &Event) -> ReplayEvent {
+    let (kind, target, amount, label) = match event {
+        Event::Moved(id, _, _) | Event::Knockback(id, _) => ("move", Some(*id), None, None),
+        Event::HpDamage(id, amount, _) => ("damage", Some(*id), Some(*amount), None),
+        Event::HpHeal(id, amount, _) => ("heal", Some(*id), Some(*amount), None),
+        Event::MpDamage(id, amount, _) => ("mp_damage", Some(*id), Some(*amount), None),
+        Event::MpHeal(id, amount, _) => ("mp_heal", Some(*id), Some(*amount), None),
+        Event::AddedCondition(id, condition, _) => {
+            ("status", Some(*id), None, Some(condition.name().to_owned()))
+        }
+        Event::LostCondition(id, condition, _) => (
+            "status_lost",
+            Some(*id),
+            None,
+            Some(condition.name().to_owned()),
+        ),
+        Event::Died(id, _) => ("died", Some(*id), None, None),
+        Event::BecameCrystal(id) => ("crystal", Some(*id), None, None),
+        Event::Evaded(id, _, _) | Event::AbilityMissed(_, id) => ("missed", Some(*id), None, None),
+        Event::UsingAbility(id, action) => (
+            "ability",
+            Some(*id),
+            None,
+            Some(action.ability.name.to_owned()),
+        ),
+        Event::StartedCharging(id, action) => (
+            "charging",
+            Some(*id),
+            None,
+            Some(action.ability.name.to_owned()),
+        ),
+        _ => ("other", None, None, None),
+    };
+    ReplayEvent {
+        kind,
+        target: target.map(|id| id.index()),
+        amount,
+        label,
+    }
+}
+
+/// Who gets the next few active turns, as `Simulation::forecast_turns` sees it from `entry`.
+fn turn_order<'a>(
+    entry: &Entry<'a>,
+    arena: &'a Arena,
+    pathfinder: &'a RefCell<Pathfinder<'a>>,
+) -> Vec<usize> {
+    let mut sim = Simulation::new(
+        entry.combatants,
+        arena,
+        pathfinder,
+        SmallRng::seed_from_u64(0),
+        false,
+    );
+    sim.combatants = entry.combatants;
+    sim.clock_tick = entry.clock_tick;
+    sim.forecast_turns(MAX_COMBATANTS)
+        .into_iter()
+        .filter_map(|turn| match turn.phase {
+            Phase::ActiveTurn(id) => Some(id.index()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn replay_frame<'a>(
+    entry: &Entry<'a>,
+    arena: &'a Arena,
+    pathfinder: &'a RefCell<Pathfinder<'a>>,
+) -> ReplayFrame {
+    ReplayFrame {
+        tick: entry.clock_tick,
+        phase: strip_colors(&describe_phase(&entry.phase, &entry.combatants)),
+        text: strip_colors(&describe_entry(entry, arena)),
+        event: replay_event(&entry.event),
+        units: entry.combatants.iter().map(replay_unit).collect(),
+        turn_order: turn_order(entry, arena, pathfinder),
+    }
+}
+
+/// A self contained HTML page that plays back a logged simulation over the map, with units
+/// moving around, damage popping up over their heads and the turn order along the top.
+pub fn replay_html(title: &str, arena: &Arena, entries: &[Entry]) -> String {
+    let pathfinder = RefCell::new(Pathfinder::new(arena));
+    let frames: Vec<_> = entries
+        .iter()
+        .filter(|entry| match entry.event {
+            Event::ChoosingAction(_, _)
+            | Event::ConsideredAction(_, _, _)
+            | Event::ChoseAction(_, _) => false,
+            _ => true,
+        })
+        .map(|entry| replay_frame(entry, arena, &pathfinder))
+        .collect();
+    let combatants = entries
+        .first()
+        .map_or(&[][..], |entry| &entry.combatants[..]);
+    let replay = Replay {
+        title: title.to_owned(),
+        tile: SVG_TILE,
+        margin: SVG_MARGIN,
+        names: combatants
+            .iter()
+            .map(|combatant| combatant.name().to_owned())
+            .collect(),
+        left_team: combatants
+            .iter()
+            .map(|combatant| combatant.team() == Team::Left)
+            .collect(),
+        frames,
+    };
+    // A name with `</script>` in it mustn't be able to end the script early.
+    let data = serde_json::to_string(&replay)
+        .unwrap()
+        .replace("</", "<\\/");
+    TEMPLATE
+        .replace("{{TITLE}}", &escape_xml(title))
+        .replace("{{MAP}}", &svg_terrain(arena))
+        .replace("{{REPLAY}}", &data)
+}
+
+pub fn write_replay(path: &str, title: &str, arena: &Arena, entries: &[Entry]) -> io::Result<()> {
+    fs::write(path, replay_html(title, arena, entries))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::test_support::{facing_off_arena, flat_arena, sim_with};
+    use crate::sim::{CombatantId, Log};
+
+    #[test]
+    pub fn empty_replays_are_still_pages() {
+        let html = replay_html("red vs <blue>", &flat_arena(), &[]);
+        assert!(html.contains("<title>red vs &lt;blue&gt;</title>"));
+        assert!(html.contains("<svg "));
+        assert!(html.contains("\"frames\":[]"));
+        assert!(!html.contains("{{"));
+    }
+
+    #[test]
+    pub fn turn_order_follows_the_forecast() {
+        let arena = facing_off_arena();
+        sim_with(
+            &arena,
+            |_| {},
+            |sim| {
+                sim.combatant_mut(CombatantId::new(6)).ct = 48;
+                let expected: Vec<_> = sim
+                    .forecast_turns(MAX_COMBATANTS)
+                    .into_iter()
+                    .filter_map(|turn| match turn.phase {
+                        Phase::ActiveTurn(id) => Some(id.index()),
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(expected[0], 6);
+
+                sim.log = Log::new();
+                sim.log_event(Event::DidNothing(CombatantId::new(0)));
+                let entries = sim.log.entries();
+                let pathfinder = RefCell::new(Pathfinder::new(&arena));
+                assert_eq!(turn_order(&entries[0], &arena, &pathfinder), expected);
+            },
+        );
+    }
+}
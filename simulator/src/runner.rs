@@ -1,33 +1,38 @@
 use std::io;
 
-use crate::data;
+use crate::archive::{Archive, ARCHIVE_PATH};
+use crate::compare;
+use crate::compare::Prediction;
+use crate::debugger::Debugger;
 use crate::dto::rust::{MatchUp, Patch};
+use crate::filter::{MatchFilter, MatchInfo};
+use crate::optimize::{Optimizer, Search, SlotChoice};
+use crate::render;
+use crate::render::ascii_arena;
+use crate::replay;
 use crate::sim::{
-    describe_entry, unit_card, Arena, Combatant, CombatantId, CombatantInfo, Gender, Pathfinder,
-    Simulation, Team,
+    describe_entry, describe_phase, estimate_state, unit_card, Arena, BattleState, Combatant,
+    CombatantId, CombatantInfo, Observation, Pathfinder, Simulation, Team,
 };
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::rngs::SmallRng;
-use rand::{thread_rng, SeedableRng};
+use rand::{thread_rng, RngCore, SeedableRng};
 use rayon::prelude::*;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::io::Write;
-use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 
-pub fn run_many_sims<'a>(
-    num_runs: i32,
-    combatants: &'a [Combatant<'a>; 8],
-    arena: &'a Arena,
-) -> (f64, u64) {
-    let mut thread_rng = thread_rng();
+/// Runs `num_runs` matches on from `start`, each seeded from `rng`, and counts how many the
+/// left team won and how many of all of them were won on time. The same `rng` seed gives the
+/// same runs, which is what lets two match ups be compared on the same luck.
+pub fn count_wins(num_runs: i32, start: &Simulation, rng: &mut impl RngCore) -> (i32, u64) {
     let mut left_wins = 0;
     let mut time_outs = 0;
     for _ in 0..num_runs {
-        let rng = SmallRng::from_rng(&mut thread_rng).unwrap();
-        let pathfinder = RefCell::new(Pathfinder::new(&arena));
-        let mut sim = Simulation::new(combatants.clone(), &arena, &pathfinder, rng, false);
+        let mut sim = start.clone();
+        *sim.rng.borrow_mut() = SmallRng::from_rng(&mut *rng).unwrap();
         sim.run();
         if sim.left_wins.unwrap() {
             left_wins += 1;
@@ -36,8 +41,59 @@ pub fn run_many_sims<'a>(
             time_outs += 1;
         }
     }
-    let left_wins_percent = left_wins as f64 / num_runs as f64;
-    (clamp(left_wins_percent, 0.05, 0.95), time_outs)
+    (left_wins, time_outs)
+}
+
+/// The left team's odds from `count_wins`, kept off the certainties either way.
+fn left_wins_percent(num_runs: i32, left_wins: i32) -> f64 {
+    clamp(left_wins as f64 / num_runs as f64, 0.05, 0.95)
+}
+
+pub fn run_many_sims<'a>(
+    num_runs: i32,
+    combatants: &'a [Combatant<'a>; 8],
+    arena: &'a Arena,
+) -> (f64, u64) {
+    // Only occupancy differs between runs, and that gets reset every time it's used.
+    let pathfinder = RefCell::new(Pathfinder::new(&arena));
+    let rng = SmallRng::from_entropy();
+    let start = Simulation::new(*combatants, arena, &pathfinder, rng, false);
+    let (left_wins, time_outs) = count_wins(num_runs, &start, &mut thread_rng());
+    (left_wins_percent(num_runs, left_wins), time_outs)
+}
+
+/// Same as `run_many_sims`, but every run picks up from `state`.
+pub fn run_many_sims_from_state<'a>(
+    num_runs: i32,
+    combatants: &'a [Combatant<'a>; 8],
+    arena: &'a Arena,
+    state: &BattleState,
+) -> Result<(f64, u64), String> {
+    let pathfinder = RefCell::new(Pathfinder::new(arena));
+    let rng = SmallRng::from_entropy();
+    let start = Simulation::from_state(*combatants, arena, &pathfinder, rng, false, state)?;
+    let (left_wins, time_outs) = count_wins(num_runs, &start, &mut thread_rng());
+    Ok((left_wins_percent(num_runs, left_wins), time_outs))
+}
+
+/// Same as `run_many_sims`, but picking up from where `observations` say the match has got to,
+/// see `estimate_state`.
+pub fn run_many_sims_observed<'a>(
+    num_runs: i32,
+    combatants: &'a [Combatant<'a>; 8],
+    arena: &'a Arena,
+    observations: &[Observation],
+) -> Result<(f64, u64), String> {
+    let pathfinder = RefCell::new(Pathfinder::new(arena));
+    let start = Simulation::new(
+        *combatants,
+        arena,
+        &pathfinder,
+        SmallRng::from_entropy(),
+        false,
+    );
+    let state = estimate_state(&start, observations)?;
+    run_many_sims_from_state(num_runs, combatants, arena, &state)
 }
 
 pub fn clamp(n: f64, min: f64, max: f64) -> f64 {
@@ -52,6 +108,15 @@ pub fn clamp(n: f64, min: f64, max: f64) -> f64 {
     x
 }
 
+pub fn log_loss(left_wins_percent: f64, left_wins: bool) -> f64 {
+    let clamped = clamp(left_wins_percent, 1e-15, 1.0 - 1e-15);
+    if left_wins {
+        -clamped.ln()
+    } else {
+        -((1.0 - clamped).ln())
+    }
+}
+
 pub fn match_to_combatant_infos<'a>(
     patch: &'a Patch,
     match_up: &'a MatchUp,
@@ -121,14 +186,29 @@ pub fn match_to_combatants<'a>(combatant_infos: &'a [CombatantInfo<'a>]) -> [Com
     ]
 }
 
-pub fn run_specific_match(match_id: u64, num_runs: i32) -> io::Result<()> {
-    let patches = data::read_all_patches()?;
-    let mut buffer = Vec::with_capacity(1024 * 1024 * 2);
-    let (patch_num, match_up) = data::read_match(match_id, &mut buffer)?;
-    let patch = patches
-        .iter()
-        .find(|p| p.time as usize == patch_num)
-        .unwrap();
+fn load_match(match_id: u64) -> io::Result<(MatchUp, Patch)> {
+    let archive = Archive::open(ARCHIVE_PATH)?;
+    let entry = archive.match_entry(match_id).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no match {} in {}", match_id, ARCHIVE_PATH),
+        )
+    })?;
+    let match_up = archive.read_match(entry)?;
+    let patch = archive.read_patch(entry.patch_time)?;
+    Ok((match_up, patch))
+}
+
+pub fn run_specific_match(
+    match_id: u64,
+    num_runs: i32,
+    interactive: bool,
+    trace_ai: bool,
+    svg: Option<String>,
+    svg_at: Option<usize>,
+    replay: Option<String>,
+) -> io::Result<()> {
+    let (match_up, patch) = load_match(match_id)?;
     let combatant_infos = match_to_combatant_infos(&patch, &match_up);
     let combatants = match_to_combatants(&combatant_infos);
     let arena = Arena::from_dto(match_up.arena.clone());
@@ -136,50 +216,109 @@ pub fn run_specific_match(match_id: u64, num_runs: i32) -> io::Result<()> {
     let (left_wins_percent, new_time_outs) = run_many_sims(num_runs, &combatants, &arena);
     let rng = SmallRng::from_entropy();
     let mut sim = Simulation::new(combatants.clone(), &arena, &pathfinder, rng, true);
+    sim.trace_ai = interactive || trace_ai;
     sim.run();
 
     for combatant in &combatants {
         println!("{}", unit_card(combatant));
     }
     println!("Playing on {}", &match_up.arena_name);
-    for entry in sim.log.entries() {
-        println!("{}", describe_entry(&entry, &arena));
+    println!("{}", ascii_arena(&arena, Some(&combatants)));
+    if let Some(path) = svg {
+        let entries = sim.log.entries();
+        let snapshot = svg_at
+            .and_then(|tick| entries.iter().find(|entry| entry.clock_tick >= tick))
+            .map_or(&combatants[..], |entry| &entry.combatants[..]);
+        render::write_svg(&path, &arena, Some(snapshot))?;
+        println!("wrote {}", path);
     }
-    let clamped = clamp(left_wins_percent, 1e-15, 1.0 - 1e-15);
-    let current_log_loss = if match_up.left_wins.unwrap() {
-        -clamped.ln()
+    if let Some(path) = replay {
+        let title = format!(
+            "{} vs {} on {}",
+            match_up.left.color, match_up.right.color, match_up.arena_name
+        );
+        replay::write_replay(&path, &title, &arena, &sim.log.entries())?;
+        println!("wrote {}", path);
+    }
+    if interactive {
+        let entries = sim.log.entries();
+        let stdin = io::stdin();
+        Debugger::new(&entries, &arena).run(stdin.lock())?;
     } else {
-        -((1.0 - clamped).ln())
-    };
+        for entry in sim.log.entries() {
+            println!("{}", describe_entry(&entry, &arena));
+        }
+    }
+    let current_log_loss = log_loss(left_wins_percent, match_up.left_wins.unwrap());
     println!("log loss: {:.6}", current_log_loss as f64);
     println!("time outs: {}", new_time_outs);
     Ok(())
 }
 
-pub fn has_equip(combatants: &[CombatantInfo], name: &str) -> bool {
-    combatants.iter().any(|info| {
-        info.main_hand.map_or(false, |eq| eq.name == name)
-            || info.off_hand.map_or(false, |eq| eq.name == name)
-            || info.headgear.map_or(false, |eq| eq.name == name)
-            || info.armor.map_or(false, |eq| eq.name == name)
-            || info.accessory.map_or(false, |eq| eq.name == name)
-    })
-}
-
-pub fn has_ability(combatants: &[CombatantInfo], name: &str) -> bool {
-    combatants
-        .iter()
-        .any(|info| info.abilities.iter().any(|ability| ability.name == name))
-}
-
-pub fn has_skill(combatants: &[CombatantInfo], name: &str) -> bool {
-    combatants
-        .iter()
-        .any(|info| info.all_skills.iter().any(|skill| *skill == name))
+pub fn forecast_match(match_id: u64, num_turns: usize) -> io::Result<()> {
+    let (match_up, patch) = load_match(match_id)?;
+    let combatant_infos = match_to_combatant_infos(&patch, &match_up);
+    let combatants = match_to_combatants(&combatant_infos);
+    let arena = Arena::from_dto(match_up.arena.clone());
+    let pathfinder = RefCell::new(Pathfinder::new(&arena));
+    let rng = SmallRng::from_entropy();
+    let sim = Simulation::new(combatants, &arena, &pathfinder, rng, false);
+    for turn in sim.forecast_turns(num_turns) {
+        println!(
+            "CT {}: {}",
+            turn.clock_tick,
+            describe_phase(&turn.phase, &sim.combatants)
+        );
+    }
+    Ok(())
 }
 
-pub fn has_monster(combatants: &[CombatantInfo]) -> bool {
-    combatants.iter().any(|info| info.gender == Gender::Monster)
+pub fn optimize_match(
+    match_id: u64,
+    num_runs: u32,
+    right: bool,
+    slots: &[SlotChoice],
+    search: Search,
+) -> io::Result<()> {
+    let (match_up, patch) = load_match(match_id)?;
+    let arena = Arena::from_dto(match_up.arena.clone());
+    let (team, color) = if right {
+        (Team::Right, &match_up.right.color)
+    } else {
+        (Team::Left, &match_up.left.color)
+    };
+    let optimizer = Optimizer::new(&patch, &match_up, &arena, team, slots, num_runs)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    println!(
+        "Optimizing the {} team with {:?} search, {} runs per loadout",
+        color, search, num_runs
+    );
+    let report = optimizer.optimize(search);
+    let percent = |x: f64| format!("{:.1}%", x * 100.0);
+    let signed = |x: f64| format!("{:+.1}%", x * 100.0);
+    for (label, interval) in &[("baseline", report.baseline), ("best", report.best)] {
+        println!(
+            "{:>8}: {} ({} to {})",
+            label,
+            percent(interval.estimate),
+            percent(interval.low),
+            percent(interval.high)
+        );
+    }
+    for change in &report.changes {
+        println!(
+            "  {} {}: {} -> {}  {} ({} to {})",
+            change.name,
+            change.slot.name(),
+            change.from,
+            change.to,
+            signed(change.marginal.estimate),
+            signed(change.marginal.low),
+            signed(change.marginal.high)
+        );
+    }
+    println!("{} simulations", report.simulations);
+    Ok(())
 }
 
 fn record_unit_kinds(involves: &mut HashMap<String, i32>, match_up: &MatchUp) {
@@ -216,7 +355,9 @@ struct ResultsData {
     time_outs: u64,
     log_loss: f64,
     worst_loss: f64,
-    replay_path: PathBuf,
+    replay_match_id: u64,
+    scored: u64,
+    predictions: Vec<Prediction>,
     replay_data: Vec<String>,
 }
 
@@ -224,23 +365,27 @@ pub fn run_all_matches(
     num_runs: i32,
     print_worst: bool,
     save: bool,
-    filter_equip: Vec<String>,
-    filter_ability: Vec<String>,
-    filter_skill: Vec<String>,
-    filter_no_monsters: bool,
-    filter_map: Vec<String>,
+    filter: MatchFilter,
     most_recent: Option<u64>,
+    compare_with: Option<String>,
 ) -> io::Result<()> {
-    let patches = data::read_all_patches()?;
+    // Read before simulating (and before --save can overwrite it) so a bad path fails fast.
+    let baseline = match &compare_with {
+        Some(path) => Some(compare::load_baseline(path)?),
+        None => None,
+    };
+
+    let archive = Archive::open(ARCHIVE_PATH)?;
+    let patches = archive.read_all_patches()?;
 
     println!("{} patches\n", patches.len());
 
-    let mut match_up_paths = data::find_all_match_ups()?;
+    let mut match_entries: Vec<_> = archive.matches().iter().collect();
+    match_entries.sort_by_key(|entry| entry.match_id);
 
     if let Some(most_recent) = most_recent {
-        match_up_paths.sort();
-        match_up_paths.reverse();
-        match_up_paths.truncate(most_recent as usize);
+        match_entries.reverse();
+        match_entries.truncate(most_recent as usize);
     }
 
     let data = Mutex::new(ResultsData {
@@ -252,11 +397,13 @@ pub fn run_all_matches(
         time_outs: 0,
         log_loss: 0.0,
         worst_loss: 0.0,
-        replay_path: PathBuf::new(),
+        replay_match_id: 0,
+        scored: 0,
+        predictions: vec![],
         replay_data: vec![],
     });
 
-    let bar1 = ProgressBar::new(match_up_paths.len() as u64);
+    let bar1 = ProgressBar::new(match_entries.len() as u64);
     bar1.set_style(
         ProgressStyle::default_bar()
             .template(
@@ -265,48 +412,50 @@ pub fn run_all_matches(
             .progress_chars("##-"),
     );
 
-    let match_ups: Vec<_> = match_up_paths
+    // One bad record shouldn't throw away a whole run, so those get reported & skipped.
+    let unreadable = AtomicU64::new(0);
+    let match_ups: Vec<_> = match_entries
         .par_iter()
-        .flat_map(|match_up_path| {
+        .flat_map(|entry| {
             bar1.inc(1);
-            let mut buffer = Vec::with_capacity(1024 * 1024);
-            let (patch_num, match_up) =
-                data::read_match_at_path(&match_up_path, &mut buffer).unwrap();
-            let patch = patches
-                .iter()
-                .find(|p| p.time as usize == patch_num)
-                .unwrap();
+            let skip = |reason: String| {
+                bar1.println(format!("skipping match {}: {}", entry.match_id, reason));
+                unreadable.fetch_add(1, Ordering::Relaxed);
+                None
+            };
+            let match_up = match archive.read_match(entry) {
+                Ok(match_up) => match_up,
+                Err(err) => return skip(err.to_string()),
+            };
+            let patch = match patches.get(&entry.patch_time) {
+                Some(patch) => patch,
+                None => return skip(format!("patch {} missing from archive", entry.patch_time)),
+            };
             let combatant_infos = match_to_combatant_infos(&patch, &match_up);
-
-            for equip in &filter_equip {
-                if !has_equip(&combatant_infos, equip) {
-                    return None;
-                }
-            }
-            for ability in &filter_ability {
-                if !has_ability(&combatant_infos, ability) {
-                    return None;
-                }
-            }
-            for skill in &filter_skill {
-                if !has_skill(&combatant_infos, skill) {
-                    return None;
-                }
-            }
-            if filter_no_monsters && has_monster(&combatant_infos) {
+            let info = MatchInfo {
+                match_up: &match_up,
+                patch,
+                combatants: &combatant_infos,
+                time_out_rate: None,
+            };
+            // Anything that depends on time outs gets checked again after simulating.
+            if filter.check(&info) == Some(false) {
                 return None;
             }
-            for map in &filter_map {
-                if !match_up.arena_name.contains(map) {
-                    return None;
-                }
-            }
 
-            Some((match_up_path, patch, match_up))
+            Some((entry.match_id, patch, match_up))
         })
         .collect();
     bar1.finish();
 
+    // Build each map (and its tables) once, every thread then shares it.
+    let mut arenas: HashMap<&str, Arena> = HashMap::new();
+    for (_, _, match_up) in &match_ups {
+        arenas
+            .entry(&match_up.arena_name)
+            .or_insert_with(|| Arena::from_dto(match_up.arena.clone()));
+    }
+
     let total = match_ups.len() as u64 * num_runs as u64;
 
     let bar = ProgressBar::new(total);
@@ -320,20 +469,31 @@ pub fn run_all_matches(
 
     match_ups
         .par_iter()
-        .for_each(|(match_up_path, patch, match_up)| {
+        .for_each(|(match_id, patch, match_up)| {
             bar.inc(num_runs as u64);
 
             let combatant_infos = match_to_combatant_infos(&patch, &match_up);
             let combatants = match_to_combatants(&combatant_infos);
-            let arena = Arena::from_dto(match_up.arena.clone());
-            let (left_wins_percent, new_time_outs) = run_many_sims(num_runs, &combatants, &arena);
+            let arena = &arenas[match_up.arena_name.as_str()];
+            let (left_wins_percent, new_time_outs) = run_many_sims(num_runs, &combatants, arena);
+
+            let info = MatchInfo {
+                match_up,
+                patch,
+                combatants: &combatant_infos,
+                time_out_rate: Some(new_time_outs as f64 / num_runs as f64),
+            };
+            if filter.check(&info) != Some(true) {
+                return;
+            }
 
             let mut data = data.lock().unwrap();
 
+            data.scored += 1;
             data.time_outs += new_time_outs;
 
             // if new_time_outs > (num_runs as u64 / 2) {
-            //     println!("time out heavy match: {}", replay_path.to_string_lossy());
+            //     println!("time out heavy match: {}", match_id);
             // }
 
             let tournament_map = data
@@ -341,7 +501,7 @@ pub fn run_all_matches(
                 .entry(match_up.tournament_id.to_string())
                 .or_insert(HashMap::new());
             let key = format!("{},{}", match_up.left.color, match_up.right.color);
-            tournament_map.insert(key, left_wins_percent);
+            tournament_map.insert(key.clone(), left_wins_percent);
 
             if match_up.left_wins.unwrap() && left_wins_percent > 0.5 {
                 data.correct += 1;
@@ -349,13 +509,15 @@ pub fn run_all_matches(
                 data.correct += 1;
             }
 
-            let clamped = clamp(left_wins_percent, 1e-15, 1.0 - 1e-15);
-            let current_log_loss = if match_up.left_wins.unwrap() {
-                -clamped.ln()
-            } else {
-                -((1.0 - clamped).ln())
-            };
+            let current_log_loss = log_loss(left_wins_percent, match_up.left_wins.unwrap());
             data.log_loss += current_log_loss;
+            data.predictions.push(Prediction {
+                match_id: *match_id,
+                tournament_id: match_up.tournament_id,
+                key: key.clone(),
+                left_wins: match_up.left_wins.unwrap(),
+                left_wins_percent,
+            });
 
             record_unit_kinds(&mut data.overall_involves, &match_up);
             if print_worst && current_log_loss >= 2.0 {
@@ -365,11 +527,10 @@ pub fn run_all_matches(
 
             if print_worst && current_log_loss >= data.worst_loss {
                 data.worst_loss = current_log_loss;
-                data.replay_path = (*match_up_path).clone();
+                data.replay_match_id = *match_id;
                 let rng = SmallRng::from_entropy();
-                let arena = Arena::from_dto(match_up.arena.clone());
-                let pathfinder = RefCell::new(Pathfinder::new(&arena));
-                let mut sim = Simulation::new(combatants.clone(), &arena, &pathfinder, rng, true);
+                let pathfinder = RefCell::new(Pathfinder::new(arena));
+                let mut sim = Simulation::new(combatants.clone(), arena, &pathfinder, rng, true);
                 sim.run();
                 data.replay_data.clear();
                 data.replay_data
@@ -382,7 +543,7 @@ pub fn run_all_matches(
                     .push(format!("Playing on {}", &match_up.arena_name));
                 for entry in sim.log.entries() {
                     data.replay_data
-                        .push(format!("{}", describe_entry(&entry, &arena)));
+                        .push(format!("{}", describe_entry(&entry, arena)));
                 }
             }
         });
@@ -390,7 +551,7 @@ pub fn run_all_matches(
 
     let data = data.lock().unwrap();
 
-    println!("\nmatch {}:", data.replay_path.to_string_lossy());
+    println!("\nmatch {}:", data.replay_match_id);
     for line in &data.replay_data {
         println!("{}", line);
     }
@@ -399,7 +560,7 @@ pub fn run_all_matches(
     let mut worst_involves_pairs = vec![];
     for key in data.worst_involves.keys() {
         let overall_amount =
-            *data.overall_involves.get(key).unwrap_or(&0) as f32 / match_ups.len() as f32;
+            *data.overall_involves.get(key).unwrap_or(&0) as f32 / data.scored as f32;
         let worst_amount =
             *data.worst_involves.get(key).unwrap_or(&0) as f32 / data.worst_count as f32;
         let more = worst_amount / overall_amount.max(0.01);
@@ -413,9 +574,13 @@ pub fn run_all_matches(
         println!("{:>25}: {:.4}", entry.0, entry.1);
     }
 
-    let total_matches = match_ups.len();
+    let total_matches = data.scored;
     let correct_percent = data.correct as f32 / total_matches as f32;
     println!("\ntotal: {}", total_matches);
+    let unreadable = unreadable.into_inner();
+    if unreadable > 0 {
+        println!("skipped: {} unreadable", unreadable);
+    }
     println!("correct: {:.1}%", correct_percent * 100.0);
     println!(
         "time_outs: {:.1}%",
@@ -424,6 +589,10 @@ pub fn run_all_matches(
     println!("improvement: {:.1}%", (correct_percent - 0.5) * 200.0);
     println!("log loss: {:.6}", data.log_loss / total_matches as f64);
 
+    if let (Some(path), Some(baseline)) = (&compare_with, &baseline) {
+        compare::print_comparison(path, baseline, &data.predictions);
+    }
+
     if save {
         let bin = serde_json::to_vec_pretty(&data.results).unwrap();
         let mut file = std::fs::File::create("data/sim.json")?;
@@ -1,7 +1,8 @@
 use crate::dto::rust::Equipment;
-use crate::sim::actions::common::mod_5_formula_pass_ma;
+use crate::sim::actions::formula::{mod_5_formula_pass_ma, zodiac};
 use crate::sim::actions::{
-    Ability, AbilityImpl, Action, AoE, ALLY_OK, BERSERK_OK, FOE_OK, FROG_OK, TARGET_NOT_SELF,
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, BERSERK_OK, FOE_OK, FROG_OK,
+    TARGET_NOT_SELF,
 };
 use crate::sim::{
     Combatant, CombatantId, Condition, Simulation, Source, WeaponType, DAMAGE_CANCELS,
@@ -21,6 +22,9 @@ pub struct AttackImpl {
 }
 
 impl AbilityImpl for AttackImpl {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -70,9 +74,7 @@ pub fn attack_range(sim: &Simulation, user: &Combatant, target: &Combatant) -> u
             let wp_range = eq.range as u8;
             if eq.weapon_type == Some(WeaponType::Bow) {
                 // TODO: This doesn't really make sense to put here, but I'm going to try it anyways
-                let user_height = sim.combatant_height(user.id());
-                let target_height = sim.combatant_height(target.id());
-                let bonus = (user_height - target_height) / 2.0;
+                let bonus = sim.facing_height_diff(user.id(), target.id()) / 2.0;
                 ((wp_range as i8) + (bonus as i8).min(0)) as u8
             } else {
                 wp_range
@@ -269,7 +271,7 @@ fn calculate_damage<'a, 'b>(
         xa = (xa * 3) / 2;
     }
 
-    xa = (xa as f32 * user.zodiac_compatibility(target)).floor() as i16;
+    xa = zodiac(user, target, xa);
 
     if user.barehanded() {
         damage = xa * user.pa_bang() as i16;
@@ -1,5 +1,8 @@
-use crate::sim::actions::common::{mod_2_formula_xa, EmpowerImpl};
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK, TARGET_NOT_SELF};
+use crate::sim::actions::common::EmpowerImpl;
+use crate::sim::actions::formula::mod_2_formula_xa;
+use crate::sim::actions::{
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK, TARGET_NOT_SELF,
+};
 use crate::sim::{
     Combatant, CombatantId, Condition, Element, EquipSlot, Event, Simulation, Source, WeaponType,
     STATS_ABILITY, TARGET_SELF_ONLY,
@@ -89,6 +92,9 @@ struct DashImpl {
 }
 
 impl AbilityImpl for DashImpl {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
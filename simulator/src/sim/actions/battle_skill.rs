@@ -1,8 +1,9 @@
 use crate::dto::rust::Equipment;
 use crate::sim::actions::attack::do_single_weapon_attack;
-use crate::sim::actions::common::{do_hp_damage, do_hp_heal, mod_3_formula_xa};
+use crate::sim::actions::common::{do_hp_damage, do_hp_heal};
+use crate::sim::actions::formula::{elemental, mod_3_formula_xa, zodiac, zodiac_hit};
 use crate::sim::actions::{
-    Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK, HITS_FOES_ONLY, TARGET_NOT_SELF,
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK, HITS_FOES_ONLY, TARGET_NOT_SELF,
 };
 use crate::sim::{
     Combatant, CombatantId, Condition, EquipSlot, Event, Simulation, Source, WeaponType,
@@ -185,6 +186,9 @@ struct MightySkillImpl {
 }
 
 impl AbilityImpl for MightySkillImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -212,7 +216,8 @@ impl AbilityImpl for MightySkillImpl {
             let user = sim.combatant(user_id);
             let target = sim.combatant(target_id);
 
-            let mut xa = mod_3_formula_xa(user.pa() as i16, user, target, false, false);
+            let mut xa = mod_3_formula_xa(user.pa(), user, target, false, false);
+            xa = zodiac(user, target, xa);
             if sim.roll_auto_fail() < 0.05 {
                 xa += sim.roll_inclusive(1, xa.max(1)) - 1;
             }
@@ -233,6 +238,9 @@ struct AbsorbSwordImpl {
 }
 
 impl AbilityImpl for AbsorbSwordImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -253,7 +261,8 @@ impl AbilityImpl for AbsorbSwordImpl {
         let target = sim.combatant(target_id);
 
         // TODO: This is a weapon elemental attack sooo...
-        let mut xa = mod_3_formula_xa(user.pa() as i16, user, target, false, false);
+        let mut xa = mod_3_formula_xa(user.pa(), user, target, false, false);
+        xa = zodiac(user, target, xa);
         if sim.roll_auto_fail() < 0.05 {
             xa += sim.roll_inclusive(1, xa.max(1)) - 1;
         }
@@ -280,6 +289,9 @@ struct ChanceToAddSwordImpl {
 }
 
 impl AbilityImpl for ChanceToAddSwordImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -299,19 +311,12 @@ impl AbilityImpl for ChanceToAddSwordImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut xa = mod_3_formula_xa(user.pa() as i16, user, target, false, false);
+        let mut xa = mod_3_formula_xa(user.pa(), user, target, false, false);
+        xa = zodiac(user, target, xa);
 
         // TODO: This shouldn't go right here, ugh
         if let Some(element) = user.main_hand().and_then(|eq| eq.weapon_element) {
-            if target.weak(element) {
-                xa *= 2;
-            }
-            if target.halves(element) {
-                xa /= 2;
-            }
-            if target.absorbs(element) {
-                xa = -xa;
-            }
+            xa = elemental(target, element, xa);
         }
 
         if sim.roll_auto_fail() < 0.05 {
@@ -335,6 +340,9 @@ struct BreakEquipImpl {
 }
 
 impl AbilityImpl for BreakEquipImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -407,8 +415,7 @@ fn try_break_equip<'a>(
     let mod_pa = mod_3_formula_xa(user.pa() as i16, user, target, false, false);
     let mod_wp = mod_3_formula_xa(wp as i16, user, target, false, false);
 
-    let mut chance = mod_pa as f32 + mod_wp as f32 + base_chance as f32 / 100.0;
-    chance *= user.zodiac_compatibility(target);
+    let chance = zodiac_hit(user, target, mod_pa + mod_wp, base_chance);
 
     let weapon_type = equip.and_then(|eq| eq.weapon_type);
     if sim.do_physical_evade(user, target, weapon_type, Source::Ability) {
@@ -429,6 +436,9 @@ struct MagicBreakImpl {
 }
 
 impl AbilityImpl for MagicBreakImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -450,8 +460,7 @@ impl AbilityImpl for MagicBreakImpl {
         let target = sim.combatant(target_id);
         // TODO: Ok, I know this probably happens twice with dual wield.. bluh.
         let xa = mod_3_formula_xa(user.pa() as i16, user, target, false, false);
-        let mut chance = (xa as f32 + self.base_chance as f32) / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, xa, self.base_chance);
         let weapon_type = user.main_hand().and_then(|eq| eq.weapon_type);
         if sim.do_physical_evade(user, target, weapon_type, Source::Ability) {
             sim.log_event(Event::AbilityMissed(user_id, target_id));
@@ -1,8 +1,8 @@
 use crate::sim::actions::attack::do_single_weapon_attack;
 
 use crate::sim::actions::{
-    Ability, AbilityImpl, Action, AoE, ALLY_OK, DONT_MOVE_WHILE_CHARGING, FOE_OK, NO_SHORT_CHARGE,
-    TARGET_NOT_SELF,
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, DONT_MOVE_WHILE_CHARGING, FOE_OK,
+    NO_SHORT_CHARGE, TARGET_NOT_SELF,
 };
 use crate::sim::{
     Combatant, CombatantId, Condition, EquipSlot, Event, Simulation, Source, WeaponType,
@@ -122,6 +122,9 @@ struct ChargeImpl {
 }
 
 impl AbilityImpl for ChargeImpl {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
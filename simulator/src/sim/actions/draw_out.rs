@@ -1,4 +1,5 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK};
+use crate::sim::actions::formula::zodiac;
+use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK};
 use crate::sim::{
     Combatant, CombatantId, Condition, Element, Simulation, Source, FOE_OK, HITS_ALLIES_ONLY,
     HITS_FOES_ONLY, NOT_ALIVE_OK, SILENCEABLE, TARGET_NOT_SELF, TARGET_SELF_ONLY,
@@ -162,6 +163,9 @@ struct DrawOutDamageImpl {
 }
 
 impl AbilityImpl for DrawOutDamageImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -191,7 +195,7 @@ impl AbilityImpl for DrawOutDamageImpl {
             }
         }
 
-        ma = (ma as f32 * user.zodiac_compatibility(target)) as i16;
+        ma = zodiac(user, target, ma);
         let damage = ma * self.ma_factor;
         if self.damage_hp_not_mp {
             sim.change_target_hp(target_id, damage, Source::Ability);
@@ -218,6 +222,9 @@ struct DrawOutBuffImpl {
 }
 
 impl AbilityImpl for DrawOutBuffImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,5 +1,5 @@
-use crate::sim::actions::common::mod_5_formula_xa;
-use crate::sim::actions::{Ability, AbilityImpl, Action};
+use crate::sim::actions::formula::mod_5_formula_xa;
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula};
 use crate::sim::constants::*;
 
 use crate::sim::{
@@ -189,6 +189,9 @@ struct ElementalImpl {
 }
 
 impl AbilityImpl for ElementalImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -201,7 +204,7 @@ impl AbilityImpl for ElementalImpl {
         if !self.terrain.contains(&tile.surface_type) {
             return;
         }
-        instant_aoe_consider(self.range, actions, ability, target)
+        instant_aoe_consider(sim, user, self.range, actions, ability, target)
     }
 
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
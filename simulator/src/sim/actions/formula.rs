@@ -0,0 +1,441 @@
+use crate::sim::{Combatant, Element, Simulation};
+
+// Damage and hit chance formulas, numbered after the "mod"s of the FFT Battle Mechanics
+// Guide. Each step floors the way the game does, so keep them in the guide's order.
+
+/// Which formula an ability works out its damage, healing or hit chance with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Formula {
+    /// Fixed amounts and chances that nothing modifies (mod 0).
+    Fixed,
+    /// Weapon damage, with crits, elements, support abilities and zodiac (mod 2).
+    Weapon,
+    /// PA based damage and hit chances, without crits or elements (mod 3).
+    Physical,
+    /// Speed based hit chances, e.g. Steal (mod 4).
+    Speed,
+    /// Magic damage and healing, scaled by faith (mod 5).
+    Magic,
+    /// Magic hit chances, scaled by faith (mod 6).
+    MagicHit,
+    /// A stat plus zodiac, but no faith, e.g. Talk Skill, Draw Out and most monster skills.
+    Stat,
+}
+
+impl Formula {
+    /// Whether an Innocent (or zero faith) caster or target can never be affected.
+    pub fn uses_faith(self) -> bool {
+        self == Formula::Magic || self == Formula::MagicHit
+    }
+}
+
+/// Apply zodiac multipliers:
+///   If compatibility is 'Good', then (X' = X + [X / 4])
+///   ElseIf compatibility is 'Bad', then (X' = X - [X / 4])
+///   ElseIf compatibility is 'Best', then (X' = X + [X / 2])
+///   ElseIf compatibility is 'Worst', then (X' = X - [X / 2])
+///   Else, X' = X
+pub fn zodiac(user: &Combatant, target: &Combatant, x: i16) -> i16 {
+    x + user.compatibility(target).addend(x)
+}
+
+/// Weak doubles, Half halves and Absorb turns damage into healing, in that order.
+pub fn elemental(target: &Combatant, element: Element, mut amount: i16) -> i16 {
+    if target.weak(element) {
+        amount *= 2;
+    }
+    if target.halves(element) {
+        amount /= 2;
+    }
+    if target.absorbs(element) {
+        amount = -amount;
+    }
+    amount
+}
+
+/// Success% = (X + Y + Z), where Z is the zodiac addend:
+///   If compatibility is 'Good', then Z = [X / 4] + [Y / 4]
+///   ElseIf compatibility is 'Bad', then Z = -[X / 4] - [Y / 4]
+///   ElseIf compatibility is 'Best', then Z = [X / 2] + [Y / 2]
+///   ElseIf compatibility is 'Worst', then Z = -[X / 2] - [Y / 2]
+///   Else, Z = 0
+pub fn zodiac_hit(user: &Combatant, target: &Combatant, x: i16, y: i16) -> f32 {
+    let compatibility = user.compatibility(target);
+    let z = compatibility.addend(x) + compatibility.addend(y);
+    (x + y + z) as f32 / 100.0
+}
+
+pub fn mod_2_formula_xa(
+    sim: &Simulation,
+    mut xa: i16,
+    user: &Combatant,
+    target: &Combatant,
+    element: Element,
+    crit: bool,
+    always_apply_martial_arts: bool,
+    ignores_protect_and_defense_up: bool,
+) -> i16 {
+    //    1. If this is a critical hit, then XA1 = XA0 + (1..XA0) - 1.
+    if crit {
+        xa += sim.roll_inclusive(1, xa.max(2)) - 1;
+    }
+
+    //    2. If the attack is endowed with an Element, and the caster has
+    //       equipment that 'Strengthens' that element, then (XA2 = [XA1 * 5/4]),
+    //       else XA2 = XA1
+    if user.strengthens(element) {
+        xa = (xa * 5) / 4;
+    }
+
+    xa = mod_3_formula_xa(
+        xa,
+        user,
+        target,
+        always_apply_martial_arts,
+        ignores_protect_and_defense_up,
+    );
+
+    //   11. Apply zodiac multipliers, see `zodiac`.
+    zodiac(user, target, xa)
+}
+
+pub fn mod_3_formula_xa(
+    mut xa: i16,
+    user: &Combatant,
+    target: &Combatant,
+    always_apply_martial_arts: bool,
+    ignores_protect_and_defense_up: bool,
+) -> i16 {
+    //    3. If caster has Attack UP, then (XA3 = [XA2 * 4/3]), else XA3 = XA2
+    if user.attack_up() {
+        xa = (xa * 4) / 3;
+    }
+
+    //    4. If caster has Martial Arts AND this is not a wpn-elemental attack,
+    //       then (XA4 = [XA3 * 3/2]), else XA4 = XA3
+    if user.martial_arts() && (always_apply_martial_arts || user.barehanded()) {
+        xa = (xa * 3) / 2;
+    }
+
+    //    5. If caster is Berserk, then (XA5 = [XA4 * 3/2]), else XA5 = XA4
+    if user.berserk() {
+        xa = (xa * 3) / 2;
+    }
+
+    //    6. If target has Defense UP, then (XA6 = [XA5 * 2/3]), else XA6 = XA5
+    if !ignores_protect_and_defense_up && target.defense_up() {
+        xa = (xa * 2) / 3;
+    }
+
+    //    7. If target has Protect, then (XA7 = [XA6 * 2/3]), else XA7 = XA6
+    if !ignores_protect_and_defense_up && target.protect() {
+        xa = (xa * 2) / 3;
+    }
+
+    //    8. If target is Charging, then (XA8 = [XA7 * 3/2]), else XA8 = XA7
+    if target.charging() {
+        xa = (xa * 3) / 2;
+    }
+
+    //    9. If target is Sleeping, then (XA9 = [XA8 * 3/2]), else XA9 = XA8
+    if target.sleep() {
+        xa = (xa * 3) / 2;
+    }
+
+    //   10. If target is a Chicken and/or a Frog, then (XA10 = [XA9 * 3/2]),
+    //       else XA10 = XA9
+    if target.chicken() || target.frog() {
+        xa = (xa * 3) / 2;
+    }
+
+    xa
+}
+
+/// Speed based hit chance, `k` is the ability's Y.
+pub fn mod_4_formula(user: &Combatant, target: &Combatant, k: i16) -> f32 {
+    let mut speed = user.speed() as i16;
+
+    //    1. If caster has Attack UP, then (Sp1 = [Sp0 * 4/3]), else Sp1 = Sp0
+    if user.attack_up() {
+        speed = (speed * 4) / 3;
+    }
+
+    //    2. If caster has Martial Arts, then (Sp2 = [Sp1 * 3/2]), else Sp2 = Sp1
+    if user.martial_arts() {
+        speed = (speed * 3) / 2;
+    }
+    //    3. If target has Defense UP, then (Sp3 = [Sp2 * 2/3]), else Sp3 = Sp2
+    if target.defense_up() {
+        speed = (speed * 2) / 3;
+    }
+    //    4. If target has Protect, then (Sp4 = [Sp3 * 2/3]), else Sp4 = Sp3
+    if target.protect() {
+        speed = (speed * 2) / 3;
+    }
+    //    5. If target is Charging, then (Sp5 = [Sp4 * 3/2]), else Sp5 = Sp4
+    if target.charging() {
+        speed = (speed * 3) / 2;
+    }
+    //    6. If target is Sleeping, then (Sp6 = [Sp5 * 3/2]), else Sp6 = Sp5
+    if target.sleep() {
+        speed = (speed * 3) / 2;
+    }
+    //    7. If target is a Frog and/or Chicken, then (Sp7 = [Sp6 * 3/2]), else
+    //       Sp7 = Sp6
+    if target.frog() || target.chicken() {
+        speed = (speed * 3) / 2;
+    }
+
+    //    8. Calculate Z (zodiac addend), see `zodiac_hit`.
+    //    9. Success% = (Sp7 + K + Z)
+    //   10. Consider physical evasion multipliers, if applicable.
+    zodiac_hit(user, target, speed, k)
+}
+
+/// MA after support abilities, Shell and zodiac, steps 1 to 5 of mod 5.
+fn mod_5_ma(
+    mut xa: i16,
+    user: &Combatant,
+    target: &Combatant,
+    element: Element,
+    ignores_shell_and_defense_up: bool,
+) -> i16 {
+    // 1. If caster has 'Strengthen: [element of spell]', then (MA1 = [MA0 * 5/4])
+    //      else MA1 = MA0
+    if user.strengthens(element) {
+        xa = (xa * 5) / 4;
+    }
+    //   2. If caster has Magic AttackUP, then (MA2 = [MA1 * 4/3]), else MA2 = MA1
+    if user.magic_attack_up() {
+        xa = (xa * 4) / 3;
+    }
+
+    //   3. If target has Magic DefendUP, then (MA3 = [MA2 * 2/3]), else MA3 = MA2
+    if !ignores_shell_and_defense_up && target.magic_defense_up() {
+        xa = (xa * 2) / 3;
+    }
+
+    //   4. If target has Shell, then (MA4 = [MA3 * 2/3]), else MA5 = MA4
+    if !ignores_shell_and_defense_up && target.shell() {
+        xa = (xa * 2) / 3;
+    }
+
+    //   5. Apply zodiac multipliers, see `zodiac`.
+    zodiac(user, target, xa)
+}
+
+/// MA for abilities that use mod 5's modifiers without faith, with the element applied.
+pub fn mod_5_formula_xa(
+    xa: i16,
+    user: &Combatant,
+    target: &Combatant,
+    element: Element,
+    ignores_shell_and_defense_up: bool,
+) -> i16 {
+    let xa = mod_5_ma(xa, user, target, element, ignores_shell_and_defense_up);
+    elemental(target, element, xa)
+}
+
+/// Faith scaled damage, [(CFa * TFa * Q * MA5) / 10000]. If caster or target has Faith
+/// status, then CFa = 100 or TFa = 100, respectively. If caster or target has Innocent
+/// status, then CFa = 0 or TFa = 0, respectively.
+fn faith_damage(user: &Combatant, target: &Combatant, q: i16, ma: i16) -> i16 {
+    let faiths = user.effective_faith() as i32 * target.effective_faith() as i32;
+    (faiths * q as i32 * ma as i32 / 10000) as i16
+}
+
+pub fn mod_5_formula_pass_ma(
+    ma: i16,
+    user: &Combatant,
+    target: &Combatant,
+    element: Element,
+    q: i16,
+) -> i16 {
+    let ma = mod_5_ma(ma, user, target, element, false);
+    //   6. damage = [(CFa * TFa * Q * MA5) / 10000]
+    //   7. Weak, Half and Absorb, see `elemental`.
+    elemental(target, element, faith_damage(user, target, q, ma))
+}
+
+pub fn mod_5_formula(user: &Combatant, target: &Combatant, element: Element, q: i16) -> i16 {
+    mod_5_formula_pass_ma(user.ma(), user, target, element, q)
+}
+
+/// Healing, [(CFa * TFa * Q * MA5) / 10000]. The caster's supports count but the target's
+/// Shell and Magic DefendUP don't get in the way of being healed.
+pub fn mod_5_heal(user: &Combatant, target: &Combatant, q: i16) -> i16 {
+    let ma = mod_5_ma(user.ma(), user, target, Element::None, true);
+    faith_damage(user, target, q, ma)
+}
+
+pub fn mod_6_formula(
+    user: &Combatant,
+    target: &Combatant,
+    element: Element,
+    base_chance: i16,
+    ignore_magic_def: bool,
+) -> f32 {
+    let mut ma = user.ma();
+
+    //   1. If caster has 'Strengthen: [element of spell]', then (MA1 = [MA0 * 5/4])
+    //      else MA1 = MA0
+    if user.strengthens(element) {
+        ma = (ma * 5) / 4;
+    }
+
+    //   2. If caster has Magic AttackUP, then (MA2 = [MA1 * 4/3]), else MA2 = MA1
+    if user.magic_attack_up() {
+        ma = (ma * 4) / 3;
+    }
+
+    //   3. If target has Magic DefendUP, then (MA3 = [MA2 * 2/3]), else MA3 = MA2
+    if !ignore_magic_def && target.magic_defense_up() {
+        ma = (ma * 2) / 3;
+    }
+    //   4. If target has Shell, then (MA4 = [MA3 * 2/3]), else MA4 = MA3
+    if !ignore_magic_def && target.shell() {
+        ma = (ma * 2) / 3;
+    }
+
+    //   5. Calculate Z (Zodiac addend), see `zodiac_hit`.
+    //   6. Apply the spell's success% formula as follows:
+    //      success% = [(CFa * TFa * (MA4 + Y + Z)) / 10000]
+    //      If caster or target has Faith status, then CFa = 100 or TFa = 100,
+    //      respectively.  If caster or target has Innocent status, then CFa = 0
+    //      or TFa = 0, respectively.
+    user.faith_percent() * target.faith_percent() * zodiac_hit(user, target, ma, base_chance)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::rust::MatchUp;
+    use crate::dto::test_support::{flat_arena, sim_with};
+    use crate::sim::{
+        ai_consider_actions, Action, CombatantId, Compatibility, Condition, Gender, Sign,
+    };
+
+    /// A Libra Wizard (unit 0) facing the right team, whose first four units are an Aries
+    /// male, an Aries female, a Gemini and a Cancer: best, worst, good and bad compatibility.
+    fn zodiac_sim(check: impl FnOnce(&mut Simulation)) {
+        let tweak = |match_up: &mut MatchUp| {
+            let signs = [
+                (Sign::Aries, Gender::Male),
+                (Sign::Aries, Gender::Female),
+                (Sign::Gemini, Gender::Female),
+                (Sign::Cancer, Gender::Female),
+            ];
+            for (combatant, (sign, gender)) in match_up.right.combatants.iter_mut().zip(&signs) {
+                combatant.sign = *sign;
+                combatant.gender = *gender;
+            }
+        };
+        sim_with(&flat_arena(), tweak, check);
+    }
+
+    fn pair<'s, 'a>(sim: &'s Simulation<'a>, target: u8) -> (&'s Combatant<'a>, &'s Combatant<'a>) {
+        (
+            sim.combatant(CombatantId::new(0)),
+            sim.combatant(CombatantId::new(target)),
+        )
+    }
+
+    #[test]
+    pub fn compatibility_addends_round_down() {
+        assert_eq!(Compatibility::Neutral.addend(7), 0);
+        assert_eq!(Compatibility::Good.addend(7), 1);
+        assert_eq!(Compatibility::Bad.addend(7), -1);
+        assert_eq!(Compatibility::Best.addend(7), 3);
+        assert_eq!(Compatibility::Worst.addend(7), -3);
+    }
+
+    #[test]
+    pub fn zodiac_takes_the_addend_off_the_value() {
+        zodiac_sim(|sim| {
+            let expected = [
+                (4, Compatibility::Best, 10),
+                (5, Compatibility::Worst, 4),
+                (6, Compatibility::Good, 8),
+                (7, Compatibility::Bad, 6),
+                (1, Compatibility::Neutral, 7),
+            ];
+            for (target, compatibility, x) in &expected {
+                let (user, target) = pair(sim, *target);
+                assert_eq!(user.compatibility(target), *compatibility);
+                assert_eq!(zodiac(user, target, 7), *x);
+            }
+        });
+    }
+
+    #[test]
+    pub fn hit_chances_add_zodiac_to_both_terms() {
+        zodiac_sim(|sim| {
+            let (user, target) = pair(sim, 6);
+            // (12 + 30 + [12 / 4] + [30 / 4])%
+            assert_eq!(zodiac_hit(user, target, 12, 30), 0.52);
+            let (user, target) = pair(sim, 7);
+            assert_eq!(zodiac_hit(user, target, 12, 30), 0.32);
+        });
+    }
+
+    #[test]
+    pub fn magic_hit_scales_by_faith_last() {
+        zodiac_sim(|sim| {
+            sim.combatant_mut(CombatantId::new(0))
+                .add_condition(Condition::Faith);
+            sim.combatant_mut(CombatantId::new(6))
+                .add_condition(Condition::Faith);
+            let (user, target) = pair(sim, 6);
+            // (13 + 30 + [13 / 4] + [30 / 4])%
+            assert_eq!(mod_6_formula(user, target, Element::None, 30, false), 0.53);
+
+            sim.combatant_mut(CombatantId::new(6))
+                .add_condition(Condition::Shell);
+            let (user, target) = pair(sim, 6);
+            // MA 13 becomes 8 under Shell: (8 + 30 + 2 + 7)%
+            assert_eq!(mod_6_formula(user, target, Element::None, 30, false), 0.47);
+        });
+    }
+
+    #[test]
+    pub fn magic_damage_floors_zodiac_before_faith() {
+        zodiac_sim(|sim| {
+            let (user, target) = pair(sim, 7);
+            // MA 13 with Bad compatibility is 10, then [65 * 65 * 14 * 10 / 10000] = 59
+            assert_eq!(mod_5_formula(user, target, Element::None, 14), 59);
+            let (user, target) = pair(sim, 1);
+            assert_eq!(mod_5_heal(user, target, 14), 76);
+        });
+    }
+
+    #[test]
+    pub fn faith_damage_floors_once() {
+        zodiac_sim(|sim| {
+            for id in &[0, 1] {
+                sim.combatant_mut(CombatantId::new(*id)).raw_faith = 70;
+            }
+            let (user, target) = pair(sim, 1);
+            // As floats 0.7 * 0.7 is just under 0.49, which floored to 489.
+            assert_eq!(faith_damage(user, target, 10, 100), 490);
+        });
+    }
+
+    #[test]
+    pub fn innocent_casters_do_not_consider_faith_spells() {
+        let uses_faith = |action: &Action| action.ability.implementation.formula().uses_faith();
+        zodiac_sim(|sim| {
+            let user = sim.combatant(CombatantId::new(0));
+            let mut actions = vec![];
+            ai_consider_actions(&mut actions, sim, user, &sim.combatants);
+            assert!(actions.iter().any(uses_faith));
+
+            sim.combatant_mut(CombatantId::new(0))
+                .add_condition(Condition::Innocent);
+            let user = sim.combatant(CombatantId::new(0));
+            let mut actions = vec![];
+            ai_consider_actions(&mut actions, sim, user, &sim.combatants);
+            assert!(!actions.is_empty());
+            assert!(!actions.iter().any(uses_faith));
+        });
+    }
+}
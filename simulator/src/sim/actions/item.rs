@@ -1,4 +1,4 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK};
 use crate::sim::common::{do_hp_heal, should_heal_ally, should_heal_foe};
 use crate::sim::{Combatant, CombatantId, Condition, Simulation, Source, NOT_ALIVE_OK};
 
@@ -150,6 +150,9 @@ struct PotionAbilityImpl {
 }
 
 impl AbilityImpl for PotionAbilityImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -181,6 +184,9 @@ struct ConditionCureItemImpl {
 }
 
 impl AbilityImpl for ConditionCureItemImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -212,6 +218,9 @@ fn item_range(user: &Combatant) -> u8 {
 struct PhoenixDownImpl {}
 
 impl AbilityImpl for PhoenixDownImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
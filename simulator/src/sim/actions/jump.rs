@@ -1,5 +1,6 @@
+use crate::sim::actions::formula::zodiac;
 use crate::sim::actions::{
-    Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK, NO_SHORT_CHARGE, TARGET_NOT_SELF,
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK, NO_SHORT_CHARGE, TARGET_NOT_SELF,
 };
 use crate::sim::{
     Combatant, CombatantId, Condition, Element, Simulation, Source, WeaponType, DAMAGE_CANCELS,
@@ -17,6 +18,9 @@ pub const JUMP_ABILITIES: &[Ability] = &[Ability {
 struct JumpImpl {}
 
 impl AbilityImpl for JumpImpl {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -42,7 +46,7 @@ impl AbilityImpl for JumpImpl {
         }
 
         // TODO: This isn't strictly correct, because this should be checked AFTER the move..
-        let height_diff = sim.height_diff(user.id(), target.id()).abs();
+        let height_diff = sim.facing_height_diff(user.id(), target.id()).abs();
         if height_diff > user.info.vertical_jump as f32 {
             return;
         }
@@ -88,7 +92,7 @@ impl AbilityImpl for JumpImpl {
             xa = (xa * 3) / 2;
         }
 
-        xa = (xa as f32 * user.zodiac_compatibility(target)) as i16;
+        xa = zodiac(user, target, xa);
 
         let damage = if user.barehanded() {
             xa * (user.pa_bang() as f32 * user.brave_percent()) as i16
@@ -1,4 +1,4 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK};
 use crate::sim::{
     ActionTarget, CalcAlgorithm, CalcAttribute, Combatant, CombatantId, Condition, EquipSlot,
     Event, Simulation, Source, WeaponType, CAN_BE_CALCULATED, SILENCEABLE, TARGET_SELF_ONLY,
@@ -15,6 +15,9 @@ pub const MATH_SKILL_ABILITY: Ability = Ability {
 struct MathSkillImpl {}
 
 impl AbilityImpl for MathSkillImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
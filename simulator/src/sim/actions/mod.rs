@@ -1,6 +1,9 @@
+pub use crate::sim::aoe::AoE;
 use crate::sim::{
-    Combatant, CombatantId, Condition, Event, Panel, Simulation, Source, COMBATANT_IDS,
+    Arena, Combatant, CombatantId, Condition, Event, Facing, Location, Panel, Simulation, Source,
+    COMBATANT_IDS,
 };
+pub use formula::Formula;
 
 pub mod attack;
 pub mod basic_skill;
@@ -10,6 +13,7 @@ pub mod charge;
 pub mod common;
 pub mod draw_out;
 pub mod elemental;
+pub mod formula;
 pub mod item;
 pub mod jump;
 pub mod math_skill;
@@ -25,6 +29,10 @@ pub mod white_magic;
 pub mod yin_yang_magic;
 
 pub trait AbilityImpl: Sync {
+    /// The formula `perform` works its numbers out with. This is only a description:
+    /// `perform` calls the formula's functions itself, and the AI reads this to skip
+    /// faith scaled abilities that cannot land.
+    fn formula(&self) -> Formula;
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -62,36 +70,6 @@ pub const PERFORMANCE: AbilityFlags = 1 << 20;
 pub const MISS_SLEEPING: AbilityFlags = 1 << 21;
 pub const CASTER_IMMUNE: AbilityFlags = 1 << 22;
 
-#[derive(Copy, Clone)]
-pub enum AoE {
-    None,
-    Diamond(u8, Option<u8>),
-    Line(Option<u8>),
-    TriLine, // Like that Tiamat ability
-    Global,
-}
-
-impl AoE {
-    pub fn is_line(self) -> bool {
-        match self {
-            AoE::None => false,
-            AoE::Diamond(_size, _tolerance) => false,
-            AoE::Line(_tolerance) => true,
-            AoE::TriLine => true,
-            AoE::Global => false,
-        }
-    }
-
-    // TODO: Tolerance
-    pub fn inside(self, from_loc: Panel, panel: Panel) -> bool {
-        match self {
-            AoE::Diamond(size, _) => from_loc.distance(panel) <= size as i16,
-            AoE::Global => true,
-            _ => false,
-        }
-    }
-}
-
 pub struct Ability<'a> {
     pub flags: AbilityFlags,
     pub mp_cost: i16,
@@ -175,6 +153,74 @@ impl ActionTarget {
             ActionTarget::Math(_, _) => None,
         }
     }
+
+    /// The target a Mime ends up using when copying `ability`, performed by `user_id` on
+    /// this target. Returns None if the mirrored target falls off the map.
+    pub fn mirror(
+        self,
+        sim: &Simulation,
+        ability: &Ability,
+        user_id: CombatantId,
+        mime_id: CombatantId,
+    ) -> Option<ActionTarget> {
+        if ability.flags & TARGET_SELF_ONLY != 0 {
+            return Some(ActionTarget::Id(mime_id));
+        }
+        match (self, ability.aoe) {
+            (ActionTarget::Math(_, _), _) => Some(self),
+            (_, AoE::Global) => Some(self),
+            (ActionTarget::Id(target_id), _) if target_id == user_id => {
+                Some(ActionTarget::Id(mime_id))
+            }
+            (_, aoe) => {
+                let user = sim.combatant(user_id);
+                let mime = sim.combatant(mime_id);
+                let target_panel = self.to_panel(sim)?;
+                mirror_panel(
+                    sim.arena,
+                    user.panel,
+                    target_panel,
+                    mime.panel,
+                    mime.facing,
+                    aoe.is_line(),
+                )
+                .map(ActionTarget::Panel)
+            }
+        }
+    }
+}
+
+/// Mirrors `target` from `user` onto `mime`: the offset to the target is turned from the
+/// direction the user acted in to the direction the Mime is facing. The layer is picked
+/// from the tiles at the mirrored location, to keep the height difference the user had.
+pub fn mirror_panel(
+    arena: &Arena,
+    user: Panel,
+    target: Panel,
+    mime: Panel,
+    mime_facing: Facing,
+    is_line: bool,
+) -> Option<Panel> {
+    let offset = target.location() - user.location();
+    if offset == Location::zero() {
+        return Some(mime);
+    }
+    let rotations = user.facing_towards(target).rotations_to(mime_facing);
+    let mut location = (mime.location() + offset).rotate_around(mime.location(), rotations);
+    if is_line && !arena.in_map(location) {
+        // Lines only care about direction, so aim at the first panel in front instead.
+        location = mime.location() + mime_facing.offset();
+    }
+
+    let height_of = |panel: Panel| {
+        if arena.panel_to_index(panel).is_some() {
+            arena.tile_height(panel)
+        } else {
+            0.0
+        }
+    };
+    let wanted_height = height_of(mime) + height_of(target) - height_of(user);
+    arena.panel_nearest_height(location, wanted_height)
 }
 
 #[derive(Copy, Clone)]
@@ -269,16 +315,27 @@ pub fn ai_consider_actions<'a>(
             continue;
         }
 
+        // Nothing that scales with faith can land if either side has none.
+        let uses_faith = ability.implementation.formula().uses_faith();
+        if uses_faith && user.effective_faith() <= 0 {
+            continue;
+        }
+
         // TODO: Not sure what the probability is supposed to be here.
         if ability.flags & STATS_ABILITY != 0 && sim.roll_inclusive(0, 1) == 1 {
             continue;
         }
 
+        let considered = actions.len();
         for target in targets {
             if !filter_target_level(user, ability, target) {
                 continue;
             }
 
+            if uses_faith && target.effective_faith() <= 0 {
+                continue;
+            }
+
             if user.foe(target) && foes_have_non_disabled {
                 if target.confusion() || target.death_sentence() {
                     continue;
@@ -289,27 +346,74 @@ pub fn ai_consider_actions<'a>(
                 .implementation
                 .consider(actions, ability, sim, user, target);
         }
+
+        if ability.flags & CAN_BE_REFLECTED != 0 {
+            consider_reflect_bounces(actions, considered, sim, user, targets);
+        }
+    }
+}
+
+/// Anything with Reflect is also a way to bounce a spell onto whoever is standing on the far
+/// side of it, friend or foe. Evaluation decides whether that's worth it, but we only try when
+/// the reflector will still be in place when the spell goes off.
+fn consider_reflect_bounces<'a>(
+    actions: &mut Vec<Action<'a>>,
+    considered: usize,
+    sim: &Simulation<'a>,
+    user: &Combatant<'a>,
+    targets: &[Combatant<'a>],
+) {
+    let template = match actions[considered..].first() {
+        Some(action) => *action,
+        None => return,
+    };
+    for target in targets {
+        if target.id() == user.id() || !target.reflect() || !target.alive() {
+            continue;
+        }
+        if target.crystal() || target.jumping() || target.petrify() {
+            continue;
+        }
+        let already_considered = actions[considered..]
+            .iter()
+            .any(|action| action.target.to_target_id_only() == Some(target.id()));
+        if already_considered {
+            continue;
+        }
+        if let Some(ctr) = template.ctr {
+            if !sim.ai_reflect_holds_for(target.id(), ctr) {
+                continue;
+            }
+        }
+        let mut action = template;
+        action.target = ActionTarget::Id(target.id());
+        actions.push(action);
     }
 }
 
-pub fn perform_action_slow<'a>(sim: &mut Simulation<'a>, user_id: CombatantId, action: Action<'a>) {
+pub fn perform_action_slow<'a>(
+    sim: &mut Simulation<'a>,
+    user_id: CombatantId,
+    action: Action<'a>,
+) -> bool {
     let ability = action.ability;
     let user = sim.combatant(user_id);
 
     // TODO: These are redundant with the entire check below..
     if action.ability.flags & SILENCEABLE != 0 && user.silence() {
         sim.log_event(Event::Silenced(user_id, action));
-        return;
+        return false;
     } else if ability.mp_cost > 0 && user.mp() < ability.mp_cost {
         sim.log_event(Event::NoMP(user_id, action));
-        return;
+        return false;
     }
 
     if !filter_ability_level(user, ability) {
-        return;
+        return false;
     }
 
-    perform_action(sim, user_id, action)
+    perform_action(sim, user_id, action);
+    true
 }
 
 pub fn perform_action<'a>(sim: &mut Simulation<'a>, user_id: CombatantId, action: Action<'a>) {
@@ -336,15 +440,13 @@ pub fn perform_action<'a>(sim: &mut Simulation<'a>, user_id: CombatantId, action
     }
 
     if action.ability.flags & CAN_BE_REFLECTED != 0 {
-        // TODO: FFT AI has some awesome decision making around the AI planning it's actions
-        //  based on reflect, if the reflective unit can move by the time the spell goes off,
-        //  etc.
         if let Some(target_id) = action_target.to_target_id(sim) {
             let target = sim.combatant(target_id);
             if target.reflect() {
+                // Bounces as far past the reflector as the caster is in front of it.
                 let user = sim.combatant(user_id);
                 let direction = target.panel.location() - user.panel.location();
-                let new_panel = target.panel.on_same_layer(direction * 2);
+                let new_panel = target.panel.plus(direction);
                 action_target = ActionTarget::Panel(new_panel);
                 sim.log_event(Event::SpellReflected(target_id, new_panel));
             }
@@ -441,64 +543,13 @@ fn handle_normal_ability(
                 // TODO: Something about the ability missing.
             }
         }
-        AoE::Diamond(size, tolerance) => {
-            let main_panel = action
-                .target
-                .to_panel(sim)
-                .expect("should only be none if math");
-            for target_panel in main_panel.diamond(size) {
-                if !sim.in_map(target_panel) || !sim.in_map(main_panel) {
-                    continue;
-                }
-                if let Some(tolerance) = tolerance {
-                    let diff = sim.height(main_panel) - sim.height(target_panel);
-                    if diff.abs() > tolerance as f32 {
-                        continue;
-                    }
-                }
-                perform_aoe_on_panel(sim, user_id, ability, target_panel)
-            }
-        }
-        AoE::Line(tolerance) => {
-            let user = sim.combatant(user_id);
-            let target_panel = action_target
-                .to_panel(sim)
-                .expect("should only be none if math");
-            let user_panel = user.panel;
-            let facing = user.panel.facing_towards(target_panel);
-            for i in 1..=action.range {
-                // TODO: This isn't right either I don't think!
-                let target_panel = user_panel.plus(facing.offset() * i as i16);
-                if !sim.in_map(target_panel) || !sim.in_map(user_panel) {
-                    continue;
-                }
-                if let Some(tolerance) = tolerance {
-                    let diff = sim.height(user_panel) - sim.height(target_panel);
-                    if diff.abs() > tolerance as f32 {
-                        continue;
-                    }
-                }
-                perform_aoe_on_panel(sim, user_id, ability, target_panel);
-            }
-        }
-        AoE::TriLine => {
+        AoE::Diamond(_, _) | AoE::Line(_) | AoE::TriLine(_) => {
             let user = sim.combatant(user_id);
             let target_panel = action_target
                 .to_panel(sim)
                 .expect("should only be none if math");
-            let facing = user.panel.facing_towards(target_panel);
-            let user_panel = user.panel;
-            let left_facing = facing.rotate(3);
-            let right_facing = facing.rotate(1);
-            for i in 1..=action.range {
-                let target_panel = user_panel.plus(facing.offset() * i as i16);
-                perform_aoe_on_panel(sim, user_id, ability, target_panel);
-
-                let target_panel = user_panel.plus(left_facing.offset() * i as i16);
-                perform_aoe_on_panel(sim, user_id, ability, target_panel);
-
-                let target_panel = user_panel.plus(right_facing.offset() * i as i16);
-                perform_aoe_on_panel(sim, user_id, ability, target_panel);
+            for panel in sim.aoe_panels(ability.aoe, user.panel, target_panel, action.range) {
+                perform_aoe_on_panel(sim, user_id, ability, panel);
             }
         }
         AoE::Global => {
@@ -553,7 +604,11 @@ fn perform_on_target(
     ability.implementation.perform(sim, user_id, target_id);
 }
 
+/// Considers every panel that an instant diamond could be aimed at and still catch `target`,
+/// on either layer where there's a bridge overhead.
 pub fn instant_aoe_consider<'a>(
+    sim: &Simulation<'a>,
+    user: &Combatant<'a>,
     range: u8,
     actions: &mut Vec<Action<'a>>,
     ability: &'a Ability<'a>,
@@ -561,8 +616,13 @@ pub fn instant_aoe_consider<'a>(
 ) {
     match ability.aoe {
         AoE::Diamond(size, _) => {
-            for target_panel in target.panel.diamond(size) {
-                actions.push(Action::target_panel(ability, range, None, target_panel));
+            for location in target.panel.location().diamond(size) {
+                for target_panel in sim.arena.panels_at(location) {
+                    if !sim.aoe_covers(ability.aoe, user.panel, target_panel, range, target.panel) {
+                        continue;
+                    }
+                    actions.push(Action::target_panel(ability, range, None, target_panel));
+                }
             }
         }
         _ => {
@@ -570,3 +630,159 @@ pub fn instant_aoe_consider<'a>(
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::test_support::{flat_arena, sim_with};
+    use crate::sim::pathfinder::tests::make_bridge_map;
+    use crate::sim::Log;
+
+    #[test]
+    pub fn mirror_rotates_to_mime_facing() {
+        let arena = make_bridge_map();
+        let user = Panel::coords(0, 0, false);
+        let target = Panel::coords(1, 0, false);
+        let mime = Panel::coords(5, 0, false);
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::West, false);
+        assert_eq!(mirrored, Some(Panel::coords(4, 0, false)));
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::North, false);
+        assert_eq!(mirrored, None);
+    }
+
+    #[test]
+    pub fn mirror_on_top_of_bridge() {
+        let arena = make_bridge_map();
+        let user = Panel::coords(2, 0, true);
+        let target = Panel::coords(3, 0, true);
+        let mime = Panel::coords(3, 0, true);
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::West, false);
+        assert_eq!(mirrored, Some(Panel::coords(2, 0, true)));
+    }
+
+    #[test]
+    pub fn mirror_under_bridge() {
+        let arena = make_bridge_map();
+        let user = Panel::coords(2, 0, false);
+        let target = Panel::coords(3, 0, false);
+        let mime = Panel::coords(3, 0, false);
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::West, false);
+        assert_eq!(mirrored, Some(Panel::coords(2, 0, false)));
+    }
+
+    #[test]
+    pub fn mirror_from_ground_onto_bridge() {
+        let arena = make_bridge_map();
+        let user = Panel::coords(0, 0, false);
+        let target = Panel::coords(2, 0, true);
+        let mime = Panel::coords(5, 0, false);
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::West, false);
+        assert_eq!(mirrored, Some(Panel::coords(3, 0, true)));
+    }
+
+    #[test]
+    pub fn mirror_self_target_keeps_mime_layer() {
+        let arena = make_bridge_map();
+        let user = Panel::coords(0, 0, false);
+        let mime = Panel::coords(3, 0, true);
+        let mirrored = mirror_panel(&arena, user, user, mime, Facing::East, false);
+        assert_eq!(mirrored, Some(mime));
+    }
+
+    #[test]
+    pub fn mirror_off_map() {
+        let arena = make_bridge_map();
+        let user = Panel::coords(0, 0, false);
+        let target = Panel::coords(2, 0, false);
+        let mime = Panel::coords(4, 0, false);
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::East, false);
+        assert_eq!(mirrored, None);
+        // Lines only care about direction, so fall back to the panel in front.
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::East, true);
+        assert_eq!(mirrored, Some(Panel::coords(5, 0, false)));
+        let mime = Panel::coords(5, 0, false);
+        let mirrored = mirror_panel(&arena, user, target, mime, Facing::East, true);
+        assert_eq!(mirrored, None);
+    }
+
+    /// Unit 0 at (0, 0) with Fire, an ally with Reflect lasting `duration` and `ct` to go at
+    /// (2, 0), and a foe at (4, 0) just past them.
+    fn reflect_sim<R>(duration: i32, ct: u8, check: impl FnOnce(&mut Simulation) -> R) -> R {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                for idx in 0..8 {
+                    sim.combatant_mut(CombatantId::new(idx)).panel = Panel::coords(idx, 7, false);
+                }
+                sim.combatant_mut(CombatantId::new(0)).panel = Panel::coords(0, 0, false);
+                let reflector = sim.combatant_mut(CombatantId::new(1));
+                reflector.panel = Panel::coords(2, 0, false);
+                reflector.ct = ct;
+                reflector.conditions.add(Condition::Reflect, Some(duration));
+                sim.combatant_mut(CombatantId::new(4)).panel = Panel::coords(4, 0, false);
+                check(sim)
+            },
+        )
+    }
+
+    /// Whether unit 0 thinks about bouncing Fire off unit 1.
+    fn considers_bounce(sim: &mut Simulation) -> bool {
+        let user = sim.combatant(CombatantId::new(0));
+        let mut actions = vec![];
+        ai_consider_actions(&mut actions, sim, user, &sim.combatants);
+        actions.iter().any(|action| {
+            action.ability.name == "Fire"
+                && action.target.to_target_id_only() == Some(CombatantId::new(1))
+        })
+    }
+
+    #[test]
+    pub fn bounces_land_past_the_reflector() {
+        reflect_sim(32, 0, |sim| {
+            sim.log = Log::new();
+            let info = sim.combatant(CombatantId::new(0)).info;
+            let ability = info.abilities.iter().find(|a| a.name == "Fire").unwrap();
+            let action = Action::new(ability, 5, None, CombatantId::new(1));
+            perform_action(sim, CombatantId::new(0), action);
+
+            let bounced_to = sim
+                .log
+                .entries()
+                .iter()
+                .find_map(|entry| match entry.event {
+                    Event::SpellReflected(id, panel) => Some((id, panel)),
+                    _ => None,
+                });
+            assert_eq!(
+                bounced_to,
+                Some((CombatantId::new(1), Panel::coords(4, 0, false)))
+            );
+            assert_eq!(sim.combatant(CombatantId::new(1)).hp(), 150);
+            assert!(sim.combatant(CombatantId::new(4)).hp() < 150);
+        });
+    }
+
+    #[test]
+    pub fn considers_bouncing_off_a_reflector_that_holds() {
+        assert!(reflect_sim(32, 0, considers_bounce));
+    }
+
+    #[test]
+    pub fn no_bounce_when_reflect_runs_out_first() {
+        // Fire takes 3 ticks to charge.
+        assert!(!reflect_sim(3, 0, considers_bounce));
+        assert!(reflect_sim(4, 0, considers_bounce));
+    }
+
+    #[test]
+    pub fn no_bounce_when_the_reflector_moves_first() {
+        // About to get a turn, and so free to walk off before the spell lands.
+        assert!(!reflect_sim(32, 99, considers_bounce));
+        // Unless they can't move anyway.
+        assert!(reflect_sim(32, 99, |sim| {
+            sim.add_condition(CombatantId::new(1), Condition::DontMove, Source::Phase);
+            considers_bounce(sim)
+        }));
+    }
+}
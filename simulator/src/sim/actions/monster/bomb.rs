@@ -1,4 +1,4 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, FOE_OK};
 
 use crate::sim::actions::attack::AttackImpl;
 use crate::sim::actions::common::ElementalDamageSpellImpl;
@@ -71,6 +71,9 @@ pub const BOMB_ABILITIES: &[Ability] = &[
 struct SelfDestructImpl;
 
 impl AbilityImpl for SelfDestructImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,7 +1,8 @@
-use crate::sim::actions::common::{mod_5_formula, AddConditionSpellImpl};
+use crate::sim::actions::common::AddConditionSpellImpl;
+use crate::sim::actions::formula::mod_5_formula;
 use crate::sim::actions::monster::{BadBreathImpl, Energize, UlmaguestImpl};
 
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Simulation, Source, CAN_BE_REFLECTED,
@@ -204,6 +205,9 @@ struct Manaburn {
 }
 
 impl AbilityImpl for Manaburn {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -229,6 +233,9 @@ struct ByblosElemental {
 }
 
 impl AbilityImpl for ByblosElemental {
+    fn formula(&self) -> Formula {
+        Formula::Magic
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
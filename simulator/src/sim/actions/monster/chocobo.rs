@@ -1,6 +1,7 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 use crate::sim::attack::AttackImpl;
-use crate::sim::common::{do_hp_heal, mod_2_formula_xa, mod_5_formula_xa};
+use crate::sim::common::do_hp_heal;
+use crate::sim::formula::{mod_2_formula_xa, mod_5_formula_xa};
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Simulation, Source, TARGET_NOT_SELF,
     TARGET_SELF_ONLY, TRIGGERS_HAMEDO,
@@ -71,6 +72,9 @@ pub struct ChocoEsunaImpl {
 }
 
 impl AbilityImpl for ChocoEsunaImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -94,6 +98,9 @@ struct ChocoBallImpl {
 }
 
 impl AbilityImpl for ChocoBallImpl {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -140,6 +147,9 @@ pub(crate) struct ChocoMeteorImpl {
 }
 
 impl AbilityImpl for ChocoMeteorImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -163,6 +173,9 @@ struct ChocoCureImpl {
 }
 
 impl AbilityImpl for ChocoCureImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
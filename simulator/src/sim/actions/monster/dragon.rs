@@ -1,6 +1,6 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 
-use crate::sim::common::{mod_2_formula_xa, mod_5_formula_xa};
+use crate::sim::formula::{mod_2_formula_xa, mod_5_formula_xa};
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Event, Simulation, Source, TARGET_NOT_SELF,
     TARGET_SELF_ONLY,
@@ -75,6 +75,9 @@ pub(crate) struct ElementalBreathImpl {
 }
 
 impl AbilityImpl for ElementalBreathImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -105,6 +108,9 @@ struct TailSwingImpl {
 }
 
 impl AbilityImpl for TailSwingImpl {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
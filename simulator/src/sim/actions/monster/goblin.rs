@@ -1,5 +1,6 @@
+use crate::sim::actions::formula::zodiac_hit;
 use crate::sim::actions::punch_art::DamagePunchArt;
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Simulation, Source, CASTER_IMMUNE,
@@ -50,6 +51,9 @@ struct GoblinPunchImpl {
 }
 
 impl AbilityImpl for GoblinPunchImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -65,8 +69,7 @@ impl AbilityImpl for GoblinPunchImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             let hp_diff = user.max_hp() - user.hp();
@@ -82,6 +85,9 @@ struct EyeGougeImpl {
 }
 
 impl AbilityImpl for EyeGougeImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -97,8 +103,7 @@ impl AbilityImpl for EyeGougeImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             sim.add_condition(target_id, Condition::Darkness, Source::Ability);
@@ -114,6 +119,9 @@ struct MutilateImpl {
 }
 
 impl AbilityImpl for MutilateImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -129,8 +137,7 @@ impl AbilityImpl for MutilateImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             let amount = (target.hp() / 3) * 2;
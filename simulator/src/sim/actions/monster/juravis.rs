@@ -1,6 +1,6 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, FOE_OK};
 
-use crate::sim::common::mod_5_formula_xa;
+use crate::sim::formula::{mod_5_formula_xa, zodiac_hit};
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Simulation, Source, TARGET_NOT_SELF,
 };
@@ -48,6 +48,9 @@ struct PeckImpl {
 }
 
 impl AbilityImpl for PeckImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -63,8 +66,7 @@ impl AbilityImpl for PeckImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             sim.change_unit_pa(target_id, -2, Source::Ability);
@@ -80,6 +82,9 @@ struct FeatherBombImpl {
 }
 
 impl AbilityImpl for FeatherBombImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -106,6 +111,9 @@ struct BeakImpl {
 }
 
 impl AbilityImpl for BeakImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -121,8 +129,7 @@ impl AbilityImpl for BeakImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             sim.add_condition(target_id, Condition::Petrify, Source::Ability);
@@ -137,6 +144,9 @@ struct ShineLoverImpl {
 }
 
 impl AbilityImpl for ShineLoverImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -152,8 +162,7 @@ impl AbilityImpl for ShineLoverImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
         if !sim.do_physical_evade(user, target, None, Source::Ability) {
-            let mut chance = (user.pa() + self.base_chance) as f32 / 100.0;
-            chance *= user.zodiac_compatibility(target);
+            let chance = zodiac_hit(user, target, user.pa(), self.base_chance);
 
             if sim.roll_auto_succeed() < chance {
                 let amount = (target.mp() / 3) * 2;
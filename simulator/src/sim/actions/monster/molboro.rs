@@ -1,4 +1,4 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 use crate::sim::attack::AttackImpl;
 
 use crate::sim::{
@@ -60,6 +60,9 @@ struct GooImpl {
 }
 
 impl AbilityImpl for GooImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -89,6 +92,9 @@ pub(crate) struct BadBreathImpl {
 }
 
 impl AbilityImpl for BadBreathImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,6 +1,7 @@
 use crate::sim::actions::monster::ChocoEsunaImpl;
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
-use crate::sim::common::{mod_5_formula_xa, mod_6_formula, ElementalDamageSpellImpl, EmpowerImpl};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
+use crate::sim::common::EmpowerImpl;
+use crate::sim::formula::mod_6_formula;
 use crate::sim::{
     instant_aoe_consider, AoE, Combatant, CombatantId, Condition, Element, Event, Simulation,
     Source, CASTER_IMMUNE, STATS_ABILITY, TARGET_SELF_ONLY,
@@ -67,15 +68,18 @@ struct MindBlastImpl {
 }
 
 impl AbilityImpl for MindBlastImpl {
+    fn formula(&self) -> Formula {
+        Formula::MagicHit
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
         ability: &'a Ability<'a>,
-        _sim: &Simulation<'a>,
-        _user: &Combatant<'a>,
+        sim: &Simulation<'a>,
+        user: &Combatant<'a>,
         target: &Combatant<'a>,
     ) {
-        instant_aoe_consider(self.range, actions, ability, target)
+        instant_aoe_consider(sim, user, self.range, actions, ability, target)
     }
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
         let user = sim.combatant(user_id);
@@ -1,6 +1,7 @@
+use crate::sim::actions::formula::zodiac_hit;
 use crate::sim::actions::steal::StealHeartImpl;
 
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Simulation, Source, CAN_BE_REFLECTED,
@@ -53,6 +54,9 @@ pub struct Energize {
 }
 
 impl AbilityImpl for Energize {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -76,6 +80,9 @@ impl AbilityImpl for Energize {
 struct OinkImpl;
 
 impl AbilityImpl for OinkImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -93,8 +100,7 @@ impl AbilityImpl for OinkImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
         let max_hp = target.max_hp();
-        let compat = user.zodiac_compatibility(target);
-        let chance = ((user.pa() + 71) as f32 * compat) / 100.0;
+        let chance = zodiac_hit(user, target, user.pa(), 71);
         if sim.roll_auto_succeed() < chance {
             sim.change_target_hp(target_id, -((max_hp / 4) * 3), Source::Ability);
         }
@@ -106,6 +112,9 @@ struct TootImpl {
 }
 
 impl AbilityImpl for TootImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,6 +1,6 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 
-use crate::sim::common::mod_5_formula_xa;
+use crate::sim::formula::mod_5_formula_xa;
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Simulation, Source, TARGET_NOT_SELF,
     TARGET_SELF_ONLY, TRIGGERS_HAMEDO,
@@ -66,6 +66,9 @@ struct SoulAbility {
 }
 
 impl AbilityImpl for SoulAbility {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,6 +1,6 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, FOE_OK};
 
-use crate::sim::common::mod_5_formula_xa;
+use crate::sim::formula::{mod_5_formula_xa, mod_6_formula};
 use crate::sim::{AoE, Combatant, CombatantId, Condition, Element, Simulation, Source};
 
 pub const SERPENTARIUS_ABILITIES: &[Ability] = &[
@@ -57,6 +57,9 @@ struct SnakeCarrierImpl {
 }
 
 impl AbilityImpl for SnakeCarrierImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -88,6 +91,9 @@ struct ToxicFrogImpl {
 }
 
 impl AbilityImpl for ToxicFrogImpl {
+    fn formula(&self) -> Formula {
+        Formula::MagicHit
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -103,10 +109,7 @@ impl AbilityImpl for ToxicFrogImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.faith_percent();
-        chance *= target.faith_percent();
-        chance *= user.zodiac_compatibility(target);
+        let chance = mod_6_formula(user, target, Element::None, self.base_chance, false);
 
         if sim.roll_auto_succeed() < chance {
             for condition in self.conditions {
@@ -123,6 +126,9 @@ pub(crate) struct MidgarSwarmImpl {
 }
 
 impl AbilityImpl for MidgarSwarmImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,8 +1,7 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 use crate::sim::attack::AttackImpl;
-use crate::sim::common::{
-    mod_5_formula, mod_5_formula_xa, mod_6_formula, DemiImpl, ElementalDamageSpellImpl,
-};
+use crate::sim::common::DemiImpl;
+use crate::sim::formula::mod_5_formula_xa;
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Event, Simulation, Source, TARGET_NOT_SELF,
     TARGET_SELF_ONLY,
@@ -14,7 +13,7 @@ pub const TIAMAT_ABILITIES: &[Ability] = &[
         name: "Triple Attack",
         flags: FOE_OK | TARGET_NOT_SELF,
         mp_cost: 0,
-        aoe: AoE::TriLine,
+        aoe: AoE::TriLine(Some(2)),
         implementation: &AttackImpl { condition: None },
     },
     // Triple Breath: 2 range, 2 AoE (x3 line). Hit: (MA + 90)%. Effect: Damage (45)%.
@@ -22,8 +21,9 @@ pub const TIAMAT_ABILITIES: &[Ability] = &[
         name: "Triple Breath",
         flags: FOE_OK | TARGET_NOT_SELF,
         mp_cost: 0,
-        aoe: AoE::TriLine,
+        aoe: AoE::TriLine(Some(2)),
         implementation: &DemiImpl {
+            element: Element::None,
             base_chance: 90,
             hp_percent: 0.45,
             range: 2,
@@ -67,6 +67,9 @@ struct TripleElementalImpl {
 }
 
 impl AbilityImpl for TripleElementalImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
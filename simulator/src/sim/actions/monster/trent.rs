@@ -1,6 +1,7 @@
+use crate::sim::actions::formula::zodiac_hit;
 use crate::sim::actions::monster::ChocoMeteorImpl;
 
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK};
 
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Element, Simulation, Source, HITS_ALLIES_ONLY,
@@ -78,6 +79,9 @@ pub struct SpiritCondition {
 }
 
 impl AbilityImpl for SpiritCondition {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -93,8 +97,7 @@ impl AbilityImpl for SpiritCondition {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             for condition in self.add_conditions {
@@ -1,5 +1,6 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK, SILENCEABLE};
-use crate::sim::common::{mod_5_formula_xa, mod_6_formula, ElementalDamageSpellImpl, EmpowerImpl};
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK, SILENCEABLE};
+use crate::sim::common::{ElementalDamageSpellImpl, EmpowerImpl};
+use crate::sim::formula::{mod_5_formula_xa, mod_6_formula};
 use crate::sim::{
     instant_aoe_consider, AoE, Combatant, CombatantId, Condition, Element, Event, Simulation,
     Source, STATS_ABILITY, TARGET_NOT_SELF, TARGET_SELF_ONLY,
@@ -90,6 +91,9 @@ struct NanoflareImpl {
 }
 
 impl AbilityImpl for NanoflareImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -119,15 +123,18 @@ pub(crate) struct UlmaguestImpl {
 }
 
 impl AbilityImpl for UlmaguestImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
         ability: &'a Ability<'a>,
-        _sim: &Simulation<'a>,
-        _user: &Combatant<'a>,
+        sim: &Simulation<'a>,
+        user: &Combatant<'a>,
         target: &Combatant<'a>,
     ) {
-        instant_aoe_consider(self.range, actions, ability, target)
+        instant_aoe_consider(sim, user, self.range, actions, ability, target)
     }
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
         let user = sim.combatant(user_id);
@@ -143,15 +150,18 @@ struct HuricaneImpl {
 }
 
 impl AbilityImpl for HuricaneImpl {
+    fn formula(&self) -> Formula {
+        Formula::MagicHit
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
         ability: &'a Ability<'a>,
-        _sim: &Simulation<'a>,
-        _user: &Combatant<'a>,
+        sim: &Simulation<'a>,
+        user: &Combatant<'a>,
         target: &Combatant<'a>,
     ) {
-        instant_aoe_consider(self.range, actions, ability, target)
+        instant_aoe_consider(sim, user, self.range, actions, ability, target)
     }
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
         let user = sim.combatant(user_id);
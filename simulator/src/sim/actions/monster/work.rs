@@ -1,4 +1,5 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ALLY_OK, FOE_OK};
+use crate::sim::actions::formula::zodiac;
+use crate::sim::actions::{Ability, AbilityImpl, Action, Formula, ALLY_OK, FOE_OK};
 use crate::sim::{
     AoE, Combatant, CombatantId, Condition, Event, Simulation, Source, TARGET_NOT_SELF,
     TARGET_SELF_ONLY,
@@ -58,6 +59,9 @@ struct WorkDamageImpl {
 }
 
 impl AbilityImpl for WorkDamageImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -71,10 +75,10 @@ impl AbilityImpl for WorkDamageImpl {
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
-        let pa = user.pa() as i16;
+        let pa = user.pa();
         let mut damage = pa * self.pa_factor;
         let self_damage = damage / self.hurt_div;
-        damage = (damage as f32 * user.zodiac_compatibility(target)) as i16;
+        damage = zodiac(user, target, damage);
         sim.change_target_hp(target_id, damage, Source::Ability);
         sim.change_target_hp(user_id, self_damage, Source::Ability);
     }
@@ -85,6 +89,9 @@ struct RepairImpl {
 }
 
 impl AbilityImpl for RepairImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,4 +1,4 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, ActionTarget, AoE, ALLY_OK};
+use crate::sim::actions::{Ability, AbilityImpl, Action, ActionTarget, AoE, Formula, ALLY_OK};
 use crate::sim::{
     Combatant, CombatantId, Condition, Element, EquipSlot, Event, Simulation, Source, WeaponType,
     HITS_ALLIES_ONLY, HITS_FOES_ONLY, MISS_SLEEPING, NO_SHORT_CHARGE, PERFORMANCE, STATS_ABILITY,
@@ -246,6 +246,9 @@ struct NamelessImpl {
 }
 
 impl AbilityImpl for NamelessImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -285,6 +288,9 @@ struct HurtDanceImpl {
 }
 
 impl AbilityImpl for HurtDanceImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -319,6 +325,9 @@ struct HealSongImpl {
 }
 
 impl AbilityImpl for HealSongImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -355,6 +364,9 @@ struct StatPerformanceImpl {
 }
 
 impl AbilityImpl for StatPerformanceImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -1,5 +1,7 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK, TARGET_NOT_SELF};
-use crate::sim::common::{mod_2_formula_xa, mod_3_formula_xa};
+use crate::sim::actions::{
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK, TARGET_NOT_SELF,
+};
+use crate::sim::formula::{mod_2_formula_xa, mod_3_formula_xa, zodiac_hit};
 use crate::sim::{
     Combatant, CombatantId, Condition, Element, Simulation, Source, CASTER_IMMUNE,
     HITS_ALLIES_ONLY, HITS_FOES_ONLY, NOT_ALIVE_OK, SILENCEABLE, TARGET_SELF_ONLY,
@@ -116,6 +118,9 @@ pub struct Pummel {
 }
 
 impl AbilityImpl for Pummel {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -162,6 +167,9 @@ pub struct DamagePunchArt {
 }
 
 impl AbilityImpl for DamagePunchArt {
+    fn formula(&self) -> Formula {
+        Formula::Weapon
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -211,6 +219,9 @@ struct SecretFistImpl {
 }
 
 impl AbilityImpl for SecretFistImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -229,8 +240,7 @@ impl AbilityImpl for SecretFistImpl {
         let target = sim.combatant(target_id);
 
         let xa = mod_3_formula_xa(user.ma() as i16, user, target, true, false);
-        let chance =
-            ((self.base_chance as f32 + xa as f32) / 100.0) * user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, xa, self.base_chance);
         if sim.roll_auto_succeed() < chance {
             sim.add_condition(target_id, Condition::DeathSentence, Source::Ability);
         }
@@ -243,6 +253,9 @@ struct ChakraImpl {
 }
 
 impl AbilityImpl for ChakraImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -273,6 +286,9 @@ struct ReviveImpl {
 }
 
 impl AbilityImpl for ReviveImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -296,8 +312,7 @@ impl AbilityImpl for ReviveImpl {
         }
 
         let xa = mod_3_formula_xa(user.pa() as i16, user, target, true, true);
-        let mut chance = (self.base_chance as f32 + xa as f32) / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, xa, self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             let heal_amount = (target.max_hp() as f32 * self.heal_amount) as i16;
@@ -312,6 +327,9 @@ struct PurificationImpl {
 }
 
 impl AbilityImpl for PurificationImpl {
+    fn formula(&self) -> Formula {
+        Formula::Physical
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -327,8 +345,7 @@ impl AbilityImpl for PurificationImpl {
         let target = sim.combatant(target_id);
 
         let xa = mod_3_formula_xa(user.pa() as i16, user, target, true, true);
-        let mut chance = (self.base_chance as f32 + xa as f32) / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, xa, self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             for condition in self.cancels {
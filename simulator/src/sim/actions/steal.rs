@@ -1,4 +1,7 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK, TARGET_NOT_SELF};
+use crate::sim::actions::formula::{mod_4_formula, zodiac_hit};
+use crate::sim::actions::{
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK, TARGET_NOT_SELF,
+};
 use crate::sim::{Combatant, CombatantId, Condition, EquipSlot, Event, Simulation, Source};
 
 pub const STEAL_ABILITIES: &[Ability] = &[
@@ -87,6 +90,9 @@ struct StealImpl {
 }
 
 impl AbilityImpl for StealImpl {
+    fn formula(&self) -> Formula {
+        Formula::Speed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -135,7 +141,7 @@ impl AbilityImpl for StealImpl {
             return;
         }
 
-        let chance = mod_4_formula(user, target, self.base_chance as f32 / 100.0);
+        let chance = mod_4_formula(user, target, self.base_chance);
 
         if sim.do_physical_evade(user, target, None, Source::Ability) {
             sim.log_event(Event::AbilityMissed(user_id, target_id));
@@ -158,6 +164,9 @@ struct GilTakingImpl {
 }
 
 impl AbilityImpl for GilTakingImpl {
+    fn formula(&self) -> Formula {
+        Formula::Speed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -175,8 +184,7 @@ impl AbilityImpl for GilTakingImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut base_chance = (user.speed() as f32 + self.base_chance as f32) / 100.0;
-        base_chance *= user.zodiac_compatibility(target);
+        let base_chance = zodiac_hit(user, target, user.speed() as i16, self.base_chance);
 
         if sim.roll_auto_succeed() < base_chance {
             if sim.roll_inclusive(1, 2) == 1 {
@@ -198,6 +206,9 @@ pub struct StealHeartImpl {
 }
 
 impl AbilityImpl for StealHeartImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -220,8 +231,7 @@ impl AbilityImpl for StealHeartImpl {
             return;
         }
 
-        let mut base_chance = (user.ma() as f32 + self.base_chance as f32) / 100.0;
-        base_chance *= user.zodiac_compatibility(target);
+        let base_chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < base_chance {
             sim.add_condition(target_id, Condition::Charm, Source::Ability);
@@ -230,51 +240,3 @@ impl AbilityImpl for StealHeartImpl {
         }
     }
 }
-
-fn mod_4_formula(user: &Combatant, target: &Combatant, k: f32) -> f32 {
-    let mut speed = user.speed() as i16;
-
-    //    1. If caster has Attack UP, then (Sp1 = [Sp0 * 4/3]), else Sp1 = Sp0
-    if user.attack_up() {
-        speed = (speed * 4) / 3;
-    }
-
-    //    2. If caster has Martial Arts, then (Sp2 = [Sp1 * 3/2]), else Sp2 = Sp1
-    if user.martial_arts() {
-        speed = (speed * 3) / 2;
-    }
-    //    3. If target has Defense UP, then (Sp3 = [Sp2 * 2/3]), else Sp3 = Sp2
-    if target.defense_up() {
-        speed = (speed * 2) / 3;
-    }
-    //    4. If target has Protect, then (Sp4 = [Sp3 * 2/3]), else Sp4 = Sp3
-    if target.protect() {
-        speed = (speed * 2) / 3;
-    }
-    //    5. If target is Charging, then (Sp5 = [Sp4 * 3/2]), else Sp5 = Sp4
-    if target.charging() {
-        speed = (speed * 3) / 2;
-    }
-    //    6. If target is Sleeping, then (Sp6 = [Sp5 * 3/2]), else Sp6 = Sp5
-    if target.sleep() {
-        speed = (speed * 3) / 2;
-    }
-    //    7. If target is a Frog and/or Chicken, then (Sp7 = [Sp6 * 3/2]), else
-    //       Sp7 = Sp6
-    if target.frog() || target.chicken() {
-        speed = (speed * 3) / 2;
-    }
-
-    //    8. Calculate Z (zodiac addend):
-    //         If compatibility is 'Good', then Z = [Sp7 / 4] + [K / 4]
-    //         ElseIf compatibility is 'Bad', then Z = -[Sp7 / 4] - [K / 4]
-    //         ElseIf compatibility is 'Best', then Z = [Sp7 / 2] + [K / 2]
-    //         ElseIf compatibility is 'Worst', then Z = -[Sp7 / 2] - [K / 2]
-    //         Else, Z = 0
-    //    9. Success% = (Sp7 + K + Z)
-    //   10. Consider physical evasion multipliers, if applicable.
-    let mut chance = (speed as f32 / 100.0) + k;
-    chance *= user.zodiac_compatibility(target);
-
-    chance
-}
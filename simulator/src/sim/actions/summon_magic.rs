@@ -1,12 +1,41 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK};
-use crate::sim::common::{mod_6_formula, ElementalDamageSpellImpl};
+use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK};
+use crate::sim::common::{CureSpellImpl, DemiImpl, ElementalDamageSpellImpl};
+use crate::sim::formula::mod_6_formula;
 use crate::sim::{
     Combatant, CombatantId, Condition, Element, Event, Simulation, Source, HITS_ALLIES_ONLY,
     HITS_FOES_ONLY, NOT_ALIVE_OK, PETRIFY_OK, SILENCEABLE,
 };
 
+// None of the summons can be used through Math Skill, so nothing here is CAN_BE_CALCULATED.
+
 pub const SUMMON_MAGIC_ABILITES: &[Ability] = &[
-    // TODO: Moogle: 4 range, 2 AoE, 3 CT, 12 MP. Hit: Faith(MA + 145)%. Effect: Cancel Petrify, Darkness, Confusion, Silence, Blood Suck, Berserk, Frog, Poison, Sleep, Don't Move, Don't Act; If successful, Heal (10)%
+    // Moogle: 4 range, 2 AoE, 3 CT, 12 MP. Hit: Faith(MA + 145)%. Effect: Cancel Petrify, Darkness, Confusion, Silence, Blood Suck, Berserk, Frog, Poison, Sleep, Don't Move, Don't Act; If successful, Heal (10)%
+    Ability {
+        name: "Moogle",
+        flags: ALLY_OK | PETRIFY_OK | SILENCEABLE | HITS_ALLIES_ONLY,
+        mp_cost: 12,
+        aoe: AoE::Diamond(2, Some(2)),
+        implementation: &CureSummonImpl {
+            base_chance: 145,
+            heal_percent: 0.10,
+            heal_needs_cure: false,
+            conditions: &[
+                Condition::Petrify,
+                Condition::Darkness,
+                Condition::Confusion,
+                Condition::Silence,
+                Condition::BloodSuck,
+                Condition::Berserk,
+                Condition::Frog,
+                Condition::Poison,
+                Condition::Sleep,
+                Condition::DontMove,
+                Condition::DontAct,
+            ],
+            range: 4,
+            ctr: 3,
+        },
+    },
     // Shiva: 4 range, 2 AoE, 4 CT, 24 MP. Element: Ice. Effect: Damage Faith(MA * 24).
     Ability {
         name: "Shiva",
@@ -70,9 +99,10 @@ pub const SUMMON_MAGIC_ABILITES: &[Ability] = &[
         flags: ALLY_OK | NOT_ALIVE_OK | PETRIFY_OK | SILENCEABLE | HITS_ALLIES_ONLY,
         mp_cost: 30,
         aoe: AoE::Diamond(2, Some(2)),
-        implementation: &CarbunkleImpl {
+        implementation: &CureSummonImpl {
             base_chance: 140,
             heal_percent: 0.25,
+            heal_needs_cure: true,
             conditions: &[
                 Condition::Undead,
                 Condition::Petrify,
@@ -127,37 +157,67 @@ pub const SUMMON_MAGIC_ABILITES: &[Ability] = &[
         },
     },
     // Salamander: 4 range, 2 AoE, 9 CT, 44 MP. Element: Fire. Effect: Damage Faith(MA * 36); Chance to add Oil.
-    // TODO: Chance to add Oil.
     Ability {
         name: "Salamander",
         flags: FOE_OK | SILENCEABLE | HITS_FOES_ONLY,
         mp_cost: 44,
         aoe: AoE::Diamond(2, Some(2)),
-        implementation: &ElementalDamageSpellImpl {
-            element: Element::Fire,
-            q: 36,
-            ctr: Some(9),
-            range: 4,
-            evadable: false,
+        implementation: &DamageWithRiderImpl {
+            damage: ElementalDamageSpellImpl {
+                element: Element::Fire,
+                q: 36,
+                ctr: Some(9),
+                range: 4,
+                evadable: false,
+            },
+            rider: Condition::Oil,
+            rider_chance: 0.20,
         },
     },
     // Silf: 4 range, 2 AoE, 7 CT, 36 MP. Element: Wind. Effect: Damage Faith(MA * 30); Chance to add Silence.
-    // TODO: Chance to add Silence.
     Ability {
         name: "Silf",
         flags: FOE_OK | SILENCEABLE | HITS_FOES_ONLY,
         mp_cost: 36,
         aoe: AoE::Diamond(2, Some(2)),
-        implementation: &ElementalDamageSpellImpl {
-            element: Element::Wind,
-            q: 30,
-            ctr: Some(7),
+        implementation: &DamageWithRiderImpl {
+            damage: ElementalDamageSpellImpl {
+                element: Element::Wind,
+                q: 30,
+                ctr: Some(7),
+                range: 4,
+                evadable: false,
+            },
+            rider: Condition::Silence,
+            rider_chance: 0.20,
+        },
+    },
+    // Fairy: 4 range, 2 AoE, 6 CT, 28 MP. Effect: Heal Faith(MA * 24).
+    Ability {
+        name: "Fairy",
+        flags: ALLY_OK | SILENCEABLE | HITS_ALLIES_ONLY,
+        mp_cost: 28,
+        aoe: AoE::Diamond(2, Some(2)),
+        implementation: &CureSpellImpl {
+            q: 24,
+            ctr: Some(6),
             range: 4,
-            evadable: false,
         },
     },
-    // TODO: Fairy: 4 range, 2 AoE, 6 CT, 28 MP. Effect: Heal Faith(MA * 24).
-    // TODO: Lich: 4 range, 2 AoE, 9 CT, 40 MP. Element: Dark. Hit: Faith(MA + 160)%. Effect: Damage (60)%.
+    // Lich: 4 range, 2 AoE, 9 CT, 40 MP. Element: Dark. Hit: Faith(MA + 160)%. Effect: Damage (60)%.
+    Ability {
+        name: "Lich",
+        flags: FOE_OK | SILENCEABLE | HITS_FOES_ONLY,
+        mp_cost: 40,
+        aoe: AoE::Diamond(2, Some(2)),
+        implementation: &DemiImpl {
+            element: Element::Dark,
+            base_chance: 160,
+            hp_percent: 0.60,
+            range: 4,
+            ctr: Some(9),
+        },
+    },
     // Cyclops: 4 range, 2 AoE, 11 CT, 62 MP. Effect: Damage Faith(MA * 50).
     Ability {
         name: "Cyclops",
@@ -188,15 +248,21 @@ pub const SUMMON_MAGIC_ABILITES: &[Ability] = &[
     },
 ];
 
-struct CarbunkleImpl {
+/// Moogle & Carbunkle: cancel a list of conditions and heal, either on every hit or only when
+/// that cured anything.
+struct CureSummonImpl {
     base_chance: i16,
     heal_percent: f32,
+    heal_needs_cure: bool,
     conditions: &'static [Condition],
     range: u8,
     ctr: u8,
 }
 
-impl AbilityImpl for CarbunkleImpl {
+impl AbilityImpl for CureSummonImpl {
+    fn formula(&self) -> Formula {
+        Formula::MagicHit
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -205,6 +271,17 @@ impl AbilityImpl for CarbunkleImpl {
         _user: &Combatant<'a>,
         target: &Combatant<'a>,
     ) {
+        let dead = ability.flags & NOT_ALIVE_OK != 0 && target.dead();
+        let hurt = !self.heal_needs_cure && !target.dead() && target.hp() < target.max_hp();
+        if !dead
+            && !hurt
+            && !self
+                .conditions
+                .iter()
+                .any(|cond| target.has_condition(*cond))
+        {
+            return;
+        }
         actions.push(Action::new(
             ability,
             self.range,
@@ -221,13 +298,252 @@ impl AbilityImpl for CarbunkleImpl {
             return;
         }
 
-        // TODO: Carbunkle heals if it cures any status condition
-        if target.dead() {
-            let heal_amount = (target.max_hp() as f32 * self.heal_percent) as i16;
+        let heal_amount = (target.max_hp() as f32 * self.heal_percent) as i16;
+        // Healing the dead is what brings them back, so that has to come first.
+        let was_dead = target.dead();
+        let mut cured = was_dead;
+        if was_dead {
             sim.change_target_hp(target_id, -heal_amount, Source::Ability);
         }
         for condition in self.conditions.iter() {
+            let had_condition = sim.combatant(target_id).has_condition(*condition);
             sim.cancel_condition(target_id, *condition, Source::Ability);
+            cured |= had_condition && !sim.combatant(target_id).has_condition(*condition);
+        }
+        if (cured || !self.heal_needs_cure) && !was_dead {
+            sim.change_target_hp(target_id, -heal_amount, Source::Ability);
+        }
+    }
+}
+
+/// A damaging summon that also has a chance to add a condition to whoever it hurt.
+struct DamageWithRiderImpl {
+    damage: ElementalDamageSpellImpl,
+    rider: Condition,
+    rider_chance: f32,
+}
+
+impl AbilityImpl for DamageWithRiderImpl {
+    fn formula(&self) -> Formula {
+        Formula::Magic
+    }
+    fn consider<'a>(
+        &self,
+        actions: &mut Vec<Action<'a>>,
+        ability: &'a Ability<'a>,
+        sim: &Simulation<'a>,
+        user: &Combatant<'a>,
+        target: &Combatant<'a>,
+    ) {
+        self.damage.consider(actions, ability, sim, user, target);
+    }
+    fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
+        if !self.damage.perform_hit(sim, user_id, target_id) {
+            return;
+        }
+        if sim.roll_auto_fail() < self.rider_chance {
+            sim.add_condition(target_id, self.rider, Source::Ability);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::rust::MatchUp;
+    use crate::dto::test_support::{flat_arena, sim_with};
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    /// Unit 0 summons `name` onto `target`, everyone at 100 faith so that the hit rolls always
+    /// land. Returns whatever `check` makes of the simulation afterwards.
+    fn summon<R>(
+        name: &str,
+        target: u8,
+        seed: u64,
+        prepare: impl FnOnce(&mut Simulation),
+        check: impl FnOnce(&Simulation) -> R,
+    ) -> R {
+        let tweak = |match_up: &mut MatchUp| {
+            for combatant in match_up
+                .left
+                .combatants
+                .iter_mut()
+                .chain(match_up.right.combatants.iter_mut())
+            {
+                combatant.faith = 100;
+            }
+            match_up.left.combatants[0].action_skill = "Summon Magic".to_owned();
+            match_up.left.combatants[0].all_abilities = vec![name.to_owned()];
+        };
+        sim_with(&flat_arena(), tweak, |sim| {
+            *sim.rng.borrow_mut() = SmallRng::seed_from_u64(seed);
+            prepare(sim);
+            let info = sim.combatant(CombatantId::new(0)).info;
+            let ability = info
+                .abilities
+                .iter()
+                .find(|ability| ability.name == name)
+                .unwrap();
+            ability
+                .implementation
+                .perform(sim, CombatantId::new(0), CombatantId::new(target));
+            check(sim)
+        })
+    }
+
+    fn hurt_ally(sim: &mut Simulation, condition: Option<Condition>) {
+        let ally = sim.combatant_mut(CombatantId::new(1));
+        ally.set_hp_within_bounds(50);
+        if let Some(condition) = condition {
+            ally.add_condition(condition);
+        }
+    }
+
+    fn hp(sim: &Simulation, target: u8) -> i16 {
+        sim.combatant(CombatantId::new(target)).hp()
+    }
+
+    #[test]
+    pub fn carbunkle_heals_when_it_cures() {
+        let (hp, charmed) = summon(
+            "Carbunkle",
+            1,
+            0,
+            |sim| hurt_ally(sim, Some(Condition::Charm)),
+            |sim| (hp(sim, 1), sim.combatant(CombatantId::new(1)).charm()),
+        );
+        assert_eq!(hp, 50 + 150 / 4);
+        assert!(!charmed);
+    }
+
+    #[test]
+    pub fn carbunkle_does_not_heal_without_a_cure() {
+        let hp = summon(
+            "Carbunkle",
+            1,
+            0,
+            |sim| hurt_ally(sim, None),
+            |sim| hp(sim, 1),
+        );
+        assert_eq!(hp, 50);
+    }
+
+    #[test]
+    pub fn moogle_heals_when_it_cures() {
+        let (hp, poisoned) = summon(
+            "Moogle",
+            1,
+            0,
+            |sim| hurt_ally(sim, Some(Condition::Poison)),
+            |sim| (hp(sim, 1), sim.combatant(CombatantId::new(1)).poison()),
+        );
+        assert_eq!(hp, 50 + 150 / 10);
+        assert!(!poisoned);
+    }
+
+    #[test]
+    pub fn fairy_heals() {
+        let hp = summon("Fairy", 1, 0, |sim| hurt_ally(sim, None), |sim| hp(sim, 1));
+        assert!(hp > 50);
+    }
+
+    #[test]
+    pub fn lich_takes_a_percentage() {
+        let hp = summon("Lich", 4, 0, |_| {}, |sim| hp(sim, 4));
+        assert_eq!(hp, 150 - (150.0 * 0.60) as i16);
+    }
+
+    #[test]
+    pub fn salamander_sometimes_adds_oil() {
+        let oiled: Vec<_> = (0..40)
+            .map(|seed| {
+                summon(
+                    "Salamander",
+                    4,
+                    seed,
+                    // Low enough faith that the target survives the hit.
+                    |sim| sim.combatant_mut(CombatantId::new(4)).raw_faith = 10,
+                    |sim| sim.combatant(CombatantId::new(4)).oil(),
+                )
+            })
+            .collect();
+        assert!(oiled.iter().any(|oiled| *oiled));
+        assert!(oiled.iter().any(|oiled| !*oiled));
+    }
+
+    #[test]
+    pub fn moogle_heals_the_merely_hurt() {
+        let hp = summon("Moogle", 1, 0, |sim| hurt_ally(sim, None), |sim| hp(sim, 1));
+        assert_eq!(hp, 50 + 150 / 10);
+    }
+
+    /// How many actions `name` comes up with for unit 0 on its ally, unit 1.
+    fn considered_on_ally(name: &str, prepare: impl FnOnce(&mut Simulation)) -> usize {
+        summon(name, 1, 0, prepare, |sim| {
+            let user = sim.combatant(CombatantId::new(0));
+            let ability = user
+                .info
+                .abilities
+                .iter()
+                .find(|ability| ability.name == name)
+                .unwrap();
+            let mut actions = vec![];
+            ability.implementation.consider(
+                &mut actions,
+                ability,
+                sim,
+                user,
+                sim.combatant(CombatantId::new(1)),
+            );
+            actions.len()
+        })
+    }
+
+    #[test]
+    pub fn moogle_is_considered_on_hurt_allies() {
+        // The summon goes off before considering, so it has to miss to leave the ally hurt.
+        let hurt = |sim: &mut Simulation| {
+            sim.combatant_mut(CombatantId::new(0)).raw_faith = 0;
+            hurt_ally(sim, None);
+        };
+        assert_eq!(considered_on_ally("Moogle", hurt), 1);
+        assert_eq!(considered_on_ally("Moogle", |_| {}), 0);
+        assert_eq!(considered_on_ally("Carbunkle", hurt), 0);
+    }
+
+    #[test]
+    pub fn riders_need_the_hit_to_land() {
+        for seed in 0..40 {
+            let oiled = summon(
+                "Salamander",
+                4,
+                seed,
+                // No faith, no damage.
+                |sim| sim.combatant_mut(CombatantId::new(4)).raw_faith = 0,
+                |sim| (hp(sim, 4), sim.combatant(CombatantId::new(4)).oil()),
+            );
+            assert_eq!(oiled, (150, false));
         }
     }
+
+    #[test]
+    pub fn salamander_oils_as_pinned() {
+        // Fixed seed regression cases standing in for the test harness, which has no summoner
+        // match ups to replay here.
+        let oiled: Vec<_> = (0..12)
+            .filter(|seed| {
+                let (hp, oiled) = summon(
+                    "Salamander",
+                    4,
+                    *seed,
+                    |sim| sim.combatant_mut(CombatantId::new(4)).raw_faith = 10,
+                    |sim| (hp(sim, 4), sim.combatant(CombatantId::new(4)).oil()),
+                );
+                assert_eq!(hp, 104);
+                oiled
+            })
+            .collect();
+        assert_eq!(oiled, vec![6, 10]);
+    }
 }
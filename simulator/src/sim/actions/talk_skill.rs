@@ -1,4 +1,5 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, FOE_OK};
+use crate::sim::actions::formula::{mod_6_formula, zodiac_hit};
+use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, Formula, FOE_OK};
 
 use crate::sim::{
     instant_aoe_consider, Combatant, CombatantId, Condition, Element, Event, Simulation, Source,
@@ -149,26 +150,28 @@ pub struct ConditionTalkSkillImpl {
 }
 
 impl AbilityImpl for ConditionTalkSkillImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
         ability: &'a Ability<'a>,
-        _sim: &Simulation<'a>,
-        _user: &Combatant<'a>,
+        sim: &Simulation<'a>,
+        user: &Combatant<'a>,
         target: &Combatant<'a>,
     ) {
         if target.earplug() {
             return;
         }
-        instant_aoe_consider(self.range, actions, ability, target)
+        instant_aoe_consider(sim, user, self.range, actions, ability, target)
     }
 
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             let index = sim.roll_inclusive(1, self.add_conditions.len() as i16) - 1;
@@ -184,6 +187,9 @@ pub struct PersuadeTalkSkillImpl {
 }
 
 impl AbilityImpl for PersuadeTalkSkillImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -200,9 +206,8 @@ impl AbilityImpl for PersuadeTalkSkillImpl {
 
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
         let user = sim.combatant(user_id);
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
         let target = sim.combatant(target_id);
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             let target = sim.combatant_mut(target_id);
@@ -217,6 +222,9 @@ pub struct RehabilitateTalkSkillImpl {
 }
 
 impl AbilityImpl for RehabilitateTalkSkillImpl {
+    fn formula(&self) -> Formula {
+        Formula::Fixed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -246,6 +254,9 @@ pub struct BraveFaithTalkSkillImpl {
 }
 
 impl AbilityImpl for BraveFaithTalkSkillImpl {
+    fn formula(&self) -> Formula {
+        Formula::Stat
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -265,8 +276,7 @@ impl AbilityImpl for BraveFaithTalkSkillImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.zodiac_compatibility(target);
+        let chance = zodiac_hit(user, target, user.ma(), self.base_chance);
 
         if sim.roll_auto_succeed() < chance {
             sim.change_unit_brave(target_id, self.brave_mod, Source::Ability);
@@ -281,6 +291,9 @@ pub struct StealStatusImpl {
 }
 
 impl AbilityImpl for StealStatusImpl {
+    fn formula(&self) -> Formula {
+        Formula::MagicHit
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -296,10 +309,7 @@ impl AbilityImpl for StealStatusImpl {
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
 
-        let mut chance = (user.ma() + self.base_chance) as f32 / 100.0;
-        chance *= user.faith_percent();
-        chance *= target.faith_percent();
-        chance *= user.zodiac_compatibility(target);
+        let chance = mod_6_formula(user, target, Element::None, self.base_chance, false);
 
         if sim.roll_auto_succeed() < chance {
             for condition in ALL_CONDITIONS.borrow().iter() {
@@ -1,118 +1,124 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK};
+use crate::dto::rust::Equipment;
+use crate::sim::actions::formula::zodiac;
+use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK};
 use crate::sim::{
-    Combatant, CombatantId, Condition, Element, Simulation, Source, TARGET_NOT_SELF,
-    TARGET_SELF_ONLY, TRIGGERS_HAMEDO,
+    Combatant, CombatantId, Element, Simulation, Source, WeaponType, TARGET_NOT_SELF,
+    TRIGGERS_HAMEDO,
 };
 
+// Shuriken & bombs aren't equipment, so they're listed out here. Everything else is thrown
+// from the weapons in the patch of the matching type, see CombatantInfo::throwables.
 pub const THROW_ABILITIES: &[Ability] = &[
     // Knife: movement range, 0 AoE. Effect: Throw Knife Damage (Speed * ThrownWP).
-    // // Air Knife: 10 WP, 1 range, 5% evade, Knife. Element: Wind.
-    // // Zorlin Shape: 12 WP, 1 range, 10% evade, Knife. Effect: Chance to Add Sleep.
     Ability {
         name: "Knife",
         flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
         mp_cost: 0,
         aoe: AoE::None,
         implementation: &ThrowImpl {
-            items: &[
-                ThrowableItem {
-                    wp: 10,
-                    element: Element::Wind,
-                    name: "Air Knife",
-                },
-                ThrowableItem {
-                    wp: 12,
-                    element: Element::None,
-                    name: "Zorlin Shape",
-                },
-            ],
+            weapon_type: Some(WeaponType::Knife),
+            items: &[],
         },
     },
     // Sword: movement range, 0 AoE. Effect: Throw Sword Damage (Speed * ThrownWP).
-    // // Coral Sword: 10 WP, 1 range, 5% evade, Sword. Element: Lightning.
-    // // Ice Brand: 13 WP, 1 range, 10% evade, Sword. Element: Ice. Effect: Chance to cast Ice 2.
-    // // Materia Blade: 15 WP, 1 range, 10% evade, Sword. Effect: Strengthen Wind, Earth, Water.
     Ability {
         name: "Sword",
         flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
         mp_cost: 0,
         aoe: AoE::None,
         implementation: &ThrowImpl {
-            items: &[
-                ThrowableItem {
-                    wp: 10,
-                    element: Element::Lightning,
-                    name: "Coral Sword",
-                },
-                ThrowableItem {
-                    wp: 13,
-                    element: Element::Ice,
-                    name: "Ice Brand",
-                },
-                ThrowableItem {
-                    wp: 15,
-                    element: Element::None,
-                    name: "Materia Blade",
-                },
-            ],
+            weapon_type: Some(WeaponType::Sword),
+            items: &[],
+        },
+    },
+    // Hammer: movement range, 0 AoE. Effect: Throw Flail Damage (Speed * ThrownWP).
+    Ability {
+        name: "Hammer",
+        flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
+        mp_cost: 0,
+        aoe: AoE::None,
+        implementation: &ThrowImpl {
+            weapon_type: Some(WeaponType::Flail),
+            items: &[],
+        },
+    },
+    // Staff: movement range, 0 AoE. Effect: Throw Staff Damage (Speed * ThrownWP).
+    Ability {
+        name: "Staff",
+        flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
+        mp_cost: 0,
+        aoe: AoE::None,
+        implementation: &ThrowImpl {
+            weapon_type: Some(WeaponType::Staff),
+            items: &[],
         },
     },
-    // TODO: Hammer: movement range, 0 AoE. Effect: Throw Flail Damage (Speed * ThrownWP).
-    // TODO: Staff: movement range, 0 AoE. Effect: Throw Staff Damage (Speed * ThrownWP).
     // Ninja Sword: movement range, 0 AoE. Effect: Throw Ninja Blade Damage (Speed * ThrownWP).
-    // // Koga Knife: 17 WP, 1 range, 5% evade, Ninja Blade.
     Ability {
         name: "Ninja Sword",
         flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
         mp_cost: 0,
         aoe: AoE::None,
         implementation: &ThrowImpl {
-            items: &[ThrowableItem {
-                wp: 17,
-                element: Element::Water,
-                name: "Koga Knife",
-            }],
+            weapon_type: Some(WeaponType::NinjaSword),
+            items: &[],
+        },
+    },
+    // Axe: movement range, 0 AoE. Effect: Throw Axe Damage (Speed * ThrownWP).
+    Ability {
+        name: "Axe",
+        flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
+        mp_cost: 0,
+        aoe: AoE::None,
+        implementation: &ThrowImpl {
+            weapon_type: Some(WeaponType::Axe),
+            items: &[],
         },
     },
-    // TODO: Axe: movement range, 0 AoE. Effect: Throw Axe Damage (Speed * ThrownWP).
     // Spear: movement range, 0 AoE. Effect: Throw Spear Damage (Speed * ThrownWP).
-    // // Obelisk: 12 WP, 2 range, 10% evade, Spear. Element: Water.
-    // // Holy Lance: 14 WP, 2 range, 10% evade, Spear. Element: Holy. Effect: Chance to cast Holy.
-    // // Dragon Whisker: 16 WP, 2 range, 10% evade, Spear. Effect: Permanent Shell.
-    // // Gungnir: 18 WP, 2 range, 10% evade, Spear. Element: Wind.
     Ability {
         name: "Spear",
         flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
         mp_cost: 0,
         aoe: AoE::None,
         implementation: &ThrowImpl {
-            items: &[
-                ThrowableItem {
-                    wp: 12,
-                    element: Element::Water,
-                    name: "Obelisk",
-                },
-                ThrowableItem {
-                    wp: 14,
-                    element: Element::Holy,
-                    name: "Holy Lance",
-                },
-                ThrowableItem {
-                    wp: 16,
-                    element: Element::None,
-                    name: "Dragon Whisker",
-                },
-                ThrowableItem {
-                    wp: 18,
-                    element: Element::Wind,
-                    name: "Gungnir",
-                },
-            ],
+            weapon_type: Some(WeaponType::Spear),
+            items: &[],
+        },
+    },
+    // Stick: movement range, 0 AoE. Effect: Throw Pole Damage (Speed * ThrownWP).
+    Ability {
+        name: "Stick",
+        flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
+        mp_cost: 0,
+        aoe: AoE::None,
+        implementation: &ThrowImpl {
+            weapon_type: Some(WeaponType::Pole),
+            items: &[],
+        },
+    },
+    // Wand: movement range, 0 AoE. Effect: Throw Rod Damage (Speed * ThrownWP).
+    Ability {
+        name: "Wand",
+        flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
+        mp_cost: 0,
+        aoe: AoE::None,
+        implementation: &ThrowImpl {
+            weapon_type: Some(WeaponType::Rod),
+            items: &[],
+        },
+    },
+    // Dictionary: movement range, 0 AoE. Effect: Throw Book Damage (Speed * ThrownWP).
+    Ability {
+        name: "Dictionary",
+        flags: ALLY_OK | FOE_OK | TARGET_NOT_SELF | TRIGGERS_HAMEDO,
+        mp_cost: 0,
+        aoe: AoE::None,
+        implementation: &ThrowImpl {
+            weapon_type: Some(WeaponType::Book),
+            items: &[],
         },
     },
-    // TODO: Stick: movement range, 0 AoE. Effect: Throw Pole Damage (Speed * ThrownWP).
-    // TODO: Wand: movement range, 0 AoE. Effect: Throw Rod Damage (Speed * ThrownWP).
-    // TODO: Dictionary: movement range, 0 AoE. Effect: Throw Book Damage (Speed * ThrownWP).
     // Shuriken: movement range, 0 AoE. Effect: Throw Shuriken Damage (Speed * ThrownWP).
     // // Shuriken: 5 WP, Shuriken.
     // // Magic Shuriken: 7 WP, Shuriken. Element: Ice.
@@ -123,6 +129,7 @@ pub const THROW_ABILITIES: &[Ability] = &[
         mp_cost: 0,
         aoe: AoE::None,
         implementation: &ThrowImpl {
+            weapon_type: None,
             items: &[
                 ThrowableItem {
                     wp: 5,
@@ -152,6 +159,7 @@ pub const THROW_ABILITIES: &[Ability] = &[
         mp_cost: 0,
         aoe: AoE::None,
         implementation: &ThrowImpl {
+            weapon_type: None,
             items: &[
                 ThrowableItem {
                     wp: 8,
@@ -179,11 +187,49 @@ struct ThrowableItem {
     name: &'static str,
 }
 
+/// One thing the user could throw, whether it came from the patch or the lists above.
+#[derive(Clone, Copy)]
+struct Thrown<'a> {
+    wp: i16,
+    element: Element,
+    weapon: Option<&'a Equipment>,
+    name: &'static str,
+}
+
 struct ThrowImpl {
+    weapon_type: Option<WeaponType>,
     items: &'static [ThrowableItem],
 }
 
+impl ThrowImpl {
+    /// Goes through the user's `CombatantInfo::throwables` of this type, or the items above.
+    fn thrown<'a, 'b>(&'b self, user: &'b Combatant<'a>) -> impl Iterator<Item = Thrown<'a>> + 'b {
+        let weapons = self.weapon_type.into_iter().flat_map(move |weapon_type| {
+            user.info
+                .throwables
+                .iter()
+                .filter(move |weapon| weapon.weapon_type == Some(weapon_type))
+                .map(|weapon| Thrown {
+                    wp: weapon.wp as i16,
+                    element: weapon.weapon_element.unwrap_or(Element::None),
+                    weapon: Some(*weapon),
+                    name: "",
+                })
+        });
+        let items = self.items.iter().map(|item| Thrown {
+            wp: item.wp,
+            element: item.element,
+            weapon: None,
+            name: item.name,
+        });
+        weapons.chain(items)
+    }
+}
+
 impl AbilityImpl for ThrowImpl {
+    fn formula(&self) -> Formula {
+        Formula::Speed
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -192,6 +238,9 @@ impl AbilityImpl for ThrowImpl {
         user: &Combatant<'a>,
         target: &Combatant<'a>,
     ) {
+        if self.thrown(user).next().is_none() {
+            return;
+        }
         actions.push(Action::new(ability, user.movement(), None, target.id()));
     }
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
@@ -203,36 +252,26 @@ impl AbilityImpl for ThrowImpl {
             return;
         }
 
-        if user.ally(target) {
-            let least_damage = self
-                .items
-                .iter()
-                .map(|item| (throw_formula(user, target, item.element, item.wp), item))
-                .min_by_key(|p| p.0);
-
-            if let Some(least_damage) = least_damage {
-                sim.change_target_hp(
-                    target_id,
-                    least_damage.0,
-                    Source::Constant(least_damage.1.name),
-                );
-                sim.try_countergrasp(user_id, target_id);
-            }
+        // Allies get the gentlest item, everyone else the strongest.
+        let damages = self
+            .thrown(user)
+            .map(|item| (throw_formula(user, target, item.element, item.wp), item));
+        let choice = if user.ally(target) {
+            damages.min_by_key(|p| p.0)
         } else {
-            let most_damage = self
-                .items
-                .iter()
-                .map(|item| (throw_formula(user, target, item.element, item.wp), item))
-                .max_by_key(|p| p.0);
+            damages.max_by_key(|p| p.0)
+        };
 
-            if let Some(most_damage) = most_damage {
-                sim.change_target_hp(
-                    target_id,
-                    most_damage.0,
-                    Source::Constant(most_damage.1.name),
-                );
-                sim.try_countergrasp(user_id, target_id);
+        if let Some((damage, item)) = choice {
+            let src = match item.weapon {
+                Some(_) => Source::Weapon(user_id, item.weapon),
+                None => Source::Constant(item.name),
+            };
+            sim.change_target_hp(target_id, damage, src);
+            if item.weapon.is_some() {
+                sim.weapon_chance_to_add_or_cancel_status(user_id, item.weapon, target_id);
             }
+            sim.try_countergrasp(user_id, target_id);
         }
     }
 }
@@ -265,13 +304,8 @@ fn throw_formula(user: &Combatant, target: &Combatant, element: Element, wp: i16
         speed = (speed * 3) / 2;
     }
 
-    //   6. Apply zodiac multipliers:
-    //            If compatibility is 'Good', then (Sp6 = Sp5 + [(Sp5)/4]))
-    //            ElseIf compatibility is 'Bad', then (Sp6 = Sp5 - [(Sp5)/4])
-    //            ElseIf compatibility is 'Best', then (Sp6 = Sp5 + [(Sp5)/2])
-    //            ElseIf compatibility is 'Worst', then (Sp6 = Sp5 - [(Sp5)/2])
-    //            Else Sp6 = Sp5
-    speed = (speed as f32 * user.zodiac_compatibility(target)) as i16;
+    //   6. Apply zodiac multipliers, see `zodiac`.
+    speed = zodiac(user, target, speed);
 
     //   7. damage0 = Sp6 * (ThrownWpnPwr)
     let mut damage = speed * wp;
@@ -300,3 +334,43 @@ fn throw_formula(user: &Combatant, target: &Combatant, element: Element, wp: i16
     //  11. The damage done by the THROW attack will be equal to damage3.
     damage
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::rust::MatchUp;
+    use crate::dto::test_support::{flat_arena, sim_with};
+
+    #[test]
+    pub fn throws_weapons_from_the_patch() {
+        let tweak = |match_up: &mut MatchUp| {
+            match_up.left.combatants[0].action_skill = "Throw".to_owned();
+            match_up.left.combatants[0].all_abilities =
+                vec!["Staff".to_owned(), "Knife".to_owned()];
+        };
+        sim_with(&flat_arena(), tweak, |sim| {
+            let user = sim.combatant(CombatantId::new(0));
+            let target = sim.combatant(CombatantId::new(4));
+            let throwables: Vec<_> = user.info.throwables.iter().map(|e| &e.name).collect();
+            assert_eq!(throwables, vec!["Oak Staff"]);
+            assert!(target.info.throwables.is_empty());
+
+            let considered = |name: &str| {
+                let ability = user
+                    .info
+                    .abilities
+                    .iter()
+                    .find(|ability| ability.name == name)
+                    .unwrap();
+                let mut actions = vec![];
+                ability
+                    .implementation
+                    .consider(&mut actions, ability, sim, user, target);
+                actions.len()
+            };
+            assert_eq!(considered("Staff"), 1);
+            // The sample patch doesn't have any knives to throw.
+            assert_eq!(considered("Knife"), 0);
+        });
+    }
+}
@@ -134,6 +134,7 @@ pub const TIME_MAGIC_ABILITIES: &[Ability] = &[
         mp_cost: 20,
         aoe: AoE::Diamond(1, Some(1)),
         implementation: &DemiImpl {
+            element: Element::None,
             base_chance: 205,
             hp_percent: 0.25,
             range: 5,
@@ -147,6 +148,7 @@ pub const TIME_MAGIC_ABILITIES: &[Ability] = &[
         mp_cost: 40,
         aoe: AoE::Diamond(1, Some(3)),
         implementation: &DemiImpl {
+            element: Element::None,
             base_chance: 165,
             hp_percent: 0.50,
             range: 5,
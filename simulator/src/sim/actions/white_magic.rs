@@ -1,11 +1,12 @@
 use crate::sim::actions::{
-    Ability, AbilityImpl, Action, AoE, ALLY_OK, CAN_BE_CALCULATED, CAN_BE_REFLECTED, FOE_OK,
-    NOT_ALIVE_OK, PETRIFY_OK, SILENCEABLE, UNDER_50_PERCENT_HP_ONLY,
+    Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, CAN_BE_CALCULATED, CAN_BE_REFLECTED,
+    FOE_OK, NOT_ALIVE_OK, PETRIFY_OK, SILENCEABLE, UNDER_50_PERCENT_HP_ONLY,
 };
 use crate::sim::common::{
     do_hp_heal, should_heal_ally, should_heal_foe, AddConditionSpellImpl, ConditionClearSpellImpl,
     CureSpellImpl, ElementalDamageSpellImpl,
 };
+use crate::sim::formula::mod_6_formula;
 use crate::sim::{Combatant, CombatantId, Condition, Element, Event, Simulation};
 
 pub const WHITE_MAGIC_ABILITIES: &[Ability] = &[
@@ -276,6 +277,9 @@ struct RaiseSpellImpl {
 }
 
 impl AbilityImpl for RaiseSpellImpl {
+    fn formula(&self) -> Formula {
+        Formula::MagicHit
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
@@ -301,16 +305,12 @@ impl AbilityImpl for RaiseSpellImpl {
         ));
     }
     fn perform<'a>(&self, sim: &mut Simulation<'a>, user_id: CombatantId, target_id: CombatantId) {
-        let mut success_chance = 1.0;
         let user = sim.combatant(user_id);
         let target = sim.combatant(target_id);
         if !target.dead() && !target.undead() {
             return;
         }
-        success_chance *= user.faith_percent();
-        success_chance *= target.faith_percent();
-        success_chance *= (user.ma() as f32 + self.base_chance as f32) / 100.0;
-        success_chance *= user.zodiac_compatibility(target);
+        let success_chance = mod_6_formula(user, target, Element::None, self.base_chance, false);
 
         if !(sim.roll_auto_succeed() < success_chance) {
             sim.log_event(Event::AbilityMissed(user_id, target_id));
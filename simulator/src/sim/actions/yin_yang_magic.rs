@@ -1,5 +1,6 @@
-use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, ALLY_OK, FOE_OK};
-use crate::sim::common::{mod_6_formula, AddConditionSpellImpl, ConditionClearSpellImpl};
+use crate::sim::actions::{Ability, AbilityImpl, Action, AoE, Formula, ALLY_OK, FOE_OK};
+use crate::sim::common::{AddConditionSpellImpl, ConditionClearSpellImpl};
+use crate::sim::formula::mod_6_formula;
 use crate::sim::{
     Combatant, CombatantId, Condition, Element, Event, Simulation, Source, CAN_BE_CALCULATED,
     CAN_BE_REFLECTED, SILENCEABLE,
@@ -220,6 +221,9 @@ struct AbsorbSpellImpl {
 }
 
 impl AbilityImpl for AbsorbSpellImpl {
+    fn formula(&self) -> Formula {
+        Formula::MagicHit
+    }
     fn consider<'a>(
         &self,
         actions: &mut Vec<Action<'a>>,
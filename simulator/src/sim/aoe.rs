@@ -0,0 +1,297 @@
+use crate::sim::{Arena, Panel};
+
+#[derive(Copy, Clone)]
+pub enum AoE {
+    None,
+    Diamond(u8, Option<u8>),
+    Line(Option<u8>),
+    TriLine(Option<u8>), // Like that Tiamat ability
+    Global,
+}
+
+impl AoE {
+    pub fn is_line(self) -> bool {
+        match self {
+            AoE::None => false,
+            AoE::Diamond(_size, _tolerance) => false,
+            AoE::Line(_tolerance) => true,
+            AoE::TriLine(_tolerance) => true,
+            AoE::Global => false,
+        }
+    }
+
+    /// Every panel hit when this is aimed from `user` at `target`, where `range` is how far
+    /// lines reach. Panels off the map are dropped, as are panels further up or down than the
+    /// tolerance allows from the centre of a diamond or from the user for lines. `height_of`
+    /// gives the height to compare, which should be that of whoever is standing there.
+    ///
+    /// Where there's a bridge both layers are hit, so long as they're within tolerance.
+    ///
+    /// Global abilities hit everyone rather than any panel in particular, so they have none.
+    pub fn panels(
+        self,
+        arena: &Arena,
+        user: Panel,
+        target: Panel,
+        range: u8,
+        height_of: impl Fn(Panel) -> f32,
+    ) -> Vec<Panel> {
+        let in_map = |panel: Panel| arena.panel_to_index(panel).is_some();
+        let within = |from: Panel, panel: Panel, tolerance: Option<u8>| match tolerance {
+            Some(tolerance) => (height_of(from) - height_of(panel)).abs() <= tolerance as f32,
+            None => true,
+        };
+
+        match self {
+            AoE::None => {
+                if in_map(target) {
+                    vec![target]
+                } else {
+                    vec![]
+                }
+            }
+            AoE::Diamond(size, tolerance) => {
+                if !in_map(target) {
+                    return vec![];
+                }
+                target
+                    .location()
+                    .diamond(size)
+                    .flat_map(|location| arena.panels_at(location))
+                    .filter(|panel| within(target, *panel, tolerance))
+                    .collect()
+            }
+            AoE::Line(tolerance) | AoE::TriLine(tolerance) => {
+                if !in_map(user) {
+                    return vec![];
+                }
+                let facing = user.facing_towards(target);
+                let directions = if let AoE::Line(_) = self {
+                    vec![facing]
+                } else {
+                    vec![facing, facing.rotate(3), facing.rotate(1)]
+                };
+                let mut panels = vec![];
+                for direction in directions {
+                    for i in 1..=range {
+                        let location = user.location() + direction.offset() * i as i16;
+                        if !arena.in_map(location) {
+                            break;
+                        }
+                        for panel in arena.panels_at(location) {
+                            if within(user, panel, tolerance) {
+                                panels.push(panel);
+                            }
+                        }
+                    }
+                }
+                panels
+            }
+            AoE::Global => vec![],
+        }
+    }
+
+    /// Whether `panel` would be caught in this when aimed from `user` at `target`, the same as
+    /// `panels` containing it but without building the list.
+    pub fn covers(
+        self,
+        arena: &Arena,
+        user: Panel,
+        target: Panel,
+        range: u8,
+        height_of: impl Fn(Panel) -> f32,
+        panel: Panel,
+    ) -> bool {
+        let in_map = |panel: Panel| arena.panel_to_index(panel).is_some();
+        let within = |from: Panel, tolerance: Option<u8>| match tolerance {
+            Some(tolerance) => (height_of(from) - height_of(panel)).abs() <= tolerance as f32,
+            None => true,
+        };
+
+        match self {
+            AoE::None => in_map(target) && panel == target,
+            AoE::Diamond(size, tolerance) => {
+                in_map(target)
+                    && arena.has_panel(panel)
+                    && target.distance(panel) <= size as i16
+                    && within(target, tolerance)
+            }
+            AoE::Line(tolerance) | AoE::TriLine(tolerance) => {
+                let distance = user.distance(panel);
+                if !in_map(user)
+                    || !arena.has_panel(panel)
+                    || !user.lined_up(panel)
+                    || distance == 0
+                    || distance > range as i16
+                {
+                    return false;
+                }
+                let facing = user.facing_towards(target);
+                let direction = user.facing_towards(panel);
+                let aimed = match self {
+                    AoE::Line(_) => direction == facing,
+                    _ => direction != facing.opposite(),
+                };
+                aimed && within(user, tolerance)
+            }
+            AoE::Global => true,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::rust::Tile;
+    use crate::dto::test_support::flat_tile;
+
+    fn tile_at(height: u8) -> Tile {
+        Tile {
+            height,
+            ..flat_tile()
+        }
+    }
+
+    // A 5x5 field at height 2, with a height 6 pillar at (3, 2) and a pit at (2, 3).
+    fn make_pillar_field() -> Arena {
+        let mut lower = vec![tile_at(2); 25];
+        lower[2 * 5 + 3] = tile_at(6);
+        lower[3 * 5 + 2] = tile_at(0);
+        Arena::new(lower, vec![tile_at(0); 25], 5, 5, vec![])
+    }
+
+    fn tile_height_of(arena: &Arena) -> impl Fn(Panel) -> f32 + '_ {
+        move |panel| arena.tile_height(panel)
+    }
+
+    #[test]
+    pub fn diamond_drops_panels_out_of_tolerance() {
+        let arena = make_pillar_field();
+        let center = Panel::coords(2, 2, false);
+        let panels =
+            AoE::Diamond(1, Some(2)).panels(&arena, center, center, 0, tile_height_of(&arena));
+        assert_eq!(
+            panels.len(),
+            4,
+            "the pillar is 4 up, the pit is only 2 down"
+        );
+        assert!(!panels.contains(&Panel::coords(3, 2, false)));
+        assert!(panels.contains(&Panel::coords(2, 3, false)));
+
+        let untolerant =
+            AoE::Diamond(1, None).panels(&arena, center, center, 0, tile_height_of(&arena));
+        assert_eq!(untolerant.len(), 5);
+    }
+
+    #[test]
+    pub fn diamond_stops_at_the_edge() {
+        let arena = make_pillar_field();
+        let corner = Panel::coords(0, 0, false);
+        let panels =
+            AoE::Diamond(1, None).panels(&arena, corner, corner, 0, tile_height_of(&arena));
+        assert_eq!(panels.len(), 3);
+    }
+
+    #[test]
+    pub fn unit_height_counts_for_tolerance() {
+        let arena = make_pillar_field();
+        let center = Panel::coords(2, 2, false);
+        let floater = Panel::coords(1, 2, false);
+        // Someone floating a long way up off an otherwise level panel.
+        let height_of = |panel: Panel| {
+            if panel == floater {
+                arena.tile_height(panel) + 3.0
+            } else {
+                arena.tile_height(panel)
+            }
+        };
+        let panels = AoE::Diamond(1, Some(2)).panels(&arena, center, center, 0, height_of);
+        assert!(!panels.contains(&floater));
+    }
+
+    #[test]
+    pub fn lines_respect_tolerance_and_bounds() {
+        let arena = make_pillar_field();
+        let user = Panel::coords(1, 2, false);
+        let east = Panel::coords(2, 2, false);
+        let panels = AoE::Line(Some(2)).panels(&arena, user, east, 8, tile_height_of(&arena));
+        assert_eq!(
+            panels,
+            vec![Panel::coords(2, 2, false), Panel::coords(4, 2, false)]
+        );
+    }
+
+    #[test]
+    pub fn tri_lines_fan_out_from_the_user() {
+        let arena = make_pillar_field();
+        let user = Panel::coords(2, 1, false);
+        let south = Panel::coords(2, 2, false);
+        let panels = AoE::TriLine(Some(2)).panels(&arena, user, south, 2, tile_height_of(&arena));
+        // Forward to (2, 3) in the pit, then two to each side, with (0, 1) still on the map.
+        assert_eq!(
+            panels,
+            vec![
+                Panel::coords(2, 2, false),
+                Panel::coords(2, 3, false),
+                Panel::coords(3, 1, false),
+                Panel::coords(4, 1, false),
+                Panel::coords(1, 1, false),
+                Panel::coords(0, 1, false),
+            ]
+        );
+        let near_edge = Panel::coords(0, 0, false);
+        let panels = AoE::TriLine(None).panels(
+            &arena,
+            near_edge,
+            Panel::coords(0, 1, false),
+            2,
+            tile_height_of(&arena),
+        );
+        assert_eq!(panels.len(), 4);
+    }
+
+    #[test]
+    pub fn global_covers_everywhere() {
+        let arena = make_pillar_field();
+        let anywhere = Panel::coords(4, 4, false);
+        assert!(AoE::Global.covers(
+            &arena,
+            anywhere,
+            anywhere,
+            0,
+            tile_height_of(&arena),
+            Panel::coords(0, 0, false)
+        ));
+    }
+
+    #[test]
+    pub fn covers_agrees_with_panels() {
+        use crate::sim::pathfinder::tests::make_bridge_map;
+
+        for arena in &[make_pillar_field(), make_bridge_map()] {
+            let every_panel: Vec<_> = (0..arena.height)
+                .flat_map(|y| (0..arena.width).map(move |x| (x, y)))
+                .flat_map(|(x, y)| vec![Panel::coords(x, y, false), Panel::coords(x, y, true)])
+                .collect();
+            for aoe in &[
+                AoE::None,
+                AoE::Diamond(1, Some(2)),
+                AoE::Diamond(2, None),
+                AoE::Line(Some(2)),
+                AoE::TriLine(None),
+            ] {
+                for user in &every_panel {
+                    for target in &every_panel {
+                        let panels = aoe.panels(arena, *user, *target, 3, tile_height_of(arena));
+                        for panel in &every_panel {
+                            assert_eq!(
+                                aoe.covers(arena, *user, *target, 3, tile_height_of(arena), *panel),
+                                panels.contains(panel),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,25 +1,120 @@
 use crate::dto::rust;
 use crate::dto::rust::{StartingLocation, Tile};
-use crate::sim::{Location, Panel};
+use crate::sim::{tile_height, tile_height_from_direction, Facing, Location, Panel, OFFSETS};
 
+/// A panel one step away along with how far up or down it is to step there.
+pub type Step = (Panel, u8);
+
+/// An arena along with tables worked out once up front, so that every simulation run on it
+/// (across threads too) can share them rather than recalculating heights as it goes.
 #[derive(Clone)]
 pub struct Arena {
-    pub lower: Vec<Tile>,
-    pub upper: Vec<Tile>,
+    lower: Vec<Tile>,
+    upper: Vec<Tile>,
     pub width: u8,
     pub height: u8,
     pub starting_locations: Vec<StartingLocation>,
+    heights: Vec<f32>,
+    directional_heights: Vec<[u8; 4]>,
+    upper_exists: Vec<bool>,
+    /// Per panel & direction (in `OFFSETS` order), the lower and then upper panel one step
+    /// over, when they exist.
+    steps: Vec<[[Option<Step>; 2]; 4]>,
+    /// Per panel & direction, every panel on the same layer in a straight line out to the
+    /// edge of the map, with the height to jump between the edges facing each other.
+    jumps: Vec<[Vec<Step>; 4]>,
 }
 
 impl Arena {
-    pub fn from_dto(arena: rust::Arena) -> Arena {
-        Arena {
-            lower: arena.lower,
-            upper: arena.upper,
-            width: arena.width,
-            height: arena.height,
-            starting_locations: arena.starting_locations,
+    pub fn new(
+        lower: Vec<Tile>,
+        upper: Vec<Tile>,
+        width: u8,
+        height: u8,
+        starting_locations: Vec<StartingLocation>,
+    ) -> Arena {
+        let mut arena = Arena {
+            lower,
+            upper,
+            width,
+            height,
+            starting_locations,
+            heights: vec![],
+            directional_heights: vec![],
+            upper_exists: vec![],
+            steps: vec![],
+            jumps: vec![],
+        };
+        // Lower layer first, then upper, see `layer_index`.
+        for tile in arena.lower.iter().chain(arena.upper.iter()) {
+            arena.heights.push(tile_height(tile));
+            arena.directional_heights.push([
+                tile_height_from_direction(tile, Facing::North),
+                tile_height_from_direction(tile, Facing::East),
+                tile_height_from_direction(tile, Facing::South),
+                tile_height_from_direction(tile, Facing::West),
+            ]);
+        }
+        for idx in 0..arena.lower.len() {
+            let exists = arena.upper[idx].height > arena.lower[idx].height;
+            arena.upper_exists.push(exists);
+        }
+        for layer in &[false, true] {
+            for y in 0..height {
+                for x in 0..width {
+                    let panel = Panel::coords(x, y, *layer);
+                    let steps = arena.steps_from(panel);
+                    let jumps = arena.jumps_from(panel);
+                    arena.steps.push(steps);
+                    arena.jumps.push(jumps);
+                }
+            }
         }
+        arena
+    }
+
+    fn steps_from(&self, start: Panel) -> [[Option<Step>; 2]; 4] {
+        let mut steps = [[None; 2]; 4];
+        for (direction, towards) in OFFSETS.iter().enumerate() {
+            let location = start.location() + *towards;
+            if !self.in_map(location) {
+                continue;
+            }
+            let lower = Panel::new(location, false);
+            steps[direction][0] = Some((lower, self.step_height_diff(start, lower)));
+            if self.has_upper(location) {
+                let upper = lower.upper();
+                steps[direction][1] = Some((upper, self.step_height_diff(start, upper)));
+            }
+        }
+        steps
+    }
+
+    fn jumps_from(&self, start: Panel) -> [Vec<Step>; 4] {
+        let mut jumps = [vec![], vec![], vec![], vec![]];
+        for (direction, towards) in OFFSETS.iter().enumerate() {
+            let facing = start.location().facing_towards(start.location() + *towards);
+            let start_height = self.height_from_direction(start, facing.opposite());
+            let mut location = start.location() + *towards;
+            while self.in_map(location) {
+                let end = start.on_same_layer(location);
+                let end_height = self.height_from_direction(end, facing);
+                let height_diff = (start_height as i16 - end_height as i16).abs() as u8;
+                jumps[direction].push((end, height_diff));
+                location = location + *towards;
+            }
+        }
+        jumps
+    }
+
+    pub fn from_dto(arena: rust::Arena) -> Arena {
+        Arena::new(
+            arena.lower,
+            arena.upper,
+            arena.width,
+            arena.height,
+            arena.starting_locations,
+        )
     }
 
     pub fn to_index(&self, x: usize, y: usize) -> usize {
@@ -44,6 +139,39 @@ impl Arena {
         Some(self.to_index(location.x as usize, location.y as usize))
     }
 
+    /// How many panels there are across both layers, see `layer_index`.
+    pub fn panel_count(&self) -> usize {
+        self.lower.len() + self.upper.len()
+    }
+
+    /// Numbers every panel on both layers, lower layer first.
+    pub fn layer_index(&self, panel: Panel) -> usize {
+        let idx = self.panel_to_index(panel).expect("panel out of bounds");
+        if panel.layer() {
+            idx + self.lower.len()
+        } else {
+            idx
+        }
+    }
+
+    pub fn lower(&self) -> &[Tile] {
+        &self.lower
+    }
+
+    pub fn upper(&self) -> &[Tile] {
+        &self.upper
+    }
+
+    /// The panels one step from `panel` going `direction`, lower layer first.
+    pub fn steps(&self, panel: Panel, direction: usize) -> &[Option<Step>; 2] {
+        &self.steps[self.layer_index(panel)][direction]
+    }
+
+    /// The panels `panel` could jump to going `direction`, nearest first.
+    pub fn jumps(&self, panel: Panel, direction: usize) -> &[Step] {
+        &self.jumps[self.layer_index(panel)][direction]
+    }
+
     pub fn tile(&self, panel: Panel) -> Tile {
         let idx = self.panel_to_index(panel).expect("panel out of bounds");
         if panel.layer() {
@@ -52,4 +180,86 @@ impl Arena {
             self.lower[idx]
         }
     }
+
+    /// Same as `tile_height` on the panel's tile.
+    pub fn tile_height(&self, panel: Panel) -> f32 {
+        self.heights[self.layer_index(panel)]
+    }
+
+    /// Same as `tile_height_from_direction` on the panel's tile.
+    pub fn height_from_direction(&self, panel: Panel, direction: Facing) -> u8 {
+        self.directional_heights[self.layer_index(panel)][direction.index()]
+    }
+
+    /// The height of the edge of `panel` that faces `towards`.
+    pub fn height_facing(&self, panel: Panel, towards: Panel) -> u8 {
+        let direction = panel.facing_towards(towards);
+        self.height_from_direction(panel, direction.opposite())
+    }
+
+    /// How far up `end` is from `start`, going by the edges the two panels present to each
+    /// other. Negative when `end` is lower.
+    pub fn facing_height_diff(&self, start: Panel, end: Panel) -> i16 {
+        self.height_facing(end, start) as i16 - self.height_facing(start, end) as i16
+    }
+
+    /// How far a unit has to climb or drop to step from `start` onto the adjacent `end`.
+    pub fn step_height_diff(&self, start: Panel, end: Panel) -> u8 {
+        self.facing_height_diff(start, end).abs() as u8
+    }
+
+    pub fn in_map(&self, location: Location) -> bool {
+        self.location_to_index(location).is_some()
+    }
+
+    pub fn has_upper(&self, location: Location) -> bool {
+        match self.location_to_index(location) {
+            Some(idx) => self.upper_exists[idx],
+            None => false,
+        }
+    }
+
+    /// Whether `panel` is on the map, which on the upper layer means there's something up top.
+    pub fn has_panel(&self, panel: Panel) -> bool {
+        if panel.layer() {
+            self.has_upper(panel.location())
+        } else {
+            self.in_map(panel.location())
+        }
+    }
+
+    /// Both layers at `location` when there's something up top, otherwise just the lower one.
+    /// Nothing at all when it's off the map.
+    pub fn panels_at(&self, location: Location) -> impl Iterator<Item = Panel> {
+        let panel = Panel::new(location, false);
+        let lower = if self.in_map(location) {
+            Some(panel)
+        } else {
+            None
+        };
+        let upper = if self.has_upper(location) {
+            Some(panel.upper())
+        } else {
+            None
+        };
+        lower.into_iter().chain(upper)
+    }
+
+    /// Picks the panel at `location` whose tile is closest to `height`, only looking at the
+    /// upper layer when there's actually something up there (e.g. a bridge).
+    pub fn panel_nearest_height(&self, location: Location, height: f32) -> Option<Panel> {
+        self.location_to_index(location)?;
+        let lower = Panel::new(location, false);
+        if !self.has_upper(location) {
+            return Some(lower);
+        }
+        let upper = lower.upper();
+        let lower_diff = (self.tile_height(lower) - height).abs();
+        let upper_diff = (self.tile_height(upper) - height).abs();
+        if upper_diff < lower_diff {
+            Some(upper)
+        } else {
+            Some(lower)
+        }
+    }
 }
@@ -26,9 +26,10 @@ use crate::sim::actions::time_magic::TIME_MAGIC_ABILITIES;
 use crate::sim::actions::white_magic::WHITE_MAGIC_ABILITIES;
 use crate::sim::actions::yin_yang_magic::YIN_YANG_MAGIC_ABILITIES;
 use crate::sim::{
-    Ability, Action, CalcAlgorithm, CalcAttribute, Condition, ConditionBlock, ConditionFlags,
-    Distance, Element, Facing, Gender, Location, Panel, RelativeFacing, Sign, SkillBlock, Team,
-    ALL_CONDITIONS, DONT_MOVE_WHILE_CHARGING, JUMPING, PERFORMANCE, SILENCEABLE,
+    Ability, Action, CalcAlgorithm, CalcAttribute, Compatibility, Condition, ConditionBlock,
+    ConditionFlags, Distance, Element, Facing, Gender, Location, Panel, RelativeFacing, Sign,
+    SkillBlock, StatusRules, Team, ACTION_BLOCKERS, ALL_CONDITIONS, DONT_MOVE_WHILE_CHARGING,
+    JUMPING, MOVEMENT_BLOCKERS, PERFORMANCE, SILENCEABLE,
 };
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -94,6 +95,15 @@ pub enum EquipSlot {
     Accessory,
 }
 
+/// The conditions `Combatant::has_condition` derives from HP or the charging action.
+const COMPUTED_CONDITIONS: [Condition; 5] = [
+    Condition::Critical,
+    Condition::Charging,
+    Condition::Jumping,
+    Condition::Performing,
+    Condition::Death,
+];
+
 impl EquipSlot {
     pub fn flag(self) -> u8 {
         1 << (self as u8)
@@ -119,6 +129,8 @@ pub struct CombatantInfo<'a> {
     pub headgear: Option<&'a Equipment>,
     pub armor: Option<&'a Equipment>,
     pub accessory: Option<&'a Equipment>,
+    pub throwables: Vec<&'a Equipment>,
+    pub status_rules: StatusRules,
     pub starting_brave: i8,
     pub starting_faith: i8,
     pub horizontal_jump: i8,
@@ -223,6 +235,24 @@ impl<'a> CombatantInfo<'a> {
             }
         }
 
+        // Every weapon in the patch, weakest first, with ties broken by name so that the pick
+        // doesn't depend on the hash map's order.
+        let mut throwables: Vec<&Equipment> = vec![];
+        if abilities.iter().any(|ability| {
+            THROW_ABILITIES
+                .iter()
+                .any(|throw| throw.name == ability.name)
+        }) {
+            throwables.extend(
+                patch
+                    .equipment
+                    .by_name
+                    .values()
+                    .filter(|equip| equip.weapon_type.is_some()),
+            );
+            throwables.sort_by(|a, b| (a.wp, &a.name).cmp(&(b.wp, &b.name)));
+        }
+
         let mut known_calc_attributes = 0;
         let mut known_calc_algorithms = 0;
         if src.action_skill == "Math Skill" || &src.class == "Calculator" {
@@ -292,6 +322,8 @@ impl<'a> CombatantInfo<'a> {
             headgear: patch.equipment.by_name.get(&src.head),
             armor: patch.equipment.by_name.get(&src.armor),
             accessory: patch.equipment.by_name.get(&src.accessory),
+            throwables,
+            status_rules: StatusRules::for_patch(patch),
             starting_brave: src.brave,
             starting_faith: src.faith,
             abilities,
@@ -558,16 +590,21 @@ impl<'a> Combatant<'a> {
         self.raw_brave as f32 / 100.0
     }
 
-    pub fn faith_percent(&self) -> f32 {
+    /// Faith as the formulas see it: 100 with Faith, 0 with Innocent.
+    pub fn effective_faith(&self) -> i16 {
         if self.faith() {
-            1.0
+            100
         } else if self.innocent() {
-            0.0
+            0
         } else {
-            self.raw_faith as f32 / 100.0
+            self.raw_faith as i16
         }
     }
 
+    pub fn faith_percent(&self) -> f32 {
+        self.effective_faith() as f32 / 100.0
+    }
+
     fn evasion_multiplier(&self, attacker_blind: bool) -> f32 {
         let mut mult = 1.0;
         // TODO: Add configuration for if charging() removes evasion.
@@ -754,7 +791,31 @@ impl<'a> Combatant<'a> {
         } else if self.short_status() {
             duration_mod = 2;
         }
-        self.conditions.add(condition, duration_mod);
+        let duration = self
+            .info
+            .status_rules
+            .duration(condition)
+            .map(|duration| duration * duration_mod / 4);
+        self.conditions.add(condition, duration);
+    }
+
+    /// Whether this unit has any of `flags`, counting the conditions `has_condition`
+    /// works out rather than reads off the block.
+    fn has_any_condition(&self, flags: ConditionFlags) -> bool {
+        self.conditions.has_any(flags)
+            || COMPUTED_CONDITIONS
+                .iter()
+                .any(|condition| flags & condition.flag() != 0 && self.has_condition(*condition))
+    }
+
+    /// Whether anything is stopping this unit from acting, see `StatusRule::blocks_action`.
+    pub fn action_blocked(&self) -> bool {
+        self.has_any_condition(ACTION_BLOCKERS)
+    }
+
+    /// Whether anything is stopping this unit from moving, see `StatusRule::blocks_movement`.
+    pub fn movement_blocked(&self) -> bool {
+        self.has_any_condition(MOVEMENT_BLOCKERS)
     }
 
     pub fn dead(&self) -> bool {
@@ -867,6 +928,19 @@ impl<'a> Combatant<'a> {
         self.conditions.has(Condition::Berserk)
     }
 
+    /// How much CT this unit gains each clock tick.
+    pub fn ct_speed(&self) -> u8 {
+        // TODO: I'm not sure what is making these overflow :/
+        let mut speed: u8 = self.speed();
+        if self.haste() {
+            speed = speed.saturating_mul(3) / 2;
+        }
+        if self.slow() {
+            speed = speed.saturating_mul(2) / 3;
+        }
+        speed
+    }
+
     pub fn dont_move(&self) -> bool {
         self.conditions.has(Condition::DontMove)
     }
@@ -1160,20 +1234,20 @@ impl<'a> Combatant<'a> {
         self.info.abilities.as_slice()
     }
 
-    pub fn zodiac_compatibility(&self, other: &Combatant) -> f32 {
+    pub fn compatibility(&self, other: &Combatant) -> Compatibility {
         let s1 = self.info.sign.index();
         let s2 = other.info.sign.index();
         match ZODIAC_CHART[s1 * 13 + s2] {
-            b'O' => 1.0,
-            b'+' => 1.25,
-            b'-' => 0.75,
+            b'O' => Compatibility::Neutral,
+            b'+' => Compatibility::Good,
+            b'-' => Compatibility::Bad,
             b'?' => {
                 if self.monster() || other.monster() {
-                    0.75
+                    Compatibility::Bad
                 } else if self.gender() != other.gender() {
-                    1.5
+                    Compatibility::Best
                 } else {
-                    0.5
+                    Compatibility::Worst
                 }
             }
             _ => unreachable!("found symbol that shouldn't be in table"),
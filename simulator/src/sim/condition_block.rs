@@ -29,13 +29,13 @@ impl ConditionBlock {
         self.innate_flags &= !innate_flags;
     }
 
-    pub fn add(&mut self, condition: Condition, duration_mod: i32) {
-        if let Some(mut duration) = condition.condition_duration() {
+    /// Adds the condition, (re)starting its countdown at `duration` if it's a timed one.
+    pub fn add(&mut self, condition: Condition, duration: Option<i32>) {
+        if condition.is_timed_condition() {
             if self.innate_flags & condition.flag() != 0 {
                 return;
             }
-            duration *= duration_mod;
-            duration /= 4;
+            let duration = duration.expect("timed conditions need a duration");
             self.timed_conditions[condition.index()] = duration as i8;
         }
         self.status_flags |= condition.flag();
@@ -45,6 +45,22 @@ impl ConditionBlock {
         (self.innate_flags | self.status_flags) & condition.flag() != 0
     }
 
+    /// Whether any of `flags` is on, innate or not.
+    pub fn has_any(&self, flags: ConditionFlags) -> bool {
+        (self.innate_flags | self.status_flags) & flags != 0
+    }
+
+    /// How many more status checks a timed condition has left, None if it isn't going away.
+    pub fn duration(&self, condition: Condition) -> Option<i8> {
+        if !condition.is_timed_condition()
+            || self.innate_flags & condition.flag() != 0
+            || !self.has(condition)
+        {
+            return None;
+        }
+        Some(self.timed_conditions[condition.index()])
+    }
+
     pub fn tick(&mut self, condition: Condition) -> Option<bool> {
         if condition.is_timed_condition() {
             let count = self.timed_conditions[condition.index()];
@@ -89,7 +105,7 @@ mod test {
         let mut block = ConditionBlock::new();
         for condition in &TIMED_CONDITIONS {
             assert!(!block.has(*condition));
-            block.add(*condition, 4);
+            block.add(*condition, condition.condition_duration());
             assert!(block.has(*condition));
             block.remove(*condition);
             assert!(!block.has(*condition));
@@ -100,7 +116,7 @@ mod test {
     pub fn tick_condition_status_until_removal() {
         for condition in &TIMED_CONDITIONS {
             let mut block = ConditionBlock::new();
-            block.add(*condition, 4);
+            block.add(*condition, condition.condition_duration());
             for _ in 0..condition.condition_duration().unwrap() - 1 {
                 assert_eq!(block.tick(*condition), Some(false));
                 assert!(block.has(*condition));
@@ -109,4 +125,18 @@ mod test {
             assert!(!block.has(*condition));
         }
     }
+
+    #[test]
+    pub fn duration_counts_down_and_ignores_innates() {
+        let mut block = ConditionBlock::new();
+        assert_eq!(block.duration(Condition::Reflect), None);
+        block.add(Condition::Reflect, Condition::Reflect.condition_duration());
+        let full = Condition::Reflect.condition_duration().unwrap() as i8;
+        assert_eq!(block.duration(Condition::Reflect), Some(full));
+        block.tick(Condition::Reflect);
+        assert_eq!(block.duration(Condition::Reflect), Some(full - 1));
+
+        let innate = ConditionBlock::new_with_innate(Condition::Reflect.flag());
+        assert_eq!(innate.duration(Condition::Reflect), None);
+    }
 }
@@ -15,22 +15,44 @@ pub const SLOPE_CONCAVE_SE: u8 = 102;
 pub const SLOPE_CONCAVE_SW: u8 = 105;
 pub const SLOPE_CONCAVE_NW: u8 = 153;
 
+/// Which corners of a tile its slope raises, in the order NE, SE, SW, NW. An incline raises a
+/// whole side, a convex slope a single corner, and a concave slope every corner but one.
+fn raised_corners(slope_type: u8) -> [bool; 4] {
+    match slope_type {
+        SLOPE_INCLINE_N => [true, false, false, true],
+        SLOPE_INCLINE_E => [true, true, false, false],
+        SLOPE_INCLINE_S => [false, true, true, false],
+        SLOPE_INCLINE_W => [false, false, true, true],
+        SLOPE_CONVEX_NE => [true, false, false, false],
+        SLOPE_CONVEX_SE => [false, true, false, false],
+        SLOPE_CONVEX_SW => [false, false, true, false],
+        SLOPE_CONVEX_NW => [false, false, false, true],
+        SLOPE_CONCAVE_NE => [true, true, false, true],
+        SLOPE_CONCAVE_SE => [true, true, true, false],
+        SLOPE_CONCAVE_SW => [false, true, true, true],
+        SLOPE_CONCAVE_NW => [true, false, true, true],
+        _ => [false; 4],
+    }
+}
+
+/// The height of the edge of `tile` that a unit crosses when moving onto it in `direction`,
+/// e.g. its south edge when moving north. An edge with only one raised corner counts as
+/// halfway up, rounded up, except on the sides of an incline.
 pub fn tile_height_from_direction(tile: &Tile, direction: Facing) -> u8 {
-    // TODO: Not sure how to handle convex?
-    match (tile.slope_type, direction) {
-        (SLOPE_FLAT_0, _) => tile.height,
-        (SLOPE_INCLINE_N, Facing::South) => tile.height + tile.slope_height,
-        (SLOPE_INCLINE_E, Facing::West) => tile.height + tile.slope_height,
-        (SLOPE_INCLINE_S, Facing::North) => tile.height + tile.slope_height,
-        (SLOPE_INCLINE_W, Facing::East) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_NE, Facing::South) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_NE, Facing::West) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_SE, Facing::North) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_SE, Facing::West) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_SW, Facing::North) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_SW, Facing::East) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_NW, Facing::South) => tile.height + tile.slope_height,
-        (SLOPE_CONCAVE_NW, Facing::East) => tile.height + tile.slope_height,
-        _ => tile.height,
+    let corners = raised_corners(tile.slope_type);
+    let [ne, se, sw, nw] = corners;
+    let incline = corners.iter().filter(|raised| **raised).count() == 2;
+    let (a, b) = match direction {
+        Facing::North => (se, sw),
+        Facing::East => (sw, nw),
+        Facing::South => (ne, nw),
+        Facing::West => (ne, se),
+    };
+    match (a, b) {
+        (true, true) => tile.height + tile.slope_height,
+        (false, false) => tile.height,
+        // TODO: Check against the game whether incline sides are halfway up too.
+        _ if incline => tile.height,
+        _ => tile.height + tile.slope_height / 2 + tile.slope_height % 2,
     }
 }
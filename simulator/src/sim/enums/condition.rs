@@ -221,24 +221,9 @@ impl Condition {
         CONDITION_NAMES[self.index()]
     }
 
+    /// How long it lasts at normal length, see `STATUS_RULES` and `StatusRules`.
     pub fn condition_duration(self) -> Option<i32> {
-        match self {
-            Condition::Charm => Some(32),
-            Condition::DontAct => Some(24),
-            Condition::DontMove => Some(24),
-            Condition::Faith => Some(32),
-            Condition::Haste => Some(32),
-            Condition::Innocent => Some(32),
-            Condition::Poison => Some(36),
-            Condition::Protect => Some(32),
-            Condition::Reflect => Some(32),
-            Condition::Regen => Some(36),
-            Condition::Shell => Some(32),
-            Condition::Slow => Some(24),
-            Condition::Sleep => Some(60),
-            Condition::Stop => Some(20),
-            _ => None,
-        }
+        self.rule().duration
     }
 
     pub const fn flag(self) -> u64 {
@@ -254,24 +239,6 @@ impl Condition {
     }
 
     pub fn cancels(self) -> &'static [Condition] {
-        match self {
-            Condition::Haste => &[Condition::Slow],
-            Condition::Slow => &[Condition::Haste],
-            Condition::Poison => &[Condition::Regen],
-            Condition::Regen => &[Condition::Poison],
-            Condition::Petrify => &[
-                Condition::Charging,
-                Condition::DeathSentence,
-                Condition::Transparent,
-            ],
-            Condition::Faith => &[Condition::Innocent],
-            Condition::Innocent => &[Condition::Faith],
-            Condition::Sleep => &[Condition::Charging],
-            Condition::Charm => &[Condition::Charging],
-            Condition::Frog => &[Condition::Charging],
-            Condition::Berserk => &[Condition::Charging],
-            Condition::Chicken => &[Condition::Charging],
-            _ => &[],
-        }
+        self.rule().cancels
     }
 }
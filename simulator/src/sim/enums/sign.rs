@@ -60,3 +60,26 @@ impl Sign {
         self as usize
     }
 }
+
+/// How well two signs get along, which scales most damage and hit chances.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compatibility {
+    Neutral,
+    Good,
+    Bad,
+    Best,
+    Worst,
+}
+
+impl Compatibility {
+    /// What gets added to `x`, rounding each quarter or half down the way the game does.
+    pub fn addend(self, x: i16) -> i16 {
+        match self {
+            Compatibility::Neutral => 0,
+            Compatibility::Good => x / 4,
+            Compatibility::Bad => -(x / 4),
+            Compatibility::Best => x / 2,
+            Compatibility::Worst => -(x / 2),
+        }
+    }
+}
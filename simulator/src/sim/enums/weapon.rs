@@ -19,6 +19,10 @@ pub enum WeaponType {
     Harp,
     Book,
     Gun,
+    // Added on the end to keep the existing discriminants stable. Older records have rods as
+    // poles and axes as flails, which `Schema::migrate` sorts out.
+    Rod,
+    Axe,
 }
 
 impl WeaponType {
@@ -30,11 +34,13 @@ impl WeaponType {
             "Knight Sword" => Some(WeaponType::KnightSword),
             "Katana" => Some(WeaponType::Katana),
             "Sword" => Some(WeaponType::Sword),
-            "Rod" | "Pole" => Some(WeaponType::Pole),
+            "Pole" => Some(WeaponType::Pole),
+            "Rod" => Some(WeaponType::Rod),
             "Spear" => Some(WeaponType::Spear),
             "Crossbow" => Some(WeaponType::Crossbow),
             "Staff" | "Stick" => Some(WeaponType::Staff),
-            "Flail" | "Axe" => Some(WeaponType::Flail),
+            "Flail" => Some(WeaponType::Flail),
+            "Axe" => Some(WeaponType::Axe),
             "Bag" => Some(WeaponType::Bag),
             "Cloth" | "Fabric" => Some(WeaponType::Cloth),
             "Musical Instrument" | "Harp" => Some(WeaponType::Harp),
@@ -30,6 +30,10 @@ pub enum Event<'a> {
     UsingAbility(CombatantId, Action<'a>),
     AbilityMissed(CombatantId, CombatantId),
     StartedCharging(CombatantId, Action<'a>),
+    CancelledCharge(CombatantId, Action<'a>),
+    ChoosingAction(CombatantId, f32),
+    ConsideredAction(CombatantId, Action<'a>, Consideration),
+    ChoseAction(CombatantId, Option<Action<'a>>),
     Silenced(CombatantId, Action<'a>),
     NoMP(CombatantId, Action<'a>),
     Broke(CombatantId, &'a Equipment),
@@ -62,6 +66,24 @@ pub enum EvasionType {
     ArrowGuard,
 }
 
+/// Why the AI threw out a candidate action.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Rejection {
+    ThirteenRule,
+    OutOfRange,
+    NoImprovement,
+}
+
+/// How the AI scored one candidate action, see `Simulation::trace_ai`.
+#[derive(Copy, Clone)]
+pub struct Consideration {
+    /// Where the user would act from, after moving if it had to move into range.
+    pub from: Panel,
+    /// The value of the simulated world after the action, if it got that far.
+    pub value: Option<f32>,
+    pub rejected: Option<Rejection>,
+}
+
 pub fn describe_entry(entry: &Entry, arena: &Arena) -> String {
     format!(
         "CT {}: {}: {}",
@@ -255,6 +277,52 @@ pub fn describe_event(event: &Event, combatants: &[Combatant], arena: &Arena) ->
             describe_target_short(action.target, combatants, arena),
         ),
 
+        Event::CancelledCharge(target_id, action) => format!(
+            "{} stopped charging {} to do something else",
+            describe_combatant(*target_id, combatants, arena),
+            action.ability.name
+        ),
+
+        Event::ChoosingAction(target_id, basis) => format!(
+            "{} is looking for something worth more than {:.4}",
+            describe_combatant_short(*target_id, combatants),
+            basis
+        ),
+
+        Event::ConsideredAction(target_id, action, consideration) => {
+            let value = match consideration.value {
+                Some(value) => format!("worth {:.4}", value),
+                None => "not simulated".to_owned(),
+            };
+            let verdict = match consideration.rejected {
+                Some(Rejection::ThirteenRule) => ", skipped by the thirteen rule",
+                Some(Rejection::OutOfRange) => ", out of range",
+                Some(Rejection::NoImprovement) => ", no improvement",
+                None => "",
+            };
+            format!(
+                "{} considered {} on {} from {}, {}{}",
+                describe_combatant_short(*target_id, combatants),
+                action.ability.name,
+                describe_target_short(action.target, combatants, arena),
+                describe_location(consideration.from, arena),
+                value,
+                verdict
+            )
+        }
+
+        Event::ChoseAction(target_id, Some(action)) => format!(
+            "{} chose {} on {}",
+            describe_combatant_short(*target_id, combatants),
+            action.ability.name,
+            describe_target_short(action.target, combatants, arena),
+        ),
+
+        Event::ChoseAction(target_id, None) => format!(
+            "{} found nothing worth doing",
+            describe_combatant_short(*target_id, combatants),
+        ),
+
         Event::Silenced(target_id, action) => format!(
             "{} couldn't finish charging {} because they were silenced",
             describe_combatant(*target_id, combatants, arena),
@@ -1,4 +1,5 @@
 pub use actions::*;
+pub use aoe::*;
 pub use arena::*;
 pub use combatant::*;
 pub use condition_block::*;
@@ -7,14 +8,18 @@ pub use enums::*;
 pub use facing::*;
 pub use location::*;
 pub use log::*;
+pub use observation::*;
 pub use panel::*;
 pub use pathfinder::*;
 pub use phase::*;
 pub use simulation::*;
 pub use skill_block::*;
+pub use state::*;
+pub use status_rules::*;
 pub use target_value::*;
 
 pub mod actions;
+pub mod aoe;
 pub mod arena;
 pub mod combatant;
 pub mod condition_block;
@@ -23,9 +28,12 @@ pub mod enums;
 pub mod facing;
 pub mod location;
 pub mod log;
+pub mod observation;
 pub mod panel;
 pub mod pathfinder;
 pub mod phase;
 pub mod simulation;
 pub mod skill_block;
+pub mod state;
+pub mod status_rules;
 pub mod target_value;
@@ -0,0 +1,344 @@
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{BattleState, CombatantId, Condition, Simulation, Source, Team, MAX_COMBATANTS};
+
+/// Something seen happen in the match on stream. `unit` is 0 to 7, the left team's four units
+/// then the right team's in match up order.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Observation {
+    /// HP lost, negative for healing.
+    Damage {
+        unit: usize,
+        amount: i16,
+    },
+    Died {
+        unit: usize,
+    },
+    StatusAdded {
+        unit: usize,
+        name: String,
+    },
+    StatusRemoved {
+        unit: usize,
+        name: String,
+    },
+}
+
+const OBSERVED: Source<'static> = Source::Constant("observed");
+
+impl Observation {
+    pub fn unit(&self) -> usize {
+        match self {
+            Observation::Damage { unit, .. }
+            | Observation::Died { unit }
+            | Observation::StatusAdded { unit, .. }
+            | Observation::StatusRemoved { unit, .. } => *unit,
+        }
+    }
+
+    pub fn apply(&self, sim: &mut Simulation) -> Result<(), String> {
+        if self.unit() >= MAX_COMBATANTS {
+            return Err(format!("there's no unit {}", self.unit()));
+        }
+        let id = CombatantId::new(self.unit() as u8);
+        match self {
+            Observation::Damage { amount, .. } => {
+                let target = sim.combatant_mut(id);
+                let was_dead = target.dead();
+                if was_dead && *amount > 0 {
+                    return Err(format!("unit {} took damage while dead", self.unit()));
+                }
+                // Healing someone dead is a revive, e.g. Raise or a Phoenix Down.
+                if *amount < 0 && target.crystal() {
+                    return Err(format!("unit {} was healed as a crystal", self.unit()));
+                }
+                target.set_hp_within_bounds(target.hp().saturating_sub(*amount));
+                if !was_dead && target.dead() {
+                    sim.target_died(id, OBSERVED);
+                }
+            }
+            Observation::Died { .. } => sim.target_died(id, OBSERVED),
+            Observation::StatusAdded { name, .. } => {
+                sim.add_condition(id, parse_condition(name)?, OBSERVED)
+            }
+            Observation::StatusRemoved { name, .. } => {
+                sim.cancel_condition(id, parse_condition(name)?, OBSERVED)
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_condition(name: &str) -> Result<Condition, String> {
+    Condition::parse(name).ok_or_else(|| format!("unknown condition {:?}", name))
+}
+
+/// How many matches `estimate_state` plays out looking for one that fits the observations.
+pub const ESTIMATE_SAMPLES: usize = 200;
+
+/// Our best guess at where the match in `sim` has got to after `observations`, in order. The
+/// stream shows HP and conditions but not positions, CT or the clock, so those come from the
+/// first of `ESTIMATE_SAMPLES` matches played out from `sim` that fits what was seen, see
+/// `fits`. If none do, they stay where `sim` has them.
+pub fn estimate_state(
+    sim: &Simulation,
+    observations: &[Observation],
+) -> Result<BattleState, String> {
+    let mut observed = sim.clone();
+    for observation in observations {
+        observation.apply(&mut observed)?;
+    }
+    let mut state = observed.battle_state();
+    if let Some(sample) = sample_fitting(sim, &observed) {
+        for (unit, sampled) in state.combatants.iter_mut().zip(sample.combatants) {
+            unit.mp = sampled.mp;
+            unit.ct = sampled.ct;
+            unit.x = sampled.x;
+            unit.y = sampled.y;
+            unit.upper = sampled.upper;
+            unit.facing = sampled.facing;
+        }
+        state.clock_tick = sample.clock_tick;
+    }
+    Ok(state)
+}
+
+fn sample_fitting(start: &Simulation, observed: &Simulation) -> Option<BattleState> {
+    for _ in 0..ESTIMATE_SAMPLES {
+        let mut sample = start.clone();
+        let rng = SmallRng::from_rng(&mut *start.rng.borrow_mut()).unwrap();
+        *sample.rng.borrow_mut() = rng;
+        while sample.left_wins.is_none() {
+            if fits(&sample, observed) {
+                return Some(sample.battle_state());
+            }
+            sample.step();
+        }
+    }
+    None
+}
+
+/// Whether `sample` could be the match that was seen as `observed`: the same units are down,
+/// and each team's HP is within a quarter of its max of what was seen. Who took which hit
+/// varies too much between runs to ask for every unit's HP to line up.
+fn fits(sample: &Simulation, observed: &Simulation) -> bool {
+    let units = sample.combatants.iter().zip(&observed.combatants);
+    if units
+        .clone()
+        .any(|(sampled, seen)| sampled.dead() != seen.dead())
+    {
+        return false;
+    }
+    [Team::Left, Team::Right].iter().all(|team| {
+        let (mut sampled_hp, mut seen_hp, mut max_hp) = (0, 0, 0);
+        for (sampled, seen) in units.clone().filter(|(_, seen)| seen.team() == *team) {
+            sampled_hp += i32::from(sampled.hp());
+            seen_hp += i32::from(seen.hp());
+            max_hp += i32::from(seen.max_hp());
+        }
+        (sampled_hp - seen_hp).abs() <= max_hp / 4
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::test_support::{facing_off_arena, flat_arena, sim_with};
+    use crate::runner::run_many_sims_observed;
+
+    fn observe(observations: &[Observation], check: impl FnOnce(Result<BattleState, String>)) {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| check(estimate_state(sim, observations)),
+        );
+    }
+
+    fn status(unit: usize, name: &str, added: bool) -> Observation {
+        let name = name.to_owned();
+        if added {
+            Observation::StatusAdded { unit, name }
+        } else {
+            Observation::StatusRemoved { unit, name }
+        }
+    }
+
+    fn has(state: &BattleState, unit: usize, name: &str) -> bool {
+        state.combatants[unit]
+            .conditions
+            .iter()
+            .any(|condition| condition.name == name)
+    }
+
+    #[test]
+    pub fn observations_read_from_json() {
+        let json = r#"[{"kind": "damage", "unit": 4, "amount": 30},
+                       {"kind": "died", "unit": 1},
+                       {"kind": "status_added", "unit": 2, "name": "Poison"}]"#;
+        let observations: Vec<Observation> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            observations,
+            vec![
+                Observation::Damage {
+                    unit: 4,
+                    amount: 30
+                },
+                Observation::Died { unit: 1 },
+                status(2, "Poison", true),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn observations_move_the_state_along() {
+        let observations = [
+            Observation::Damage {
+                unit: 4,
+                amount: 30,
+            },
+            Observation::Damage {
+                unit: 4,
+                amount: -10,
+            },
+            status(2, "Poison", true),
+            status(2, "Regen", true),
+            status(3, "Haste", true),
+            status(3, "Haste", false),
+            status(1, "Slow", true),
+            Observation::Died { unit: 1 },
+        ];
+        observe(&observations, |state| {
+            let state = state.unwrap();
+            let max_hp = state.combatants[0].hp;
+            assert_eq!(state.combatants[4].hp, max_hp - 20);
+            // Regen cancels Poison.
+            assert!(has(&state, 2, "Regen"));
+            assert!(!has(&state, 2, "Poison"));
+            assert!(!has(&state, 3, "Haste"));
+            // Dying cancels Slow.
+            assert_eq!(state.combatants[1].hp, 0);
+            assert!(has(&state, 1, "Death"));
+            assert!(!has(&state, 1, "Slow"));
+        });
+    }
+
+    #[test]
+    pub fn rejects_observations_that_do_not_fit() {
+        observe(&[Observation::Died { unit: 8 }], |state| {
+            assert!(state.is_err());
+        });
+        observe(&[status(0, "Sleepy", true)], |state| {
+            assert!(state.unwrap_err().contains("Sleepy"));
+        });
+        let hurt_while_dead = [
+            Observation::Died { unit: 1 },
+            Observation::Damage {
+                unit: 1,
+                amount: 10,
+            },
+        ];
+        observe(&hurt_while_dead, |state| {
+            assert!(state.unwrap_err().contains("dead"));
+        });
+    }
+
+    #[test]
+    pub fn extreme_amounts_stay_within_bounds() {
+        let observations = [
+            Observation::Damage {
+                unit: 4,
+                amount: 30,
+            },
+            Observation::Damage {
+                unit: 4,
+                amount: i16::MIN,
+            },
+            Observation::Damage {
+                unit: 5,
+                amount: i16::MAX,
+            },
+        ];
+        observe(&observations, |state| {
+            let state = state.unwrap();
+            assert_eq!(state.combatants[4].hp, state.combatants[0].hp);
+            assert_eq!(state.combatants[5].hp, 0);
+            assert!(has(&state, 5, "Death"));
+        });
+    }
+
+    #[test]
+    pub fn healing_the_dead_revives_them() {
+        let observations = [
+            Observation::Died { unit: 1 },
+            Observation::Damage {
+                unit: 1,
+                amount: -50,
+            },
+        ];
+        observe(&observations, |state| {
+            let state = state.unwrap();
+            assert_eq!(state.combatants[1].hp, 50);
+            assert!(!has(&state, 1, "Death"));
+        });
+    }
+
+    #[test]
+    pub fn estimates_pick_up_the_clock_and_positions_from_a_fitting_match() {
+        sim_with(
+            &facing_off_arena(),
+            |_| {},
+            |sim| {
+                // What the stream would have shown of a match up to its first death.
+                let mut seen = sim.clone();
+                while seen.combatants.iter().all(|unit| !unit.dead()) {
+                    seen.step();
+                }
+                let observations: Vec<_> = seen
+                    .combatants
+                    .iter()
+                    .enumerate()
+                    .map(|(unit, combatant)| match combatant.dead() {
+                        true => Observation::Died { unit },
+                        false => Observation::Damage {
+                            unit,
+                            amount: combatant.max_hp() - combatant.hp(),
+                        },
+                    })
+                    .collect();
+
+                let state = estimate_state(sim, &observations).unwrap();
+                assert!(state.clock_tick > 0);
+                for (unit, combatant) in state.combatants.iter().zip(&seen.combatants) {
+                    assert_eq!(unit.hp, combatant.hp());
+                }
+                assert!(state.apply(&mut sim.clone()).is_ok());
+
+                let fresh = estimate_state(sim, &[]).unwrap();
+                assert_eq!(fresh, sim.battle_state());
+            },
+        );
+    }
+
+    #[test]
+    pub fn odds_follow_observed_deaths() {
+        sim_with(
+            &facing_off_arena(),
+            |_| {},
+            |sim| {
+                let (combatants, arena) = (&sim.combatants, sim.arena);
+                let right_dead: Vec<_> = (4..8).map(|unit| Observation::Died { unit }).collect();
+                let (left_wins, _) =
+                    run_many_sims_observed(5, combatants, arena, &right_dead).unwrap();
+                assert_eq!(left_wins, 0.95);
+
+                let left_dead: Vec<_> = (0..4).map(|unit| Observation::Died { unit }).collect();
+                let (left_wins, _) =
+                    run_many_sims_observed(5, combatants, arena, &left_dead).unwrap();
+                assert_eq!(left_wins, 0.05);
+            },
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::sim::{tile_height_from_direction, Arena, Combatant, Location, Panel, OFFSETS};
+use crate::sim::{Arena, Combatant, Location, Panel, Step, OFFSETS};
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::usize;
@@ -53,9 +53,12 @@ pub struct Pathfinder<'a> {
     reachable: Vec<Panel>,
     lower_temp: Vec<TileMarking>,
     upper_temp: Vec<TileMarking>,
+    /// What the last `calculate_reachable_around` was asked, while its answer still stands.
+    reachable_from: Option<(MovementInfo, Panel)>,
+    blockers: Vec<(Panel, bool)>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct MovementInfo {
     pub movement: u8,
     pub vertical_jump: u8,
@@ -97,6 +100,8 @@ impl<'a> Pathfinder<'a> {
             reachable: Vec::with_capacity(255),
             lower_temp: Vec::with_capacity(area),
             upper_temp: Vec::with_capacity(area),
+            reachable_from: None,
+            blockers: vec![],
         };
         pathfinder.lower.resize(area, TileMarking::default());
         pathfinder.upper.resize(area, TileMarking::default());
@@ -106,6 +111,7 @@ impl<'a> Pathfinder<'a> {
     }
 
     pub fn reset(&mut self) {
+        self.reachable_from = None;
         for i in 0..self.lower.len() {
             self.lower[i].distance = MAX_DISTANCE;
             self.lower[i].flags &= OCCUPIED_FLAG | IMPASSABLE_FLAG;
@@ -117,6 +123,7 @@ impl<'a> Pathfinder<'a> {
     }
 
     pub fn reset_all(&mut self) {
+        self.reachable_from = None;
         for i in 0..self.lower.len() {
             self.lower[i] = TileMarking::default();
             self.upper[i] = TileMarking::default();
@@ -152,10 +159,12 @@ impl<'a> Pathfinder<'a> {
     }
 
     pub fn set_occupied(&mut self, panel: Panel) {
+        self.reachable_from = None;
         self.tile_marking_mut(panel).flags |= OCCUPIED_FLAG;
     }
 
     pub fn set_impassable(&mut self, panel: Panel) {
+        self.reachable_from = None;
         self.tile_marking_mut(panel).flags |= IMPASSABLE_FLAG;
     }
 
@@ -235,6 +244,33 @@ impl<'a> Pathfinder<'a> {
             .unwrap()
     }
 
+    /// Everywhere a unit at `start` can move to, with `blockers` standing in the way: each is
+    /// occupied, and impassable when it's true. Nothing gets recalculated if it's the same
+    /// question as last time.
+    pub fn calculate_reachable_around(
+        &mut self,
+        info: &MovementInfo,
+        start: Panel,
+        blockers: &[(Panel, bool)],
+    ) {
+        if let Some((last_info, last_start)) = &self.reachable_from {
+            if last_info == info && *last_start == start && self.blockers == blockers {
+                return;
+            }
+        }
+        self.reset_all();
+        for (panel, impassable) in blockers {
+            self.set_occupied(*panel);
+            if *impassable {
+                self.set_impassable(*panel);
+            }
+        }
+        self.calculate_reachable_no_reset(info, start);
+        self.blockers.clear();
+        self.blockers.extend_from_slice(blockers);
+        self.reachable_from = Some((info.clone(), start));
+    }
+
     pub fn calculate_reachable_no_reset(&mut self, info: &MovementInfo, start: Panel) {
         self.calculate_reachable_with_goal_no_reset(info, start, None);
     }
@@ -246,6 +282,7 @@ impl<'a> Pathfinder<'a> {
         goal: Option<Panel>,
     ) {
         assert!(self.inside_map(start));
+        self.reachable_from = None;
         self.expand_open_set(start, 0);
         self.expand_reachable(start);
         self.set_distance(start, 0);
@@ -263,30 +300,25 @@ impl<'a> Pathfinder<'a> {
                 continue;
             }
 
-            for direction in &OFFSETS {
-                let end_lower = start.plus(*direction).lower();
-                if !self.inside_map(end_lower) {
-                    continue;
-                }
-
-                let end_upper = end_lower.upper();
-                let end_lower_tile = self.arena.tile(end_lower);
-                let end_upper_tile = self.arena.tile(end_upper);
+            for direction in 0..OFFSETS.len() {
+                let [lower, upper] = *self.arena.steps(start, direction);
+                let lower = match lower {
+                    Some(lower) => lower,
+                    None => continue,
+                };
 
-                if self.try_move_to(info, start, end_lower) {
+                if self.try_move_to(info, start, lower) {
                     continue;
-                } else if end_upper_tile.height > end_lower_tile.height
-                    && self.try_move_to(info, start, end_upper)
-                {
+                } else if upper.map(|upper| self.try_move_to(info, start, upper)) == Some(true) {
                     continue;
                 } else {
-                    self.horizontal_jump_search(info, start, *direction);
+                    self.horizontal_jump_search(info, start, direction);
                 }
             }
         }
     }
 
-    fn try_move_to(&mut self, info: &MovementInfo, start: Panel, end: Panel) -> bool {
+    fn try_move_to(&mut self, info: &MovementInfo, start: Panel, (end, height_diff): Step) -> bool {
         let distance = self.distance(start);
         let new_distance = distance + 1;
         let old_distance = self.distance(end);
@@ -294,7 +326,7 @@ impl<'a> Pathfinder<'a> {
             return true;
         }
 
-        if self.can_transition(info, start, end) {
+        if self.can_transition(info, end, height_diff) {
             self.set_distance(end, new_distance);
             if new_distance >= info.movement {
                 return true;
@@ -309,28 +341,16 @@ impl<'a> Pathfinder<'a> {
         }
     }
 
-    fn horizontal_jump_search(&mut self, info: &MovementInfo, start: Panel, direction: Location) {
+    fn horizontal_jump_search(&mut self, info: &MovementInfo, start: Panel, direction: usize) {
         if info.horizontal_jump == 0 {
             return;
         }
 
-        let start_point = start.location();
-        let end_point = start_point + direction * (info.horizontal_jump as i16 + 1);
-        let towards_direction = start_point.facing_towards(end_point);
-        let start_tile = self.arena.tile(start);
         let distance = self.distance(start);
-
-        for (i, end_location) in start_point.line(end_point).enumerate().skip(1) {
-            if !self.inside_map_location(end_location) {
-                return;
-            }
-            let end = start.on_same_layer(end_location);
-            let end_tile = self.arena.tile(end);
-            let end_tile_height = tile_height_from_direction(&end_tile, towards_direction);
-            let start_tile_height =
-                tile_height_from_direction(&start_tile, towards_direction.opposite());
-
-            let height_diff = (start_tile_height as i16 - end_tile_height as i16).abs() as u8;
+        let arena = self.arena;
+        let jumps = arena.jumps(start, direction);
+        let reach = jumps.len().min(info.horizontal_jump as usize + 1);
+        for (i, (end, height_diff)) in (1..).zip(jumps[..reach].iter().copied()) {
             if height_diff > info.vertical_jump {
                 continue;
             }
@@ -338,7 +358,7 @@ impl<'a> Pathfinder<'a> {
                 continue;
             }
 
-            let new_distance = distance + i as u8;
+            let new_distance = distance + i;
             if new_distance >= self.distance(end) {
                 continue;
             }
@@ -379,8 +399,7 @@ impl<'a> Pathfinder<'a> {
             || location.y >= self.arena.height as i16)
     }
 
-    fn can_transition(&self, info: &MovementInfo, start: Panel, end: Panel) -> bool {
-        let start_tile = self.arena.tile(start);
+    fn can_transition(&self, info: &MovementInfo, end: Panel, height_diff: u8) -> bool {
         let end_tile = self.arena.tile(end);
 
         if info.fly_teleport {
@@ -391,12 +410,6 @@ impl<'a> Pathfinder<'a> {
         }
 
         // TODO: Still not entirely sure of this logic
-        let towards_direction = start.facing_towards(end);
-        let end_tile_height = tile_height_from_direction(&end_tile, towards_direction);
-        let start_tile_height =
-            tile_height_from_direction(&start_tile, towards_direction.opposite());
-        let height_diff = (start_tile_height as i16 - end_tile_height as i16).abs() as u8;
-
         if height_diff > info.vertical_jump {
             return false;
         }
@@ -426,7 +439,10 @@ impl<'a> Pathfinder<'a> {
 pub mod tests {
     use super::*;
     use crate::dto::rust::Tile;
-    use crate::sim::{Facing, SLOPE_FLAT_0, SLOPE_INCLINE_E, SLOPE_INCLINE_W};
+    use crate::sim::{
+        tile_height_from_direction, AoE, Facing, SLOPE_CONCAVE_SW, SLOPE_CONVEX_NE, SLOPE_FLAT_0,
+        SLOPE_INCLINE_E, SLOPE_INCLINE_W,
+    };
 
     fn tile_no_walk() -> Tile {
         Tile {
@@ -456,13 +472,7 @@ pub mod tests {
         }
         let mut upper = vec![];
         upper.resize(100, tile_no_walk());
-        Arena {
-            lower: tiles,
-            upper,
-            width: 10,
-            height: 10,
-            starting_locations: vec![],
-        }
+        Arena::new(tiles, upper, 10, 10, vec![])
     }
 
     #[test]
@@ -506,13 +516,7 @@ pub mod tests {
         }
         let mut upper = vec![];
         upper.resize(5, no_walk);
-        Arena {
-            lower: tiles,
-            upper,
-            width: 5,
-            height: 1,
-            starting_locations: vec![],
-        }
+        Arena::new(tiles, upper, 5, 1, vec![])
     }
 
     fn make_high_slope_map() -> Arena {
@@ -546,16 +550,37 @@ pub mod tests {
         let tiles = vec![walk, up_east, walk, up_west, walk];
         let mut upper = vec![];
         upper.resize(5, tile_no_walk());
-        Arena {
-            lower: tiles,
-            upper,
-            width: 5,
-            height: 1,
-            starting_locations: vec![],
-        }
+        Arena::new(tiles, upper, 5, 1, vec![])
+    }
+
+    // Flat, then a slope rising to its north east corner, then flat again.
+    fn make_convex_slope_map() -> Arena {
+        let walk = Tile {
+            height: 0,
+            depth: 0,
+            slope_type: SLOPE_FLAT_0,
+            surface_type: 0,
+            slope_height: 0,
+            no_cursor: false,
+            no_walk: false,
+        };
+        let convex = Tile {
+            height: 0,
+            depth: 0,
+            slope_type: SLOPE_CONVEX_NE,
+            surface_type: 0,
+            slope_height: 3,
+            no_cursor: false,
+            no_walk: false,
+        };
+        let tiles = vec![walk, convex, walk];
+        let mut upper = vec![];
+        upper.resize(3, tile_no_walk());
+        Arena::new(tiles, upper, 3, 1, vec![])
     }
 
-    fn make_bridge_map() -> Arena {
+    /// A 6x1 strip with a bridge over x = 2 & 3, reached by inclines at x = 1 & 4.
+    pub fn make_bridge_map() -> Arena {
         let walk = Tile {
             height: 0,
             depth: 0,
@@ -597,13 +622,7 @@ pub mod tests {
         upper.resize(6, tile_no_walk());
         upper[2] = middle;
         upper[3] = middle;
-        Arena {
-            lower: tiles,
-            upper,
-            width: 6,
-            height: 1,
-            starting_locations: vec![],
-        }
+        Arena::new(tiles, upper, 6, 1, vec![])
     }
 
     #[test]
@@ -632,6 +651,50 @@ pub mod tests {
         assert_eq!(pathfinder.can_reach_and_end_turn_on(end), true);
     }
 
+    #[test]
+    pub fn bridge_aoe_hits_whichever_layers_are_in_tolerance() {
+        let arena = make_bridge_map();
+        let height_of = |panel| arena.tile_height(panel);
+        let on_bridge = Panel::coords(2, 0, true);
+        let under_bridge = Panel::coords(2, 0, false);
+
+        let panels = AoE::Diamond(1, Some(3)).panels(&arena, on_bridge, on_bridge, 0, height_of);
+        assert_eq!(panels, vec![on_bridge, Panel::coords(3, 0, true)]);
+
+        let panels =
+            AoE::Diamond(1, Some(3)).panels(&arena, under_bridge, under_bridge, 0, height_of);
+        assert!(panels.contains(&Panel::coords(3, 0, false)));
+        assert!(!panels.contains(&on_bridge));
+
+        let panels = AoE::Diamond(1, None).panels(&arena, on_bridge, on_bridge, 0, height_of);
+        assert!(panels.contains(&under_bridge));
+        assert!(panels.contains(&Panel::coords(3, 0, false)));
+        assert_eq!(panels.len(), 5);
+    }
+
+    #[test]
+    pub fn lines_pass_over_and_under_the_bridge() {
+        let arena = make_bridge_map();
+        let height_of = |panel| arena.tile_height(panel);
+        let start = Panel::coords(0, 0, false);
+        let east = Panel::coords(1, 0, false);
+
+        let panels = AoE::Line(Some(2)).panels(&arena, start, east, 5, height_of);
+        assert_eq!(
+            panels,
+            vec![
+                Panel::coords(2, 0, false),
+                Panel::coords(3, 0, false),
+                Panel::coords(5, 0, false)
+            ]
+        );
+
+        let panels = AoE::Line(None).panels(&arena, start, east, 5, height_of);
+        assert!(panels.contains(&Panel::coords(2, 0, true)));
+        assert!(panels.contains(&Panel::coords(3, 0, true)));
+        assert_eq!(panels.len(), 7);
+    }
+
     #[test]
     pub fn test_walk_up_and_jump_slope() {
         let arena = make_high_slope_map();
@@ -653,6 +716,55 @@ pub mod tests {
         assert_eq!(pathfinder.can_reach_and_end_turn_on(end), true);
     }
 
+    #[test]
+    pub fn directional_heights_of_corner_slopes() {
+        let tile = |slope_type| Tile {
+            height: 2,
+            depth: 0,
+            slope_type,
+            surface_type: 0,
+            slope_height: 3,
+            no_cursor: false,
+            no_walk: false,
+        };
+        let heights = |slope_type| {
+            let tile = tile(slope_type);
+            [Facing::North, Facing::East, Facing::South, Facing::West]
+                .iter()
+                .map(|facing| tile_height_from_direction(&tile, *facing))
+                .collect::<Vec<_>>()
+        };
+        // Entering from the south, west, north & east edges respectively.
+        assert_eq!(heights(SLOPE_FLAT_0), vec![2, 2, 2, 2]);
+        assert_eq!(heights(SLOPE_INCLINE_E), vec![2, 2, 2, 5]);
+        assert_eq!(heights(SLOPE_CONVEX_NE), vec![2, 2, 4, 4]);
+        assert_eq!(heights(SLOPE_CONCAVE_SW), vec![5, 5, 4, 4]);
+    }
+
+    #[test]
+    pub fn convex_slope_is_steep_on_its_raised_sides() {
+        let arena = make_convex_slope_map();
+        let mut pathfinder = Pathfinder::new(&arena);
+        let start = Panel::coords(0, 0, false);
+        let slope = Panel::coords(1, 0, false);
+        let end = Panel::coords(2, 0, false);
+        assert_eq!(arena.step_height_diff(start, slope), 0);
+        assert_eq!(arena.step_height_diff(slope, end), 2);
+        assert_eq!(arena.facing_height_diff(slope, end), -2);
+        assert_eq!(arena.facing_height_diff(end, slope), 2);
+
+        let movement_info = MovementInfo {
+            movement: 10,
+            vertical_jump: 1,
+            horizontal_jump: 0,
+            fly_teleport: false,
+            water_ok: false,
+        };
+        pathfinder.calculate_reachable(&movement_info, start);
+        assert!(pathfinder.is_reachable(slope));
+        assert!(!pathfinder.is_reachable(end));
+    }
+
     #[test]
     pub fn test_jump_over_no_walk() {
         let arena = make_impassible_map();
@@ -742,4 +854,56 @@ pub mod tests {
         assert_eq!(pathfinder.is_reachable(middle), true);
         assert_eq!(pathfinder.can_reach_and_end_turn_on(middle), false);
     }
+
+    #[test]
+    pub fn bridge_tables_step_and_jump_between_edges() {
+        let arena = make_bridge_map();
+        let east = Facing::East.index();
+        let foot = Panel::coords(1, 0, false);
+        let under = Panel::coords(2, 0, false);
+        let over = Panel::coords(2, 0, true);
+        assert_eq!(
+            arena.steps(foot, east),
+            &[
+                Some((under, arena.step_height_diff(foot, under))),
+                Some((over, arena.step_height_diff(foot, over)))
+            ]
+        );
+        assert_eq!(arena.steps(Panel::coords(5, 0, false), east), &[None, None]);
+
+        let start = Panel::coords(0, 0, false);
+        let jumps: Vec<_> = arena.jumps(start, east).iter().map(|jump| jump.0).collect();
+        assert_eq!(
+            jumps,
+            (1..6)
+                .map(|x| Panel::coords(x, 0, false))
+                .collect::<Vec<_>>()
+        );
+        assert!(arena.jumps(start, Facing::North.index()).is_empty());
+    }
+
+    #[test]
+    pub fn reuses_reachable_until_someone_moves() {
+        let arena = make_simple_map();
+        let mut pathfinder = Pathfinder::new(&arena);
+        let start = Panel::coords(0, 0, false);
+        let movement_info = MovementInfo {
+            movement: 3,
+            horizontal_jump: 0,
+            vertical_jump: 3,
+            fly_teleport: false,
+            water_ok: false,
+        };
+        let blocked = [(start, false), (Panel::coords(1, 0, false), true)];
+        pathfinder.calculate_reachable_around(&movement_info, start, &blocked);
+        let first = pathfinder.reachable_set().to_vec();
+        assert!(!first.contains(&Panel::coords(2, 0, false)));
+
+        pathfinder.calculate_reachable_around(&movement_info, start, &blocked);
+        assert_eq!(pathfinder.reachable_set(), &first[..]);
+
+        let moved = [(start, false), (Panel::coords(5, 5, false), true)];
+        pathfinder.calculate_reachable_around(&movement_info, start, &moved);
+        assert!(pathfinder.can_reach_and_end_turn_on(Panel::coords(2, 0, false)));
+    }
 }
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
 
 use rand;
 use rand::prelude::SmallRng;
@@ -9,17 +9,40 @@ use crate::sim::actions::attack::{attack_range, ATTACK_ABILITY};
 use crate::sim::actions::basic_skill::DASH_ABILITY;
 
 use crate::sim::{
-    ai_consider_actions, ai_target_value_sum, perform_action, perform_action_slow, AbilityFlags,
-    Action, ActionTarget, Arena, Combatant, CombatantId, Condition, EvasionType, Event, Location,
-    Log, MovementInfo, Panel, Pathfinder, Phase, SlowAction, Source, Team, WeaponType, ALLY_OK,
-    ALL_CONDITIONS, COMBATANT_IDS, COMBATANT_IDS_LEN, COMBATANT_IDS_TURN_RESOLVE, DAMAGE_CANCELS,
-    DEATH_CANCELS, FOE_OK, NO_SHORT_CHARGE, TIMED_CONDITIONS,
+    ai_consider_actions, ai_target_value_sum, perform_action, perform_action_slow, ticking_at,
+    AbilityFlags, Action, AoE, Arena, BattleState, Combatant, CombatantId, CombatantState,
+    Condition, Consideration, EvasionType, Event, Location, Log, MovementInfo, Panel, Pathfinder,
+    Phase, Rejection, SlowAction, Source, StatusTick, Team, WeaponType, ALLY_OK, ALL_CONDITIONS,
+    COMBATANT_IDS, COMBATANT_IDS_LEN, COMBATANT_IDS_TURN_RESOLVE, DAMAGE_CANCELS, DEATH_CANCELS,
+    FOE_OK, NO_SHORT_CHARGE,
 };
 use std::borrow::Borrow;
 
 pub const MAX_COMBATANTS: usize = COMBATANT_IDS_LEN;
 const TIME_OUT_CT: usize = 1_000;
 
+/// Whether a unit moves into range before using its action, or acts from where it stands
+/// and is then free to move afterwards.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TurnOrder {
+    MoveThenAct,
+    ActThenMove,
+}
+
+#[derive(Copy, Clone)]
+pub struct TurnPlan<'a> {
+    pub action: Action<'a>,
+    pub order: TurnOrder,
+}
+
+/// Someone getting to go in `Simulation::forecast_turns`, either `Phase::ActiveTurn` or a
+/// charged action resolving in `Phase::SlowAction`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ForecastTurn {
+    pub clock_tick: usize,
+    pub phase: Phase,
+}
+
 #[derive(Clone)]
 pub struct Simulation<'a> {
     pub rng: RefCell<SmallRng>,
@@ -31,10 +54,15 @@ pub struct Simulation<'a> {
     pub prediction_mode: bool,
     pub trigger_countergrasps: bool,
     pub log: Log<'a>,
+    /// Also log the AI's decision making: the basis each turn has to beat, every candidate
+    /// action with its score or why it was thrown out, and the action it settled on.
+    pub trace_ai: bool,
     pub slow_actions: bool,
     pub active_turns: bool,
     pub left_wins: Option<bool>,
     pub time_out_win: Option<bool>,
+    /// Turns noted down by `forecast_turns` in place of actually taking them.
+    forecast: Option<Vec<ForecastTurn>>,
 }
 
 impl<'a> Simulation<'a> {
@@ -59,15 +87,39 @@ impl<'a> Simulation<'a> {
             } else {
                 Log::new_no_log()
             },
+            trace_ai: false,
             slow_actions: false,
             active_turns: false,
             left_wins: None,
             time_out_win: None,
+            forecast: None,
         };
         sim.set_starting_locations();
         sim
     }
 
+    /// A simulation picking up from `state` instead of the start of the match.
+    pub fn from_state(
+        combatants: [Combatant<'a>; MAX_COMBATANTS],
+        arena: &'a Arena,
+        pathfinder: &'a RefCell<Pathfinder<'a>>,
+        rng: SmallRng,
+        event_log: bool,
+        state: &BattleState,
+    ) -> Result<Simulation<'a>, String> {
+        let mut sim = Simulation::new(combatants, arena, pathfinder, rng, event_log);
+        state.apply(&mut sim)?;
+        Ok(sim)
+    }
+
+    /// Where everyone is right now, enough to pick the battle back up with `from_state`.
+    pub fn battle_state(&self) -> BattleState {
+        BattleState {
+            clock_tick: self.clock_tick,
+            combatants: self.combatants.iter().map(CombatantState::of).collect(),
+        }
+    }
+
     fn set_starting_locations(&mut self) {
         for starting_location in &self.arena.starting_locations {
             let idx = if starting_location.left_team {
@@ -95,17 +147,39 @@ impl<'a> Simulation<'a> {
             prediction_mode: true,
             trigger_countergrasps: true,
             log: Log::new_no_log(),
+            trace_ai: false,
             slow_actions: self.slow_actions,
             active_turns: self.active_turns,
             left_wins: self.left_wins,
             time_out_win: self.time_out_win,
+            forecast: None,
         }
     }
 
+    /// Puts a world made by `prediction_clone` back to how `origin` is, so one prediction can
+    /// be reused for every candidate action instead of building a new one.
+    fn rewind_prediction(&mut self, origin: &Simulation<'a>) {
+        *self.rng.borrow_mut() = origin.rng.borrow().clone();
+        self.combatants = origin.combatants;
+        self.clock_tick = origin.clock_tick;
+        self.trigger_countergrasps = true;
+        self.slow_actions = origin.slow_actions;
+        self.active_turns = origin.active_turns;
+        self.left_wins = origin.left_wins;
+        self.time_out_win = origin.time_out_win;
+    }
+
     pub fn log_event(&self, event: Event<'a>) {
         self.log.add(&self.combatants, event);
     }
 
+    /// Logs one step of the AI's decision making, only when `trace_ai` is on.
+    pub fn trace_event(&self, event: Event<'a>) {
+        if self.trace_ai {
+            self.log_event(event);
+        }
+    }
+
     pub fn combatant(&self, cid: CombatantId) -> &Combatant<'a> {
         &self.combatants[cid.index()]
     }
@@ -116,22 +190,27 @@ impl<'a> Simulation<'a> {
 
     pub fn run(&mut self) {
         while let None = self.left_wins {
-            self.tick();
+            self.step();
+        }
+    }
 
-            if !self.team_healthy(Team::Left) {
-                self.left_wins = Some(false);
-                self.time_out_win = Some(false);
-            }
+    /// One clock tick, then whether that's settled the match.
+    pub fn step(&mut self) {
+        self.tick();
 
-            if !self.team_healthy(Team::Right) {
-                self.left_wins = Some(true);
-                self.time_out_win = Some(false);
-            }
+        if !self.team_healthy(Team::Left) {
+            self.left_wins = Some(false);
+            self.time_out_win = Some(false);
+        }
 
-            if self.clock_tick > TIME_OUT_CT {
-                self.left_wins = Some(true);
-                self.time_out_win = Some(true);
-            }
+        if !self.team_healthy(Team::Right) {
+            self.left_wins = Some(true);
+            self.time_out_win = Some(false);
+        }
+
+        if self.clock_tick > TIME_OUT_CT {
+            self.left_wins = Some(true);
+            self.time_out_win = Some(true);
         }
     }
 
@@ -161,6 +240,32 @@ impl<'a> Simulation<'a> {
         }
     }
 
+    /// The next `count` active turns & slow actions from this point, found by ticking a
+    /// prediction along. Charged actions don't go off & every active turn is assumed to both
+    /// move and act, so it's only a forecast once anyone does something.
+    pub fn forecast_turns(&self, count: usize) -> Vec<ForecastTurn> {
+        let mut sim = self.prediction_clone();
+        sim.forecast = Some(vec![]);
+        while sim.forecast.as_ref().map_or(0, Vec::len) < count && sim.clock_tick <= TIME_OUT_CT {
+            sim.tick();
+        }
+        let mut turns = sim.forecast.take().unwrap_or_default();
+        turns.truncate(count);
+        turns
+    }
+
+    /// Notes down `phase` when forecasting, in which case the turn shouldn't be taken.
+    fn forecasting(&mut self, phase: Phase) -> bool {
+        let clock_tick = self.clock_tick;
+        match self.forecast.as_mut() {
+            Some(turns) => {
+                turns.push(ForecastTurn { clock_tick, phase });
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn slow_actions_remaining(&self) -> bool {
         for combatant in &self.combatants {
             if combatant.stop() {
@@ -192,10 +297,10 @@ impl<'a> Simulation<'a> {
                 continue;
             }
 
-            for condition in &TIMED_CONDITIONS {
-                let removed = self.combatant_mut(*cid).tick_condition(*condition).unwrap();
+            for condition in ticking_at(StatusTick::StatusCheck) {
+                let removed = self.combatant_mut(*cid).tick_condition(condition).unwrap();
                 if removed {
-                    self.log_event(Event::LostCondition(*cid, *condition, Source::Phase));
+                    self.log_event(Event::LostCondition(*cid, condition, Source::Phase));
                 }
             }
         }
@@ -232,8 +337,12 @@ impl<'a> Simulation<'a> {
                     continue;
                 }
                 self.log.set_phase(Phase::SlowAction(*c_id));
-                self.log_event(Event::UsingAbility(*c_id, slow_action.action));
-                perform_action_slow(self, *c_id, slow_action.action);
+                let performed = if self.forecasting(Phase::SlowAction(*c_id)) {
+                    false
+                } else {
+                    self.log_event(Event::UsingAbility(*c_id, slow_action.action));
+                    perform_action_slow(self, *c_id, slow_action.action)
+                };
 
                 let combatant = self.combatant_mut(*c_id);
 
@@ -249,7 +358,8 @@ impl<'a> Simulation<'a> {
                     combatant.ctr_action = None;
                 }
 
-                if !self.combatant(*c_id).monster() && !self.combatant(*c_id).mimic() {
+                // Mimes copy a charged action as it resolves, but not one that fizzled out.
+                if performed && !self.combatant(*c_id).monster() && !self.combatant(*c_id).mimic() {
                     self.do_mime_cycle(*c_id, slow_action.action);
                 }
             }
@@ -263,15 +373,7 @@ impl<'a> Simulation<'a> {
                 continue;
             }
 
-            // TODO: I'm not sure what is making these overflow :/
-            let mut speed: u8 = combatant.speed();
-            if combatant.haste() {
-                speed = speed.saturating_mul(3) / 2;
-            }
-            if combatant.slow() {
-                speed = speed.saturating_mul(2) / 3;
-            }
-            combatant.ct = combatant.ct.saturating_add(speed);
+            combatant.ct = combatant.ct.saturating_add(combatant.ct_speed());
             if combatant.ct >= 100 {
                 self.active_turns = true;
             }
@@ -376,6 +478,51 @@ impl<'a> Simulation<'a> {
         }
     }
 
+    /// Counts `condition` down at the start of `c_id`'s active turn, returning whether
+    /// that ends the turn.
+    fn tick_at_active_turn(&mut self, c_id: CombatantId, condition: Condition) -> bool {
+        let combatant = self.combatant(c_id);
+        match condition {
+            Condition::Death if combatant.dead() && !combatant.crystal() => {
+                let now_crystal = self.combatant_mut(c_id).tick_crystal_counter();
+                let combatant = self.combatant(c_id);
+
+                if now_crystal && combatant.undead() && self.roll_auto_fail() < 0.5 {
+                    let max_hp = combatant.max_hp();
+                    self.combatant_mut(c_id).reset_crystal_counter();
+                    let heal_amount = self.roll_inclusive(1, max_hp);
+                    self.change_target_hp(c_id, -heal_amount, Source::Condition(Condition::Undead));
+                } else {
+                    self.combatant_mut(c_id).ct = 0;
+                }
+
+                let combatant = self.combatant(c_id);
+                if combatant.crystal() {
+                    self.log_event(Event::BecameCrystal(c_id));
+                    return true;
+                }
+            }
+            Condition::DeathSentence if combatant.death_sentence() => {
+                let is_undead = combatant.undead();
+                let now_dead = self.combatant_mut(c_id).tick_death_sentence_counter();
+                if now_dead && is_undead {
+                    self.cancel_condition(
+                        c_id,
+                        Condition::DeathSentence,
+                        Source::Condition(Condition::Undead),
+                    );
+                } else if now_dead {
+                    self.target_died(c_id, Source::Condition(Condition::DeathSentence));
+                }
+            }
+            Condition::Defending if combatant.defending() => {
+                self.cancel_condition(c_id, Condition::Defending, Source::Phase);
+            }
+            _ => {}
+        }
+        false
+    }
+
     pub fn phase_active_turn_resolve(&mut self) {
         for c_id in &COMBATANT_IDS_TURN_RESOLVE {
             let combatant = self.combatant(*c_id);
@@ -404,49 +551,10 @@ impl<'a> Simulation<'a> {
                 );
             }
 
-            let combatant = self.combatant(*c_id);
-            if combatant.dead() && !combatant.crystal() {
-                let now_crystal = self.combatant_mut(*c_id).tick_crystal_counter();
-                let combatant = self.combatant(*c_id);
-
-                if now_crystal && combatant.undead() && self.roll_auto_fail() < 0.5 {
-                    let max_hp = combatant.max_hp();
-                    self.combatant_mut(*c_id).reset_crystal_counter();
-                    let heal_amount = self.roll_inclusive(1, max_hp);
-                    self.change_target_hp(
-                        *c_id,
-                        -heal_amount,
-                        Source::Condition(Condition::Undead),
-                    );
-                } else {
-                    self.combatant_mut(*c_id).ct = 0;
-                }
-
-                let combatant = self.combatant(*c_id);
-                if combatant.crystal() {
-                    self.log_event(Event::BecameCrystal(*c_id));
-                    continue;
-                }
-            }
-
-            let combatant = self.combatant(*c_id);
-            if combatant.defending() {
-                self.cancel_condition(*c_id, Condition::Defending, Source::Phase);
-            }
-
-            let combatant = self.combatant(*c_id);
-            if combatant.death_sentence() {
-                let is_undead = combatant.undead();
-                let now_dead = self.combatant_mut(*c_id).tick_death_sentence_counter();
-                if now_dead && is_undead {
-                    self.cancel_condition(
-                        *c_id,
-                        Condition::DeathSentence,
-                        Source::Condition(Condition::Undead),
-                    );
-                } else if now_dead {
-                    self.target_died(*c_id, Source::Condition(Condition::DeathSentence));
-                }
+            if ticking_at(StatusTick::ActiveTurn)
+                .any(|condition| self.tick_at_active_turn(*c_id, condition))
+            {
+                continue;
             }
 
             let combatant = self.combatant(*c_id);
@@ -466,8 +574,14 @@ impl<'a> Simulation<'a> {
                 );
             }
 
-            self.ai_do_active_turn(*c_id);
-            self.face_closest_enemy(*c_id);
+            if self.forecasting(Phase::ActiveTurn(*c_id)) {
+                let combatant = self.combatant_mut(*c_id);
+                combatant.moved_during_active_turn = true;
+                combatant.acted_during_active_turn = true;
+            } else {
+                self.ai_do_active_turn(*c_id);
+                self.face_closest_enemy(*c_id);
+            }
 
             let combatant = self.combatant(*c_id);
             if combatant.poison() {
@@ -492,8 +606,7 @@ impl<'a> Simulation<'a> {
     }
 
     pub fn height(&self, panel: Panel) -> f32 {
-        let tile = self.tile(panel);
-        tile_height(&tile)
+        self.arena.tile_height(panel)
     }
 
     pub fn combatant_height(&self, combatant_id: CombatantId) -> f32 {
@@ -503,10 +616,56 @@ impl<'a> Simulation<'a> {
         combatant_height(&tile, combatant)
     }
 
+    /// The height of whoever is standing on `panel`, or of the panel itself if nobody is.
+    pub fn unit_height(&self, panel: Panel) -> f32 {
+        match self.combatant_on_panel(panel) {
+            Some(combatant_id) => self.combatant_height(combatant_id),
+            None => self.height(panel),
+        }
+    }
+
+    /// Every panel `aoe` hits when aimed from `user` at `target`, see `AoE::panels`.
+    pub fn aoe_panels(&self, aoe: AoE, user: Panel, target: Panel, range: u8) -> Vec<Panel> {
+        aoe.panels(self.arena, user, target, range, |panel| {
+            self.unit_height(panel)
+        })
+    }
+
+    /// Whether `aoe` aimed from `user` at `target` hits `panel`, see `AoE::covers`.
+    pub fn aoe_covers(
+        &self,
+        aoe: AoE,
+        user: Panel,
+        target: Panel,
+        range: u8,
+        panel: Panel,
+    ) -> bool {
+        aoe.covers(
+            self.arena,
+            user,
+            target,
+            range,
+            |panel| self.unit_height(panel),
+            panel,
+        )
+    }
+
     pub fn height_diff(&self, user_id: CombatantId, target_id: CombatantId) -> f32 {
         self.combatant_height(user_id) - self.combatant_height(target_id)
     }
 
+    /// Like `height_diff`, but going by the edges of the two panels that face each other, so
+    /// that standing at the top or the bottom of a slope counts.
+    pub fn facing_height_diff(&self, user_id: CombatantId, target_id: CombatantId) -> f32 {
+        let user = self.combatant(user_id);
+        let target = self.combatant(target_id);
+        let user_height = self.arena.height_facing(user.panel, target.panel) as f32
+            + float_bonus(&self.tile(user.panel), user);
+        let target_height = self.arena.height_facing(target.panel, user.panel) as f32
+            + float_bonus(&self.tile(target.panel), target);
+        user_height - target_height
+    }
+
     pub fn roll_inclusive(&self, min: i16, max: i16) -> i16 {
         self.rng.borrow_mut().gen_range(min, max + 1)
     }
@@ -615,6 +774,29 @@ impl<'a> Simulation<'a> {
         }
     }
 
+    /// Whether a spell charging for `ctr` ticks will still find `target_id` standing where it
+    /// is now with Reflect up, rather than having had a turn to walk off first.
+    pub fn ai_reflect_holds_for(&self, target_id: CombatantId, ctr: u8) -> bool {
+        let target = self.combatant(target_id);
+        if !target.reflect() {
+            return false;
+        }
+        if let Some(duration) = target.conditions.duration(Condition::Reflect) {
+            if duration as u8 <= ctr {
+                return false;
+            }
+        }
+        if target.movement_blocked() {
+            return true;
+        }
+        let speed = target.ct_speed() as i16;
+        if speed == 0 {
+            return true;
+        }
+        let ticks_until_turn = (100 - (target.ct as i16).min(100) + speed - 1) / speed;
+        ticks_until_turn >= ctr as i16
+    }
+
     pub fn ai_foes_have_non_disabled_units(&self, user: &Combatant) -> bool {
         self.combatants
             .iter()
@@ -645,12 +827,94 @@ impl<'a> Simulation<'a> {
             return;
         }
 
-        if self.ai_cancel_charge_check(user_id) {
-            // TODO: The AI in reality reconsiders here, will have to learn more.
-            self.post_action_move(user_id);
+        let plan = if self.ai_cancel_charge_check(user_id) {
+            match self.ai_replan_charge(user_id) {
+                Some(plan) => Some(plan),
+                None => {
+                    self.post_action_move(user_id);
+                    return;
+                }
+            }
+        } else {
+            self.ai_plan_turn(user_id, true, None)
+        };
+
+        let user = self.combatant(user_id);
+        let acting_cowardly = user.critical() && self.ai_can_be_cowardly(user);
+        let mut targeted_self = false;
+        if let Some(plan) = plan {
+            targeted_self = plan.action.target.to_target_id(self) == Some(user_id);
+            self.ai_perform_plan(user_id, plan);
+        }
+
+        let user = self.combatant(user_id);
+        if !user.healthy() || user.jumping() {
+            return;
+        }
+
+        if user.moved_during_active_turn {
             return;
         }
 
+        if !user.acted_during_active_turn {
+            self.engage_enemy_blindly(user_id);
+
+            // Having closed in, something may be in range now without moving again.
+            let user = self.combatant(user_id);
+            if user.healthy() && user.moved_during_active_turn {
+                if let Some(plan) = self.ai_plan_turn(user_id, false, None) {
+                    self.ai_perform_plan(user_id, plan);
+                }
+            }
+            return;
+        }
+
+        if targeted_self && !acting_cowardly {
+            self.engage_enemy_blindly(user_id);
+            return;
+        }
+
+        self.post_action_move(user_id);
+    }
+
+    /// A unit that is still charging when it gets a turn weighs finishing that charge against
+    /// everything else it could do. Returns a plan only when dropping the charge is worth it.
+    fn ai_replan_charge(&mut self, user_id: CombatantId) -> Option<TurnPlan<'a>> {
+        let user = self.combatant(user_id);
+        if user.action_blocked() {
+            return None;
+        }
+
+        let ignore_confusion = self.enemy_team_only_confused(user);
+        let keep_charging_value = {
+            let mut cloned = self.prediction_clone();
+            cloned.run_slow_actions();
+            ai_target_value_sum(user, &cloned.combatants, ignore_confusion)
+        };
+
+        let charging = self.combatant_mut(user_id).ctr_action.take();
+        let plan = self.ai_plan_turn(user_id, true, Some(keep_charging_value));
+        match (plan, charging) {
+            (Some(plan), Some(slow_action)) => {
+                self.log_event(Event::CancelledCharge(user_id, slow_action.action));
+                Some(plan)
+            }
+            _ => {
+                self.combatant_mut(user_id).ctr_action = charging;
+                None
+            }
+        }
+    }
+
+    /// Picks the best action along with whether to move before or after using it. When
+    /// `can_move` is false only actions in range of where the unit stands are considered.
+    /// A `minimum` value overrides the basis an action has to beat.
+    fn ai_plan_turn(
+        &mut self,
+        user_id: CombatantId,
+        can_move: bool,
+        minimum: Option<f32>,
+    ) -> Option<TurnPlan<'a>> {
         let user = self.combatant(user_id);
         let acting_cowardly = user.critical() && self.ai_can_be_cowardly(user);
         let targets = if acting_cowardly {
@@ -660,11 +924,11 @@ impl<'a> Simulation<'a> {
         };
 
         let ignore_confusion = self.enemy_team_only_confused(user);
-        let basis = {
+        let basis = minimum.unwrap_or_else(|| {
             let mut cloned = self.prediction_clone();
             cloned.run_slow_actions();
             ai_target_value_sum(user, &cloned.combatants, ignore_confusion)
-        };
+        });
 
         {
             let mut actions = self.actions.borrow_mut();
@@ -672,64 +936,61 @@ impl<'a> Simulation<'a> {
             ai_consider_actions(&mut actions, self, user, targets);
         }
 
-        let best_action = self.ai_choose_best_action(user_id, basis, ignore_confusion);
-        let mut targeted_self = false;
-        if let Some(action) = best_action {
+        let best_action = self.ai_choose_best_action(user_id, basis, ignore_confusion, can_move);
+        let plan = best_action.and_then(|action| {
             let user = self.combatant(user_id);
-
-            if let Some(target_id) = action.target.to_target_id(self) {
-                targeted_self = user_id == target_id;
+            let order = match action.target.to_panel(self) {
+                Some(target_panel) if !in_range_panel(user, &action, target_panel) => {
+                    TurnOrder::MoveThenAct
+                }
+                _ => TurnOrder::ActThenMove,
+            };
+            if order == TurnOrder::MoveThenAct && !can_move {
+                return None;
             }
-
+            Some(TurnPlan { action, order })
+        });
+        self.trace_event(Event::ChoseAction(
+            user_id,
+            plan.as_ref().map(|plan| plan.action),
+        ));
+        plan
+    }
+
+    fn ai_perform_plan(&mut self, user_id: CombatantId, plan: TurnPlan<'a>) {
+        let action = plan.action;
+        if plan.order == TurnOrder::MoveThenAct {
             if let Some(target_panel) = action.target.to_panel(self) {
-                if !in_range_panel(user, &action, target_panel) {
-                    self.pre_action_move(user_id, &action, target_panel);
-                }
+                self.pre_action_move(user_id, &action, target_panel);
             }
+        }
 
-            if let Some(mut ctr) = action.ctr {
-                let user = self.combatant(user_id);
-                if user.short_charge() && action.ability.flags & NO_SHORT_CHARGE == 0 {
-                    ctr /= 2;
-                }
+        if let Some(mut ctr) = action.ctr {
+            let user = self.combatant(user_id);
+            if user.short_charge() && action.ability.flags & NO_SHORT_CHARGE == 0 {
+                ctr /= 2;
+            }
+            let mut user = self.combatant_mut(user_id);
+            user.ctr_action = Some(SlowAction {
+                ctr,
+                starting_ctr: ctr,
+                action,
+            });
+            self.log_event(Event::StartedCharging(user_id, action));
+        } else {
+            if let Some(target_panel) = action.target.to_panel(self) {
                 let mut user = self.combatant_mut(user_id);
-                user.ctr_action = Some(SlowAction {
-                    ctr,
-                    starting_ctr: ctr,
-                    action,
-                });
-                self.log_event(Event::StartedCharging(user_id, action));
-            } else {
-                if let Some(target_panel) = action.target.to_panel(self) {
-                    let mut user = self.combatant_mut(user_id);
-                    user.facing = user.panel.facing_towards(target_panel);
-                }
-
-                self.log_event(Event::UsingAbility(user_id, action));
-                perform_action(self, user_id, action);
-
-                if !self.combatant(user_id).monster() && !self.combatant(user_id).mimic() {
-                    self.do_mime_cycle(user_id, action);
-                }
+                user.facing = user.panel.facing_towards(target_panel);
             }
-            self.combatant_mut(user_id).acted_during_active_turn = true;
-        }
-
-        let user = self.combatant(user_id);
-        if !user.healthy() || user.jumping() {
-            return;
-        }
 
-        if user.moved_during_active_turn {
-            return;
-        }
+            self.log_event(Event::UsingAbility(user_id, action));
+            perform_action(self, user_id, action);
 
-        if (targeted_self && !acting_cowardly) || !user.acted_during_active_turn {
-            self.engage_enemy_blindly(user_id);
-            return;
+            if !self.combatant(user_id).monster() && !self.combatant(user_id).mimic() {
+                self.do_mime_cycle(user_id, action);
+            }
         }
-
-        self.post_action_move(user_id);
+        self.combatant_mut(user_id).acted_during_active_turn = true;
     }
 
     pub fn ai_choose_confused_action(&mut self, user_id: CombatantId) -> Option<Action<'a>> {
@@ -755,37 +1016,76 @@ impl<'a> Simulation<'a> {
         user_id: CombatantId,
         basis: f32,
         ignore_confusion: bool,
+        can_move: bool,
     ) -> Option<Action<'a>> {
         let user = self.combatant(user_id);
         if user.confusion() {
             return self.ai_choose_confused_action(user_id);
         }
 
+        // Every candidate starts from this same world, so where the user could move to before
+        // acting only needs working out once.
+        let mut simulated_world = self.prediction_clone();
+        // Looked up for every panel an action could be used from, see `Arena::layer_index`.
+        let mut reachable: Option<Vec<bool>> = None;
+
+        self.trace_event(Event::ChoosingAction(user_id, basis));
         self.actions
             .borrow()
             .iter()
             .flat_map(|action| {
+                let reject = |from, value, rejected| {
+                    self.trace_event(Event::ConsideredAction(
+                        user_id,
+                        *action,
+                        Consideration {
+                            from,
+                            value,
+                            rejected: Some(rejected),
+                        },
+                    ));
+                    None
+                };
+
                 if self.ai_thirteen_rule() {
-                    return None;
+                    return reject(user.panel, None, Rejection::ThirteenRule);
                 }
                 // TODO: This isn't strictly correct..
                 if let Some(target_id) = action.target.to_target_id(self) {
                     if !can_move_into_range(user, action, self.combatant(target_id)) {
-                        return None;
+                        return reject(user.panel, None, Rejection::OutOfRange);
                     }
                 }
 
-                let mut simulated_world = self.prediction_clone();
+                simulated_world.rewind_prediction(self);
                 let user = self.combatant(user_id);
                 if let Some(target_panel) = action.target.to_panel(self) {
                     if !in_range_panel(user, action, target_panel) {
-                        simulated_world.pre_action_move(user_id, action, target_panel);
+                        if !can_move {
+                            return reject(user.panel, None, Rejection::OutOfRange);
+                        }
+                        let reachable = reachable.get_or_insert_with(|| {
+                            let pathfinder = self.calculate_reachable(user);
+                            let mut by_index = vec![false; self.arena.panel_count()];
+                            for panel in pathfinder.reachable_set() {
+                                by_index[self.arena.layer_index(*panel)] = true;
+                            }
+                            by_index
+                        });
+                        let destination =
+                            self.pre_action_destination(user, action, target_panel, |panel| {
+                                self.in_map(panel) && reachable[self.arena.layer_index(panel)]
+                            });
+                        if let Some(panel) = destination {
+                            simulated_world.do_move_with_bounds(user_id, panel);
+                        }
                     }
                     let sim_user = simulated_world.combatant(user_id);
                     if !in_range_panel(sim_user, action, target_panel) {
-                        return None;
+                        return reject(sim_user.panel, None, Rejection::OutOfRange);
                     }
                 }
+                let from = simulated_world.combatant(user_id).panel;
                 perform_action(&mut simulated_world, user_id, *action);
                 simulated_world.run_slow_actions();
                 let new_value = ai_target_value_sum(
@@ -794,8 +1094,17 @@ impl<'a> Simulation<'a> {
                     ignore_confusion,
                 );
                 if new_value <= basis {
-                    return None;
+                    return reject(from, Some(new_value), Rejection::NoImprovement);
                 }
+                self.trace_event(Event::ConsideredAction(
+                    user_id,
+                    *action,
+                    Consideration {
+                        from,
+                        value: Some(new_value),
+                        rejected: None,
+                    },
+                ));
 
                 // FIXME: A hack to get around the whole partial ord thing
                 let ordered_val = (new_value * 1_000_000.0) as i64;
@@ -828,26 +1137,12 @@ impl<'a> Simulation<'a> {
                 for combatant in &mut self.combatants {
                     combatant.damage_took_during_active_turn = None;
                 }
-                let user = self.combatant(user_id);
-                let possible_mime = self.combatant(*c_id);
-
-                if let Some(original_target_panel) = action.target.to_panel(self) {
-                    let original_vec = user.panel.location() - original_target_panel.location();
-                    let original_facing = user.panel.facing_towards(original_target_panel);
-                    let rotations = original_facing.rotations_to(possible_mime.facing);
-                    let vec_on_mime = possible_mime.panel.location() + original_vec;
-                    let new_target_loc =
-                        vec_on_mime.rotate_around(possible_mime.panel.location(), rotations);
+
+                if let Some(target) = action.target.mirror(self, action.ability, user_id, *c_id) {
                     let mut new_action = action;
-                    // TODO: Fix this, be smarter about layer selection...
-                    new_action.target =
-                        ActionTarget::Panel(original_target_panel.on_same_layer(new_target_loc));
+                    new_action.target = target;
                     self.log_event(Event::UsingAbility(*c_id, new_action));
                     perform_action(self, *c_id, new_action);
-                } else {
-                    // If we are using math, then execute the same action.
-                    self.log_event(Event::UsingAbility(*c_id, action));
-                    perform_action(self, *c_id, action);
                 }
             }
         }
@@ -940,7 +1235,7 @@ impl<'a> Simulation<'a> {
 
         if let Some(equip) = weapon {
             // FIXME: Out of all my flag refactoring, this is the part that sucks
-            if equip.chance_to_cancel == 0 && equip.chance_to_cancel == 0 {
+            if equip.chance_to_add == 0 && equip.chance_to_cancel == 0 {
                 return;
             }
 
@@ -1244,12 +1539,13 @@ impl<'a> Simulation<'a> {
 
             Some(WeaponType::Sword)
             | Some(WeaponType::Pole)
+            | Some(WeaponType::Rod)
             | Some(WeaponType::Spear)
             | Some(WeaponType::Crossbow) => user.pa() as i16 + k,
 
             Some(WeaponType::Staff) => user.ma() as i16 + k,
 
-            Some(WeaponType::Flail) | Some(WeaponType::Bag) => {
+            Some(WeaponType::Flail) | Some(WeaponType::Axe) | Some(WeaponType::Bag) => {
                 self.roll_inclusive(1, (user.pa() as i16 + k).max(1))
             }
 
@@ -1269,31 +1565,10 @@ impl<'a> Simulation<'a> {
         }
 
         let best_panel = {
-            self.mark_enemy_occupied_panels(user);
-            let movement_info = MovementInfo::new(user);
-            let mut pathfinder = self.pathfinder.borrow_mut();
-            pathfinder.calculate_reachable_no_reset(&movement_info, user.panel);
-
-            target_panel
-                .diamond(action.range as u8)
-                .flat_map(|panel| {
-                    if action.ability.aoe.is_line() && !panel.lined_up(target_panel) {
-                        return None;
-                    }
-                    if !pathfinder.can_reach_and_end_turn_on(panel) {
-                        return None;
-                    }
-                    let tile = self.tile(panel);
-                    if combatant_submerged(&tile, user) {
-                        return None;
-                    }
-                    let enemy_distance = self.enemy_distance_metric(user, panel.location());
-                    let crystal = self.crystal_metric(panel);
-                    let avoid_aoe = -self.avoid_aoe_metric(panel);
-                    Some((enemy_distance + crystal + avoid_aoe, panel))
-                })
-                .max_by_key(|p| p.0)
-                .map(|p| p.1)
+            let pathfinder = self.calculate_reachable(user);
+            self.pre_action_destination(user, action, target_panel, |panel| {
+                pathfinder.can_reach_and_end_turn_on(panel)
+            })
         };
 
         if let Some(panel) = best_panel {
@@ -1301,16 +1576,42 @@ impl<'a> Simulation<'a> {
         }
     }
 
+    fn pre_action_destination(
+        &self,
+        user: &Combatant,
+        action: &Action,
+        target_panel: Panel,
+        can_end_turn_on: impl Fn(Panel) -> bool,
+    ) -> Option<Panel> {
+        target_panel
+            .diamond(action.range as u8)
+            .flat_map(|panel| {
+                if action.ability.aoe.is_line() && !panel.lined_up(target_panel) {
+                    return None;
+                }
+                if !can_end_turn_on(panel) {
+                    return None;
+                }
+                let tile = self.tile(panel);
+                if combatant_submerged(&tile, user) {
+                    return None;
+                }
+                let enemy_distance = self.enemy_distance_metric(user, panel.location());
+                let crystal = self.crystal_metric(panel);
+                let avoid_aoe = -self.avoid_aoe_metric(user, panel);
+                Some((enemy_distance + crystal + avoid_aoe, panel))
+            })
+            .max_by_key(|p| p.0)
+            .map(|p| p.1)
+    }
+
     fn post_action_move(&mut self, user_id: CombatantId) {
         let user = self.combatant(user_id);
         if user.dont_move() || user.dont_move_while_charging() {
             return;
         }
         let best_panel = {
-            self.mark_enemy_occupied_panels(user);
-            let movement_info = MovementInfo::new(user);
-            let mut pathfinder = self.pathfinder.borrow_mut();
-            pathfinder.calculate_reachable_no_reset(&movement_info, user.panel);
+            let pathfinder = self.calculate_reachable(user);
 
             pathfinder
                 .reachable_set()
@@ -1318,7 +1619,7 @@ impl<'a> Simulation<'a> {
                 .map(|panel| {
                     let enemy_distance = self.enemy_distance_metric(user, panel.location());
                     let crystal = self.crystal_metric(*panel);
-                    let avoid_aoe = -self.avoid_aoe_metric(*panel);
+                    let avoid_aoe = -self.avoid_aoe_metric(user, *panel);
                     // TODO: Add metric based on currently charging slow actions.
                     (enemy_distance + crystal + avoid_aoe, *panel)
                 })
@@ -1363,17 +1664,14 @@ impl<'a> Simulation<'a> {
             .unwrap_or_else(|| Panel::coords(self.arena.width / 2, self.arena.height / 2, false));
 
         let best_panel = {
-            self.mark_enemy_occupied_panels(user);
-            let movement_info = MovementInfo::new(user);
-            let mut pathfinder = self.pathfinder.borrow_mut();
-            pathfinder.calculate_reachable_no_reset(&movement_info, user.panel);
+            let pathfinder = self.calculate_reachable(user);
             pathfinder
                 .reachable_set()
                 .iter()
                 .map(|panel| {
                     let enemy_distance = self.enemy_distance_metric(user, panel.location());
                     let crystal = -self.crystal_metric(*panel);
-                    let avoid_aoe = self.avoid_aoe_metric(*panel);
+                    let avoid_aoe = self.avoid_aoe_metric(user, *panel);
                     // TODO: Add metric based on currently charging slow actions.
                     (enemy_distance + crystal + avoid_aoe, *panel)
                 })
@@ -1396,6 +1694,28 @@ impl<'a> Simulation<'a> {
         }
     }
 
+    /// Everywhere `user` can move to right now, going around enemies and not ending up on
+    /// anyone, left in the pathfinder. Asking again with nobody having moved is free.
+    fn calculate_reachable(&self, user: &Combatant) -> RefMut<'a, Pathfinder<'a>> {
+        let mut blockers = [(user.panel, false); MAX_COMBATANTS];
+        let mut count = 0;
+        for combatant in &self.combatants {
+            if combatant.crystal() {
+                continue;
+            }
+            let impassable = !user.ally(combatant) && !combatant.dead();
+            blockers[count] = (combatant.panel, impassable);
+            count += 1;
+        }
+        let mut pathfinder = self.pathfinder.borrow_mut();
+        pathfinder.calculate_reachable_around(
+            &MovementInfo::new(user),
+            user.panel,
+            &blockers[..count],
+        );
+        pathfinder
+    }
+
     fn mark_enemy_occupied_panels(&self, user: &Combatant) {
         let mut pathfinder = self.pathfinder.borrow_mut();
         pathfinder.reset_all();
@@ -1436,12 +1756,33 @@ impl<'a> Simulation<'a> {
         return 0;
     }
 
-    fn avoid_aoe_metric(&self, panel: Panel) -> i16 {
+    /// How many charging actions would catch `user` if they stood on `panel`.
+    fn avoid_aoe_metric(&self, user: &Combatant, panel: Panel) -> i16 {
+        // Heights as they'd be with `user` moved over to `panel`.
+        let height_of = |other: Panel| {
+            if other == panel {
+                return combatant_height(&self.tile(panel), user);
+            }
+            match self.combatant_on_panel(other) {
+                Some(combatant_id) if combatant_id != user.id() => {
+                    self.combatant_height(combatant_id)
+                }
+                _ => self.height(other),
+            }
+        };
         let mut metric = 0;
         for combatant in &self.combatants {
             if let Some(slow_action) = combatant.ctr_action {
-                if let Some(target_panel) = slow_action.action.target.to_panel(self) {
-                    if slow_action.action.ability.aoe.inside(target_panel, panel) {
+                let action = slow_action.action;
+                if let Some(target_panel) = action.target.to_panel(self) {
+                    if action.ability.aoe.covers(
+                        self.arena,
+                        combatant.panel,
+                        target_panel,
+                        action.range,
+                        height_of,
+                        panel,
+                    ) {
                         metric += 5;
                     }
                 }
@@ -1459,26 +1800,42 @@ impl<'a> Simulation<'a> {
         None
     }
 
+    /// Knocks the target one panel away from the user. It fails if that panel is off the map,
+    /// can't be stood on, is taken, or is any higher than where the target is standing. A drop
+    /// further than the target's Jump costs 10% of their max HP for each height past it.
     pub fn do_knockback(&mut self, user_id: CombatantId, target_id: CombatantId) {
-        // TODO: I just realized you could knock someone up the map lol.
         let user = self.combatant(user_id);
         let target = self.combatant(target_id);
         let direction = user.panel.facing_towards(target.panel).offset();
-        let new_panel = target.panel.plus(direction);
+        let location = target.panel.location() + direction;
+        let new_panel = match self
+            .arena
+            .panel_nearest_height(location, self.height(target.panel))
         {
-            let pathfinder = self.pathfinder.borrow();
-            if !pathfinder.inside_map(new_panel) {
-                return;
-            }
+            Some(panel) => panel,
+            None => return,
+        };
+        if self.tile(new_panel).no_walk || self.combatant_on_panel(new_panel).is_some() {
+            return;
         }
-        if self.combatant_on_panel(new_panel).is_none() {
-            let mut target = self.combatant_mut(target_id);
-            target.panel = new_panel;
-            self.log_event(Event::Knockback(target_id, new_panel));
-            let mut target = self.combatant_mut(target_id);
-            if target.dont_move_while_charging() {
-                target.ctr_action = None;
-            }
+        let climb = self.arena.facing_height_diff(target.panel, new_panel);
+        if climb > 0 {
+            return;
+        }
+        let fall = (-climb) as u8;
+        let jump = target.jump();
+        let max_hp = target.max_hp();
+
+        let mut target = self.combatant_mut(target_id);
+        target.panel = new_panel;
+        self.log_event(Event::Knockback(target_id, new_panel));
+        let mut target = self.combatant_mut(target_id);
+        if target.dont_move_while_charging() {
+            target.ctr_action = None;
+        }
+        if fall > jump {
+            let damage = (max_hp as i32 * (fall - jump) as i32 / 10) as i16;
+            self.change_target_hp(target_id, damage, Source::Constant("Fall"));
         }
     }
 }
@@ -1530,13 +1887,15 @@ pub fn can_move_into_range_line(user: &Combatant, action: &Action, target: &Comb
 // #  - Would be interesting to see if these true positives align with bird's true positives
 
 pub fn combatant_height(tile: &Tile, combatant: &Combatant) -> f32 {
-    let tile_height = tile_height(tile);
-    let float_bonus = if combatant.float() {
+    tile_height(tile) + float_bonus(tile, combatant)
+}
+
+fn float_bonus(tile: &Tile, combatant: &Combatant) -> f32 {
+    if combatant.float() {
         0.5 + tile.depth as f32
     } else {
         0.0
-    };
-    tile_height + float_bonus
+    }
 }
 
 pub fn combatant_submerged(tile: &Tile, combatant: &Combatant) -> bool {
@@ -1549,3 +1908,540 @@ pub fn combatant_submerged(tile: &Tile, combatant: &Combatant) -> bool {
 pub fn tile_height(tile: &Tile) -> f32 {
     tile.height as f32 + tile.slope_height as f32 / 2.0
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::rust::MatchUp;
+    use crate::dto::test_support::{arena_with, flat_arena, sample_equipment, sim_with};
+    use rand::SeedableRng;
+
+    #[test]
+    pub fn forecasts_turns_in_resolve_order() {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                // Unit 6 starts with a head start on everyone else.
+                sim.combatant_mut(CombatantId::new(6)).ct = 48;
+                let speed = sim.combatant(CombatantId::new(0)).ct_speed() as usize;
+                let first_turn = (100.0 / speed as f32).ceil() as usize;
+
+                let turns = sim.forecast_turns(9);
+                let order: Vec<_> = turns
+                    .iter()
+                    .map(|turn| match turn.phase {
+                        Phase::ActiveTurn(id) => id.index(),
+                        _ => panic!("nobody is charging anything"),
+                    })
+                    .collect();
+                assert_eq!(order, vec![6, 0, 4, 5, 1, 2, 7, 3, 6]);
+                assert!(turns[0].clock_tick < first_turn);
+                assert_eq!(turns[1].clock_tick, first_turn);
+                assert_eq!(turns[7].clock_tick, first_turn);
+                // After a turn spent moving & acting, units are left with whatever CT was over 100.
+                let left_over = 48 + turns[0].clock_tick * speed - 100;
+                let second_turn =
+                    turns[0].clock_tick + ((100 - left_over) as f32 / speed as f32).ceil() as usize;
+                assert_eq!(turns[8].clock_tick, second_turn);
+                // Forecasting doesn't touch the simulation itself.
+                assert_eq!(sim.clock_tick, 0);
+                assert_eq!(sim.combatant(CombatantId::new(6)).ct, 48);
+            },
+        );
+    }
+
+    /// Puts unit 0 at `user`, its allies in the far corner and the enemies at `foes`.
+    fn place_for_planning(sim: &mut Simulation, user: (u8, u8), foes: [(u8, u8); 4]) {
+        sim.combatant_mut(CombatantId::new(0)).panel = Panel::coords(user.0, user.1, false);
+        for idx in 1..4 {
+            sim.combatant_mut(CombatantId::new(idx)).panel = Panel::coords(7, 8 - idx, false);
+        }
+        for (idx, (x, y)) in foes.iter().enumerate() {
+            sim.combatant_mut(CombatantId::new(4 + idx as u8)).panel = Panel::coords(*x, *y, false);
+        }
+    }
+
+    /// The plans unit 0 comes up with over a handful of seeds, since the thirteen rule skips
+    /// candidates at random.
+    fn plans_over_seeds<'a>(sim: &mut Simulation<'a>, can_move: bool) -> Vec<Option<TurnPlan<'a>>> {
+        (0..8)
+            .map(|seed| {
+                *sim.rng.borrow_mut() = SmallRng::seed_from_u64(seed);
+                sim.ai_plan_turn(CombatantId::new(0), can_move, None)
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn acts_then_moves_when_already_in_range() {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                place_for_planning(sim, (3, 3), [(3, 6), (6, 3), (0, 3), (3, 0)]);
+                let plans = plans_over_seeds(sim, true);
+                assert!(plans.iter().any(Option::is_some));
+                for plan in plans.iter().flatten() {
+                    assert_eq!(plan.order, TurnOrder::ActThenMove);
+                    let target = plan.action.target.to_panel(sim).unwrap();
+                    assert!(in_range_panel(
+                        sim.combatant(CombatantId::new(0)),
+                        &plan.action,
+                        target
+                    ));
+                }
+            },
+        );
+    }
+
+    #[test]
+    pub fn moves_then_acts_when_out_of_range() {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                place_for_planning(sim, (0, 0), [(0, 7), (2, 7), (4, 7), (6, 7)]);
+                let plans = plans_over_seeds(sim, true);
+                assert!(plans.iter().any(Option::is_some));
+                for plan in plans.iter().flatten() {
+                    assert_eq!(plan.order, TurnOrder::MoveThenAct);
+                }
+                // Having already moved, there is nothing left to do from here.
+                assert!(plans_over_seeds(sim, false).iter().all(Option::is_none));
+            },
+        );
+    }
+
+    #[test]
+    pub fn replans_a_charge_only_when_something_beats_it() {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                sim.log = Log::new();
+                let user_id = CombatantId::new(0);
+                place_for_planning(sim, (3, 3), [(3, 6), (6, 3), (0, 3), (3, 0)]);
+                let plan = plans_over_seeds(sim, true)
+                    .into_iter()
+                    .flatten()
+                    .next()
+                    .unwrap();
+                let target_id = plan.action.target.to_target_id(sim).unwrap();
+                sim.ai_perform_plan(user_id, plan);
+                assert!(sim.combatant(user_id).ctr_action.is_some());
+
+                // Nothing has changed, so there's no reason to start over.
+                for seed in 0..8 {
+                    *sim.rng.borrow_mut() = SmallRng::seed_from_u64(seed);
+                    assert!(sim.ai_replan_charge(user_id).is_none());
+                    assert!(sim.combatant(user_id).ctr_action.is_some());
+                }
+
+                // Someone else fells the target before the spell goes off.
+                sim.change_target_hp(target_id, 999, Source::Phase);
+                assert!(sim.combatant(target_id).dead());
+                let replanned = (0..8).find_map(|seed| {
+                    *sim.rng.borrow_mut() = SmallRng::seed_from_u64(seed);
+                    sim.ai_replan_charge(user_id)
+                });
+                assert!(replanned.is_some());
+                assert!(sim.combatant(user_id).ctr_action.is_none());
+                assert!(sim.log.entries().iter().any(|entry| match entry.event {
+                    Event::CancelledCharge(id, _) => id == user_id,
+                    _ => false,
+                }));
+            },
+        );
+    }
+
+    #[test]
+    pub fn rewound_predictions_match_fresh_ones() {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                let user_id = CombatantId::new(0);
+                place_for_planning(sim, (3, 3), [(3, 5), (5, 3), (1, 3), (3, 1)]);
+                let mut actions = vec![];
+                let user = sim.combatant(user_id);
+                ai_consider_actions(&mut actions, sim, user, &sim.combatants);
+                assert!(actions.len() > 4);
+
+                // Reusing one world has to score every candidate the same as cloning anew.
+                let mut reused = sim.prediction_clone();
+                for action in actions {
+                    reused.rewind_prediction(sim);
+                    let mut fresh = sim.prediction_clone();
+                    for world in &mut [&mut reused, &mut fresh] {
+                        perform_action(world, user_id, action);
+                        world.run_slow_actions();
+                    }
+                    assert_eq!(reused.battle_state(), fresh.battle_state());
+                    assert_eq!(
+                        ai_target_value_sum(user, &reused.combatants, false),
+                        ai_target_value_sum(user, &fresh.combatants, false)
+                    );
+                    let roll = |world: &Simulation| world.rng.borrow_mut().gen::<u32>();
+                    assert_eq!(roll(&reused), roll(&fresh));
+                }
+            },
+        );
+    }
+
+    /// Not verified against replays: there is no replay data in the tree. This only pins what
+    /// seed 0 does on the opening turns of a mirror match, to catch planning changes that
+    /// weren't meant.
+    #[test]
+    pub fn opening_turns_stay_as_pinned() {
+        use crate::dto::test_support::facing_off_arena;
+
+        sim_with(
+            &facing_off_arena(),
+            |_| {},
+            |sim| {
+                sim.log = Log::new();
+                for _ in 0..13 {
+                    sim.tick();
+                }
+                let actions: Vec<_> = sim
+                    .log
+                    .entries()
+                    .iter()
+                    .filter_map(|entry| match entry.event {
+                        Event::StartedCharging(id, action) | Event::UsingAbility(id, action) => {
+                            Some((entry.clock_tick, id.index(), action.ability.name))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(
+                    actions,
+                    vec![
+                        (13, 0, "Fire"),
+                        (13, 4, "Fire"),
+                        (13, 5, "Fire"),
+                        (13, 1, "Bolt 2"),
+                        (13, 2, "Bolt 2"),
+                        (13, 7, "Bolt 2"),
+                        (13, 3, "Bolt 2")
+                    ]
+                );
+                // Unit 6 found nothing worth casting, so held on to an extra 20 CT.
+                let cts: Vec<_> = sim.combatants.iter().map(|c| c.ct).collect();
+                assert_eq!(cts, vec![4, 4, 4, 4, 4, 4, 24, 4]);
+            },
+        );
+    }
+
+    /// Unit 0 at (0, 0) knocks unit 4 at (1, 0), standing at height `from`, towards (2, 0) at
+    /// height `to`. Returns where unit 4 ends up and their HP.
+    fn knock_back(from: u8, to: u8) -> (Panel, i16) {
+        let arena = arena_with(|lower, upper| {
+            for layer in &mut [lower, upper] {
+                layer[1].height = from;
+                layer[2].height = to;
+            }
+        });
+        sim_with(
+            &arena,
+            |_| {},
+            |sim| {
+                for idx in 0..8 {
+                    sim.combatant_mut(CombatantId::new(idx)).panel = Panel::coords(idx, 7, false);
+                }
+                sim.combatant_mut(CombatantId::new(0)).panel = Panel::coords(0, 0, false);
+                sim.combatant_mut(CombatantId::new(4)).panel = Panel::coords(1, 0, false);
+                sim.do_knockback(CombatantId::new(0), CombatantId::new(4));
+                let target = sim.combatant(CombatantId::new(4));
+                (target.panel, target.hp())
+            },
+        )
+    }
+
+    #[test]
+    pub fn knockback_fails_uphill() {
+        assert_eq!(knock_back(1, 2), (Panel::coords(1, 0, false), 150));
+    }
+
+    #[test]
+    pub fn knockback_hurts_past_jump() {
+        assert_eq!(knock_back(1, 0), (Panel::coords(2, 0, false), 150));
+        // Dropping 8 is 5 past a Jump of 3, so 50% of max HP.
+        assert_eq!(knock_back(9, 1), (Panel::coords(2, 0, false), 75));
+    }
+
+    #[test]
+    pub fn weapons_that_only_add_a_condition_still_add_it() {
+        // Leaked to outlive the simulation, whose lifetime `sim_with` picks.
+        let weapon: &'static Equipment = Box::leak(Box::new(Equipment {
+            chance_to_add: Condition::Poison.flag(),
+            ..sample_equipment()
+        }));
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                let poisoned = (0..50).filter(|seed| {
+                    *sim.rng.borrow_mut() = SmallRng::seed_from_u64(*seed);
+                    let target = CombatantId::new(4);
+                    sim.cancel_condition(target, Condition::Poison, Source::Phase);
+                    sim.weapon_chance_to_add_or_cancel_status(
+                        CombatantId::new(0),
+                        Some(weapon),
+                        target,
+                    );
+                    sim.combatant(target).poison()
+                });
+                // 19% of the time.
+                assert!((3..=17).contains(&poisoned.count()));
+            },
+        );
+    }
+
+    #[test]
+    pub fn avoids_aoe_by_the_movers_own_height() {
+        // Whoever floats over the deep water at (3, 4) is well out of Fire's tolerance.
+        let arena = arena_with(|lower, _upper| lower[4 * 8 + 3].depth = 3);
+        sim_with(
+            &arena,
+            |_| {},
+            |sim| {
+                let center = Panel::coords(3, 3, false);
+                let water = Panel::coords(3, 4, false);
+                let info = sim.combatant(CombatantId::new(4)).info;
+                let fire = info
+                    .abilities
+                    .iter()
+                    .find(|ability| ability.name == "Fire")
+                    .unwrap();
+                sim.combatant_mut(CombatantId::new(4)).ctr_action = Some(SlowAction {
+                    ctr: 3,
+                    starting_ctr: 3,
+                    action: Action::target_panel(fire, 5, Some(3), center),
+                });
+                let floater = sim.combatant_mut(CombatantId::new(1));
+                floater.panel = water;
+                floater.add_condition(Condition::Float);
+
+                let walker = sim.combatant(CombatantId::new(0));
+                assert_eq!(sim.avoid_aoe_metric(walker, water), 5);
+                let floater = sim.combatant(CombatantId::new(1));
+                assert_eq!(sim.avoid_aoe_metric(floater, water), 0);
+            },
+        );
+    }
+
+    #[test]
+    pub fn aims_diamonds_at_the_right_side_of_a_bridge() {
+        use crate::sim::actions::talk_skill::TALK_SKILL_ABILITIES;
+
+        let arena = arena_with(|_lower, upper| {
+            for tile in &mut upper[2..5] {
+                tile.height = 8;
+            }
+        });
+        sim_with(
+            &arena,
+            |_| {},
+            |sim| {
+                for idx in 0..8 {
+                    sim.combatant_mut(CombatantId::new(idx)).panel = Panel::coords(idx, 7, false);
+                }
+                sim.combatant_mut(CombatantId::new(0)).panel = Panel::coords(0, 0, false);
+                // Mimic Daravon only reaches 2 up or down from where it lands.
+                let ability = TALK_SKILL_ABILITIES
+                    .iter()
+                    .find(|ability| ability.name == "Mimic Daravon")
+                    .unwrap();
+                let mut aimed_at = |target_panel: Panel| {
+                    sim.combatant_mut(CombatantId::new(4)).panel = target_panel;
+                    let user = sim.combatant(CombatantId::new(0));
+                    let target = sim.combatant(CombatantId::new(4));
+                    let mut actions = vec![];
+                    ability
+                        .implementation
+                        .consider(&mut actions, ability, sim, user, target);
+                    actions
+                        .iter()
+                        .map(|action| action.target.to_panel(sim).unwrap())
+                        .collect::<Vec<_>>()
+                };
+
+                let under = aimed_at(Panel::coords(3, 0, false));
+                assert_eq!(under.len(), 4);
+                assert!(under.iter().all(|panel| !panel.layer()));
+
+                let over = aimed_at(Panel::coords(3, 0, true));
+                assert_eq!(
+                    over,
+                    vec![
+                        Panel::coords(2, 0, true),
+                        Panel::coords(3, 0, true),
+                        Panel::coords(4, 0, true)
+                    ]
+                );
+            },
+        );
+    }
+
+    fn status_sim(support_skill: &str, check: impl FnOnce(&mut Simulation)) {
+        let tweak = |match_up: &mut MatchUp| {
+            match_up.left.combatants[0].support_skill = support_skill.to_owned();
+        };
+        sim_with(&flat_arena(), tweak, |sim| {
+            sim.log = Log::new();
+            check(sim);
+        });
+    }
+
+    #[test]
+    pub fn short_and_long_status_scale_durations() {
+        let haste_for = |support_skill| {
+            let mut duration = None;
+            status_sim(support_skill, |sim| {
+                let id = CombatantId::new(0);
+                sim.add_condition(id, Condition::Haste, Source::Phase);
+                duration = sim.combatant(id).conditions.duration(Condition::Haste);
+            });
+            duration
+        };
+        assert_eq!(haste_for("Short Charge"), Some(32));
+        assert_eq!(haste_for("Short Status"), Some(16));
+        assert_eq!(haste_for("Long Status"), Some(48));
+    }
+
+    #[test]
+    pub fn reapplying_restarts_the_countdown() {
+        status_sim("", |sim| {
+            let id = CombatantId::new(0);
+            sim.add_condition(id, Condition::Haste, Source::Phase);
+            for _ in 0..10 {
+                sim.phase_status_check();
+            }
+            assert_eq!(
+                sim.combatant(id).conditions.duration(Condition::Haste),
+                Some(22)
+            );
+            sim.add_condition(id, Condition::Haste, Source::Phase);
+            assert_eq!(
+                sim.combatant(id).conditions.duration(Condition::Haste),
+                Some(32)
+            );
+        });
+    }
+
+    #[test]
+    pub fn opposite_conditions_replace_each_other() {
+        status_sim("", |sim| {
+            let id = CombatantId::new(0);
+            sim.add_condition(id, Condition::Haste, Source::Phase);
+            sim.add_condition(id, Condition::Slow, Source::Phase);
+            assert!(!sim.combatant(id).haste());
+            assert!(sim.combatant(id).slow());
+            sim.add_condition(id, Condition::Regen, Source::Phase);
+            sim.add_condition(id, Condition::Poison, Source::Phase);
+            assert!(!sim.combatant(id).regen());
+            assert!(sim.combatant(id).poison());
+        });
+    }
+
+    #[test]
+    pub fn status_check_wears_conditions_off_in_order() {
+        status_sim("", |sim| {
+            let id = CombatantId::new(0);
+            // Stop lasts 20, Don't Move & Don't Act 24, so those two go together.
+            sim.add_condition(id, Condition::DontAct, Source::Phase);
+            sim.add_condition(id, Condition::Stop, Source::Phase);
+            sim.add_condition(id, Condition::DontMove, Source::Phase);
+            assert!(sim.combatant(id).action_blocked());
+            assert!(sim.combatant(id).movement_blocked());
+            for _ in 0..24 {
+                sim.phase_status_check();
+            }
+            assert!(!sim.combatant(id).action_blocked());
+            assert!(!sim.combatant(id).movement_blocked());
+            let lost: Vec<_> = sim
+                .log
+                .entries()
+                .iter()
+                .filter_map(|entry| match entry.event {
+                    Event::LostCondition(_, condition, _) => Some((entry.clock_tick, condition)),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(
+                lost,
+                vec![
+                    (20, Condition::Stop),
+                    (24, Condition::DontMove),
+                    (24, Condition::DontAct)
+                ]
+            );
+        });
+    }
+
+    /// Runs one active turn for the first left unit with `condition` on, then checks it.
+    fn turn_with(condition: Option<Condition>, check: impl FnOnce(&Combatant)) {
+        status_sim("", |sim| {
+            let id = CombatantId::new(0);
+            if let Some(condition) = condition {
+                sim.add_condition(id, condition, Source::Phase);
+            }
+            for combatant in &mut sim.combatants {
+                combatant.ct = 0;
+            }
+            sim.combatant_mut(id).ct = 100;
+            sim.phase_active_turn_resolve();
+            check(sim.combatant(id));
+        });
+    }
+
+    #[test]
+    pub fn a_free_unit_moves_and_acts() {
+        turn_with(None, |after| {
+            assert!(after.moved_during_active_turn);
+            assert!(after.acted_during_active_turn);
+        });
+    }
+
+    #[test]
+    pub fn stopped_and_sleeping_units_get_no_turn() {
+        for condition in &[Condition::Stop, Condition::Sleep] {
+            turn_with(Some(*condition), |after| {
+                // A turn spent doing nothing would leave 40 CT behind.
+                assert_eq!(after.ct, 0, "{:?}", condition);
+                assert!(!after.moved_during_active_turn, "{:?}", condition);
+                assert!(!after.acted_during_active_turn, "{:?}", condition);
+            });
+        }
+    }
+
+    #[test]
+    pub fn dont_move_keeps_the_unit_in_place() {
+        turn_with(Some(Condition::DontMove), |after| {
+            assert!(!after.moved_during_active_turn);
+            assert!(after.acted_during_active_turn);
+        });
+    }
+
+    #[test]
+    pub fn dont_act_leaves_the_unit_only_moving() {
+        turn_with(Some(Condition::DontAct), |after| {
+            assert!(after.moved_during_active_turn);
+            assert!(!after.acted_during_active_turn);
+        });
+    }
+
+    #[test]
+    pub fn reflect_holds_on_units_that_cannot_walk_off() {
+        status_sim("", |sim| {
+            let id = CombatantId::new(0);
+            sim.add_condition(id, Condition::Reflect, Source::Phase);
+            sim.combatant_mut(id).ct = 0;
+            assert!(!sim.ai_reflect_holds_for(id, 20));
+            sim.add_condition(id, Condition::DontMove, Source::Phase);
+            assert!(sim.ai_reflect_holds_for(id, 20));
+        });
+    }
+}
@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{
+    Combatant, Condition, Facing, Location, Panel, Simulation, ALL_CONDITIONS, MAX_COMBATANTS,
+};
+
+/// A battle part way through, e.g. as read off the stream, so it can be run out from there.
+/// Everything not in here (equipment, abilities, stats) comes from the match up as usual.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BattleState {
+    pub clock_tick: usize,
+    /// The left team's four units then the right team's, in match up order.
+    pub combatants: Vec<CombatantState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CombatantState {
+    pub hp: i16,
+    pub mp: i16,
+    pub ct: u8,
+    pub x: i16,
+    pub y: i16,
+    #[serde(default)]
+    pub upper: bool,
+    /// 0 to 3 for North, East, South & West.
+    pub facing: Facing,
+    #[serde(default)]
+    pub conditions: Vec<ConditionState>,
+    pub brave: i8,
+    pub faith: i8,
+    #[serde(default)]
+    pub speed_mod: i8,
+    #[serde(default)]
+    pub pa_mod: i8,
+    #[serde(default)]
+    pub ma_mod: i8,
+}
+
+/// A condition by its display name, e.g. "Don't Move". `remaining` is how many status checks
+/// a timed condition has left, the Death Sentence counter, or for Death the crystal counter.
+/// Without it the condition starts fresh, as if it had just been added.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConditionState {
+    pub name: String,
+    #[serde(default)]
+    pub remaining: Option<i8>,
+}
+
+/// Conditions that come from something else: HP for Critical & Death, and the slow action
+/// being waited on for the rest. A BattleState can't say which spell is being charged, so
+/// anyone mid charge just gets their turn back.
+const DERIVED_CONDITIONS: &[Condition] = &[
+    Condition::Critical,
+    Condition::Death,
+    Condition::Charging,
+    Condition::Jumping,
+    Condition::Performing,
+];
+
+/// A `ConditionState` with its name looked up.
+type ParsedCondition = (Condition, Option<i8>);
+
+impl BattleState {
+    /// Puts the clock and every unit in `sim` into the state described here. Nothing
+    /// changes unless the whole state fits.
+    pub fn apply(&self, sim: &mut Simulation) -> Result<(), String> {
+        if self.combatants.len() != MAX_COMBATANTS {
+            return Err(format!(
+                "expected {} combatants, got {}",
+                MAX_COMBATANTS,
+                self.combatants.len()
+            ));
+        }
+        let mut placed: Vec<(Panel, Vec<ParsedCondition>)> = vec![];
+        for (combatant, state) in sim.combatants.iter().zip(&self.combatants) {
+            let location = Location::new(state.x, state.y);
+            if !sim.arena.in_map(location) {
+                return Err(format!(
+                    "{} is off the map at ({}, {})",
+                    combatant.name(),
+                    state.x,
+                    state.y
+                ));
+            }
+            if state.upper && !sim.arena.has_upper(location) {
+                return Err(format!(
+                    "{} is on an upper layer that isn't there at ({}, {})",
+                    combatant.name(),
+                    state.x,
+                    state.y
+                ));
+            }
+            let panel = Panel::new(location, state.upper);
+            if sim.arena.tile(panel).no_walk {
+                return Err(format!(
+                    "{} is on a tile nobody can stand on at ({}, {})",
+                    combatant.name(),
+                    state.x,
+                    state.y
+                ));
+            }
+            let conditions = state.parse_conditions()?;
+            // Crystals can be walked onto, so they're the only ones who share a panel.
+            let crystal =
+                |conditions: &[ParsedCondition]| conditions.contains(&(Condition::Death, Some(0)));
+            let stacked = placed.iter().position(|(other, other_conditions)| {
+                *other == panel && !crystal(&conditions) && !crystal(other_conditions)
+            });
+            if let Some(other) = stacked {
+                return Err(format!(
+                    "{} and {} are both at ({}, {})",
+                    sim.combatants[other].name(),
+                    combatant.name(),
+                    state.x,
+                    state.y
+                ));
+            }
+            placed.push((panel, conditions));
+        }
+
+        let units = sim.combatants.iter_mut().zip(&self.combatants);
+        for ((combatant, state), (panel, conditions)) in units.zip(placed) {
+            state.apply(combatant, &conditions);
+            combatant.panel = panel;
+        }
+        sim.clock_tick = self.clock_tick;
+        Ok(())
+    }
+}
+
+impl CombatantState {
+    fn parse_conditions(&self) -> Result<Vec<ParsedCondition>, String> {
+        self.conditions
+            .iter()
+            .map(|state| {
+                Condition::parse(&state.name)
+                    .map(|condition| (condition, state.remaining))
+                    .ok_or_else(|| format!("unknown condition {:?}", state.name))
+            })
+            .collect()
+    }
+
+    fn apply(&self, combatant: &mut Combatant, conditions: &[ParsedCondition]) {
+        combatant.ctr_action = None;
+        combatant.set_hp_within_bounds(self.hp);
+        combatant.set_mp_within_bounds(self.mp);
+        combatant.ct = self.ct;
+        combatant.facing = self.facing;
+        combatant.raw_brave = self.brave;
+        combatant.raw_faith = self.faith;
+        combatant.speed_mod = self.speed_mod;
+        combatant.pa_mod = self.pa_mod;
+        combatant.ma_mod = self.ma_mod;
+
+        for condition in ALL_CONDITIONS.iter() {
+            combatant.conditions.remove(*condition);
+        }
+        for (condition, remaining) in conditions {
+            let condition = *condition;
+            match (condition, *remaining) {
+                (Condition::Death, Some(remaining)) => combatant.crystal_counter = remaining,
+                (Condition::DeathSentence, remaining) => {
+                    combatant.add_condition(condition);
+                    if let Some(remaining) = remaining {
+                        combatant.death_sentence_counter = remaining;
+                    }
+                }
+                _ if DERIVED_CONDITIONS.contains(&condition) => {}
+                (_, Some(remaining)) if condition.is_timed_condition() => combatant
+                    .conditions
+                    .add(condition, Some(i32::from(remaining))),
+                _ => combatant.add_condition(condition),
+            }
+        }
+    }
+
+    pub fn of(combatant: &Combatant) -> CombatantState {
+        let mut conditions = vec![];
+        for condition in ALL_CONDITIONS.iter() {
+            let remaining = match condition {
+                Condition::Death if combatant.dead() => Some(combatant.crystal_counter),
+                Condition::DeathSentence if combatant.death_sentence() => {
+                    Some(combatant.death_sentence_counter)
+                }
+                _ if combatant.conditions.status_flags & condition.flag() == 0 => continue,
+                _ => combatant.conditions.duration(*condition),
+            };
+            conditions.push(ConditionState {
+                name: condition.name().to_owned(),
+                remaining,
+            });
+        }
+        CombatantState {
+            hp: combatant.hp(),
+            mp: combatant.mp(),
+            ct: combatant.ct,
+            x: combatant.panel.location().x,
+            y: combatant.panel.location().y,
+            upper: combatant.panel.layer(),
+            facing: combatant.facing,
+            conditions,
+            brave: combatant.raw_brave,
+            faith: combatant.raw_faith,
+            speed_mod: combatant.speed_mod,
+            pa_mod: combatant.pa_mod,
+            ma_mod: combatant.ma_mod,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::test_support::{arena_with, flat_arena, sim_with};
+    use crate::sim::CombatantId;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    /// Plays `ticks` clock ticks of a fresh match, then hands its state and the starting
+    /// units to `check`.
+    fn mid_battle(ticks: usize, check: impl FnOnce(&Simulation, &Simulation)) {
+        sim_with(
+            &flat_arena(),
+            |_| {},
+            |sim| {
+                let fresh = sim.clone();
+                *fresh.rng.borrow_mut() = SmallRng::seed_from_u64(1);
+                for (i, combatant) in sim.combatants.iter_mut().enumerate() {
+                    combatant.panel =
+                        Panel::new(Location::new(i as i16, (i % 2) as i16 * 5), false);
+                }
+                for _ in 0..ticks {
+                    sim.tick();
+                }
+                check(sim, &fresh);
+            },
+        );
+    }
+
+    #[test]
+    pub fn snapshots_restore_to_the_same_state() {
+        mid_battle(60, |sim, fresh| {
+            let state = sim.battle_state();
+            assert_eq!(state.clock_tick, 60);
+            let restored = Simulation::from_state(
+                fresh.combatants,
+                fresh.arena,
+                fresh.pathfinder,
+                SmallRng::seed_from_u64(2),
+                false,
+                &state,
+            )
+            .unwrap();
+            assert_eq!(restored.battle_state(), state);
+        });
+    }
+
+    #[test]
+    pub fn states_read_from_json() {
+        mid_battle(0, |_sim, fresh| {
+            let unit = |x| {
+                format!(
+                    r#"{{"hp": 20, "mp": 5, "ct": 90, "x": {}, "y": 4, "facing": 2,
+                        "brave": 70, "faith": 65,
+                        "conditions": [{{"name": "Haste", "remaining": 5}},
+                                       {{"name": "Death Sentence"}}, {{"name": "Charging"}}]}}"#,
+                    x
+                )
+            };
+            let units: Vec<_> = (0..8).map(unit).collect();
+            let json = format!(
+                r#"{{"clock_tick": 120, "combatants": [{}]}}"#,
+                units.join(",")
+            );
+            let state: BattleState = serde_json::from_str(&json).unwrap();
+            let mut sim = fresh.clone();
+            state.apply(&mut sim).unwrap();
+
+            assert_eq!(sim.clock_tick, 120);
+            let unit = sim.combatant(CombatantId::new(5));
+            assert_eq!(unit.hp(), 20);
+            assert_eq!(unit.mp(), 5);
+            assert_eq!(unit.ct, 90);
+            assert_eq!(unit.panel, Panel::new(Location::new(5, 4), false));
+            assert_eq!(unit.facing, Facing::South);
+            assert!(unit.critical());
+            assert_eq!(unit.conditions.duration(Condition::Haste), Some(5));
+            assert!(unit.death_sentence());
+            assert_eq!(unit.death_sentence_counter, 4);
+            assert!(!unit.charging());
+        });
+    }
+
+    #[test]
+    pub fn rejects_states_that_do_not_fit() {
+        mid_battle(0, |sim, fresh| {
+            let state = sim.battle_state();
+
+            let mut short = state.clone();
+            short.combatants.pop();
+            assert!(short.apply(&mut fresh.clone()).is_err());
+
+            let mut off_map = state.clone();
+            off_map.combatants[2].x = 8;
+            assert!(off_map.apply(&mut fresh.clone()).is_err());
+
+            let mut stacked = state.clone();
+            stacked.combatants[6].x = stacked.combatants[1].x;
+            stacked.combatants[6].y = stacked.combatants[1].y;
+            assert!(stacked.apply(&mut fresh.clone()).is_err());
+            // Unless one of them is a crystal someone has walked onto.
+            stacked.combatants[1].hp = 0;
+            stacked.combatants[1].conditions.push(ConditionState {
+                name: "Death".to_owned(),
+                remaining: Some(0),
+            });
+            assert!(stacked.apply(&mut fresh.clone()).is_ok());
+
+            // Nothing is applied when a later unit doesn't fit.
+            let mut unknown = state.clone();
+            unknown.combatants[7].conditions.push(ConditionState {
+                name: "Sleepy".to_owned(),
+                remaining: None,
+            });
+            let mut untouched = fresh.clone();
+            let before = untouched.battle_state();
+            let err = unknown.apply(&mut untouched).unwrap_err();
+            assert!(err.contains("Sleepy"));
+            assert_eq!(untouched.battle_state(), before);
+            assert_ne!(before, state);
+        });
+    }
+
+    #[test]
+    pub fn rejects_units_on_tiles_nobody_can_stand_on() {
+        let arena = arena_with(|lower, _upper| lower[0].no_walk = true);
+        sim_with(
+            &arena,
+            |_| {},
+            |sim| {
+                let mut state = sim.battle_state();
+                for (i, unit) in state.combatants.iter_mut().enumerate() {
+                    unit.x = i as i16;
+                    unit.y = 1;
+                }
+                assert!(state.apply(&mut sim.clone()).is_ok());
+                state.combatants[3].y = 0;
+                state.combatants[3].x = 0;
+                let err = state.apply(sim).unwrap_err();
+                assert!(err.contains("(0, 0)"));
+            },
+        );
+    }
+}
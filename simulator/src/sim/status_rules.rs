@@ -0,0 +1,303 @@
+use crate::dto::rust::Patch;
+use crate::sim::{Condition, ConditionFlags, TIMED_CONDITIONS, TIMED_CONDITIONS_LEN};
+
+/// When a condition counts down towards wearing off.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum StatusTick {
+    /// Stays until something removes it.
+    Never,
+    /// Once per clock tick, at the start of the status check phase.
+    StatusCheck,
+    /// At the start of the unit's own active turn.
+    ActiveTurn,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct StatusRule {
+    pub condition: Condition,
+    /// How many status checks it lasts without Short Status or Long Status.
+    pub duration: Option<i32>,
+    /// Conditions removed when this one is added.
+    pub cancels: &'static [Condition],
+    pub tick: StatusTick,
+    pub blocks_action: bool,
+    pub blocks_movement: bool,
+}
+
+const fn rule(
+    condition: Condition,
+    duration: Option<i32>,
+    cancels: &'static [Condition],
+    tick: StatusTick,
+    blocks_action: bool,
+    blocks_movement: bool,
+) -> StatusRule {
+    StatusRule {
+        condition,
+        duration,
+        cancels,
+        tick,
+        blocks_action,
+        blocks_movement,
+    }
+}
+
+const fn timed(condition: Condition, duration: i32, cancels: &'static [Condition]) -> StatusRule {
+    rule(
+        condition,
+        Some(duration),
+        cancels,
+        StatusTick::StatusCheck,
+        false,
+        false,
+    )
+}
+
+const fn lasting(condition: Condition, cancels: &'static [Condition]) -> StatusRule {
+    rule(condition, None, cancels, StatusTick::Never, false, false)
+}
+
+pub const STATUS_RULES_LEN: usize = Condition::DeathToCaster.index() + 1;
+
+/// One row per condition, in `Condition` order.
+pub const STATUS_RULES: [StatusRule; STATUS_RULES_LEN] = [
+    rule(
+        Condition::Stop,
+        Some(20),
+        &[],
+        StatusTick::StatusCheck,
+        true,
+        true,
+    ),
+    rule(
+        Condition::Sleep,
+        Some(60),
+        &[Condition::Charging],
+        StatusTick::StatusCheck,
+        true,
+        true,
+    ),
+    timed(Condition::Slow, 24, &[Condition::Haste]),
+    timed(Condition::Shell, 32, &[]),
+    timed(Condition::Regen, 36, &[Condition::Poison]),
+    timed(Condition::Reflect, 32, &[]),
+    timed(Condition::Protect, 32, &[]),
+    timed(Condition::Poison, 36, &[Condition::Regen]),
+    timed(Condition::Innocent, 32, &[Condition::Faith]),
+    timed(Condition::Haste, 32, &[Condition::Slow]),
+    timed(Condition::Faith, 32, &[Condition::Innocent]),
+    rule(
+        Condition::DontMove,
+        Some(24),
+        &[],
+        StatusTick::StatusCheck,
+        false,
+        true,
+    ),
+    rule(
+        Condition::DontAct,
+        Some(24),
+        &[],
+        StatusTick::StatusCheck,
+        true,
+        false,
+    ),
+    timed(Condition::Charm, 32, &[Condition::Charging]),
+    lasting(Condition::Chicken, &[Condition::Charging]),
+    lasting(Condition::Frog, &[Condition::Charging]),
+    // Charging, Jumping & Performing last until the slow action goes off.
+    lasting(Condition::Charging, &[]),
+    lasting(Condition::Berserk, &[Condition::Charging]),
+    rule(
+        Condition::Petrify,
+        None,
+        &[
+            Condition::Charging,
+            Condition::DeathSentence,
+            Condition::Transparent,
+        ],
+        StatusTick::Never,
+        true,
+        true,
+    ),
+    rule(Condition::Jumping, None, &[], StatusTick::Never, true, true),
+    lasting(Condition::Undead, &[]),
+    lasting(Condition::Silence, &[]),
+    lasting(Condition::Oil, &[]),
+    lasting(Condition::Reraise, &[]),
+    lasting(Condition::Wall, &[]),
+    lasting(Condition::Darkness, &[]),
+    // Dying cancels everything in DEATH_CANCELS, that's handled along with the HP. The
+    // crystal counter is what ticks down on active turns.
+    rule(
+        Condition::Death,
+        None,
+        &[],
+        StatusTick::ActiveTurn,
+        true,
+        true,
+    ),
+    lasting(Condition::BloodSuck, &[]),
+    lasting(Condition::Confusion, &[]),
+    lasting(Condition::Critical, &[]),
+    rule(
+        Condition::DeathSentence,
+        None,
+        &[],
+        StatusTick::ActiveTurn,
+        false,
+        false,
+    ),
+    rule(
+        Condition::Defending,
+        None,
+        &[],
+        StatusTick::ActiveTurn,
+        false,
+        false,
+    ),
+    lasting(Condition::Float, &[]),
+    lasting(Condition::Performing, &[]),
+    lasting(Condition::Evil, &[]),
+    lasting(Condition::Transparent, &[]),
+    lasting(Condition::DeathToCaster, &[]),
+];
+
+/// Every condition whose rule has `blocks_action`.
+pub const ACTION_BLOCKERS: ConditionFlags = blockers(false);
+/// Every condition whose rule has `blocks_movement`.
+pub const MOVEMENT_BLOCKERS: ConditionFlags = blockers(true);
+
+const fn blockers(movement: bool) -> ConditionFlags {
+    let mut flags = 0;
+    let mut idx = 0;
+    while idx < STATUS_RULES_LEN {
+        let rule = &STATUS_RULES[idx];
+        if (movement && rule.blocks_movement) || (!movement && rule.blocks_action) {
+            flags |= rule.condition.flag();
+        }
+        idx += 1;
+    }
+    flags
+}
+
+/// The conditions that count down at `tick`, in `Condition` order.
+pub fn ticking_at(tick: StatusTick) -> impl Iterator<Item = Condition> {
+    STATUS_RULES
+        .iter()
+        .filter(move |rule| rule.tick == tick)
+        .map(|rule| rule.condition)
+}
+
+impl Condition {
+    pub fn rule(self) -> &'static StatusRule {
+        &STATUS_RULES[self.index()]
+    }
+}
+
+/// How long timed conditions last in a given patch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusRules {
+    durations: [i32; TIMED_CONDITIONS_LEN],
+}
+
+/// The longest a patch can make a condition last. Long Status makes it last half again as long,
+/// and the countdown has to fit in an i8.
+pub const MAX_STATUS_DURATION: i32 = i8::MAX as i32 * 4 / 6;
+
+impl StatusRules {
+    pub fn standard() -> StatusRules {
+        let mut durations = [0; TIMED_CONDITIONS_LEN];
+        for condition in &TIMED_CONDITIONS {
+            durations[condition.index()] = condition.rule().duration.unwrap();
+        }
+        StatusRules { durations }
+    }
+
+    /// The standard durations with some replaced, conditions that don't time out are ignored.
+    pub fn with_overrides(overrides: &[(Condition, i32)]) -> StatusRules {
+        let mut rules = StatusRules::standard();
+        for (condition, duration) in overrides {
+            if condition.is_timed_condition() {
+                rules.durations[condition.index()] = *duration;
+            }
+        }
+        rules
+    }
+
+    /// The standard durations with whatever FFTBattleground changed in `patch`.
+    pub fn for_patch(patch: &Patch) -> StatusRules {
+        StatusRules::with_overrides(&patch.status_durations)
+    }
+
+    pub fn duration(&self, condition: Condition) -> Option<i32> {
+        if condition.is_timed_condition() {
+            Some(self.durations[condition.index()])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::dto::test_support::sample_patch;
+    use crate::sim::ALL_CONDITIONS;
+
+    #[test]
+    pub fn rules_are_in_condition_order() {
+        for condition in ALL_CONDITIONS.iter().chain(&[Condition::DeathToCaster]) {
+            assert_eq!(condition.rule().condition, *condition);
+        }
+        assert_eq!(ALL_CONDITIONS.len() + 1, STATUS_RULES.len());
+    }
+
+    #[test]
+    pub fn timed_conditions_wear_off_in_the_status_check() {
+        for rule in &STATUS_RULES {
+            let timed = rule.condition.is_timed_condition();
+            assert_eq!(rule.duration.is_some(), timed, "{:?}", rule.condition);
+            assert_eq!(
+                rule.tick == StatusTick::StatusCheck,
+                timed,
+                "{:?}",
+                rule.condition
+            );
+        }
+    }
+
+    #[test]
+    pub fn opposites_cancel_each_other() {
+        let pairs = [
+            (Condition::Haste, Condition::Slow),
+            (Condition::Regen, Condition::Poison),
+            (Condition::Faith, Condition::Innocent),
+        ];
+        for (a, b) in &pairs {
+            assert!(a.cancels().contains(b));
+            assert!(b.cancels().contains(a));
+        }
+        for rule in &STATUS_RULES {
+            assert!(!rule.cancels.contains(&rule.condition));
+        }
+    }
+
+    #[test]
+    pub fn patches_override_durations() {
+        let mut patch = sample_patch();
+        assert_eq!(StatusRules::for_patch(&patch), StatusRules::standard());
+        patch.status_durations = vec![(Condition::Stop, 12)];
+        assert_eq!(
+            StatusRules::for_patch(&patch).duration(Condition::Stop),
+            Some(12)
+        );
+        assert_eq!(StatusRules::standard().duration(Condition::Haste), Some(32));
+        assert_eq!(StatusRules::standard().duration(Condition::Berserk), None);
+
+        let rules = StatusRules::with_overrides(&[(Condition::Haste, 24), (Condition::Oil, 8)]);
+        assert_eq!(rules.duration(Condition::Haste), Some(24));
+        assert_eq!(rules.duration(Condition::Slow), Some(24));
+        assert_eq!(rules.duration(Condition::Oil), None);
+    }
+}